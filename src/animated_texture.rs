@@ -0,0 +1,105 @@
+//
+/// The number of animated texture slots in the section (it is always a fixed size table)
+//
+pub const ANIMATED_TEXTURE_SLOT_COUNT : usize = 4;
+
+//
+/// The size of a single slot in the section, in bytes
+//
+pub const ANIMATED_TEXTURE_SLOT_SIZE : usize = 8;
+
+//
+/// A single animated texture slot from the model header.
+///
+/// The header field at 0x2C points to a fixed table of 4 slots, each 8 bytes:
+/// [ssss] [cccc] [ffff ffff]
+/// s: size of a single frame of the texture in bytes
+/// c: number of frames
+/// f: frame rate (f32, frames per second)
+///
+/// The frames are stored back to back in the texture data, so a slot with
+/// 4 frames of 0x200 bytes covers 0x800 bytes of texture data.
+/// Slots which are unused are all zero.
+//
+#[derive(Copy, Clone, Debug)]
+pub struct AnimatedTexture
+{
+    //
+    /// The slot within the table (0-3)
+    //
+    m_slot : u8,
+
+    //
+    /// The size of a single frame, in bytes
+    //
+    m_frame_size : u16,
+
+    //
+    /// The number of frames in the animation
+    //
+    m_frame_count : u16,
+
+    //
+    /// How fast the animation plays, in frames per second
+    //
+    m_frame_rate : f32
+}
+
+impl AnimatedTexture
+{
+    //
+    /// Create a new animated texture slot
+    //
+    pub fn new (slot : u8, frame_size : u16, frame_count : u16, frame_rate : f32) -> AnimatedTexture
+    {
+        return AnimatedTexture {
+            m_slot: slot,
+            m_frame_size: frame_size,
+            m_frame_count: frame_count,
+            m_frame_rate: frame_rate
+        };
+    }
+
+    // --- public properties
+
+    pub fn slot (&self) -> u8 {
+        return self.m_slot;
+    }
+
+    pub fn frame_size (&self) -> u16 {
+        return self.m_frame_size;
+    }
+
+    pub fn frame_count (&self) -> u16 {
+        return self.m_frame_count;
+    }
+
+    pub fn frame_rate (&self) -> f32 {
+        return self.m_frame_rate;
+    }
+
+    //
+    /// If the slot is actually used (unused slots have no frames)
+    //
+    pub fn is_active (&self) -> bool {
+        return self.m_frame_count != 0 && self.m_frame_size != 0;
+    }
+
+    //
+    /// The total amount of texture data covered by all the frames, in bytes
+    //
+    pub fn total_size (&self) -> u32 {
+        return self.m_frame_size as u32 * self.m_frame_count as u32;
+    }
+
+    //
+    /// How long a full loop of the animation takes, in seconds (0 if it doesnt animate)
+    //
+    pub fn loop_duration (&self) -> f32 {
+        if self.m_frame_rate <= 0.0 {
+            return 0.0;
+        }
+
+        return self.m_frame_count as f32 / self.m_frame_rate;
+    }
+}
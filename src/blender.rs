@@ -0,0 +1,230 @@
+use crate::other_mode;
+
+// The RDP blender, set by the render mode in the lower other mode word (G_SetOtherMode_L).
+//
+// Each cycle works out (P * A + M * B) / (A + B), where P and M are colors (the combiner's
+// output, the framebuffer, the blend or fog color) and A and B are how much of each to use.
+// Without FORCE_BL the last cycle only blends at the edges of triangles, so inside a
+// triangle the pixel is just P.
+
+//
+/// The colors and alphas the blender can pick from, as 0-1 RGBA
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BlenderInputs
+{
+    //
+    /// The combiner's output (or the first cycle's, in the second cycle)
+    //
+    pub input: [f64; 4],
+
+    //
+    /// What is in the framebuffer
+    //
+    pub memory: [f64; 4],
+
+    pub blend_color: [f64; 4],
+    pub fog_color: [f64; 4],
+    pub shade_alpha: f64
+}
+
+//
+/// The render mode part of the lower other mode word
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderMode
+{
+    m_mode: u32
+}
+
+impl RenderMode
+{
+    //
+    /// Wrap the lower other mode word
+    //
+    pub fn new (other_mode_low : u32) -> RenderMode
+    {
+        return RenderMode { m_mode: other_mode_low };
+    }
+
+    //
+    /// If the pixel is tested against the Z buffer
+    //
+    pub fn z_compare (&self) -> bool
+    { return self.m_mode & other_mode::Z_CMP != 0; }
+
+    //
+    /// If the pixel's depth is written to the Z buffer
+    //
+    pub fn z_update (&self) -> bool
+    { return self.m_mode & other_mode::Z_UPD != 0; }
+
+    //
+    /// The Z mode (other_mode::ZMODE_OPA, ZMODE_INTER, ZMODE_XLU or ZMODE_DEC)
+    //
+    pub fn z_mode (&self) -> u32
+    { return self.m_mode & other_mode::ZMODE_MASK; }
+
+    //
+    /// The alpha compare mode (0 for none, other_mode::G_AC_THRESHOLD or G_AC_DITHER)
+    //
+    pub fn alpha_compare (&self) -> u32
+    { return other_mode::field(self.m_mode, other_mode::G_MDSFT_ALPHACOMPARE, 2); }
+
+    //
+    /// If the pixel's coverage is multiplied by its alpha (cutout textures)
+    //
+    pub fn coverage_times_alpha (&self) -> bool
+    { return self.m_mode & other_mode::CVG_X_ALPHA != 0; }
+
+    //
+    /// If the blender runs on every pixel, not just the edges of triangles
+    //
+    pub fn force_blend (&self) -> bool
+    { return self.m_mode & other_mode::FORCE_BL != 0; }
+
+    //
+    /// Run the blender for a cycle. The last cycle only blends when FORCE_BL is on.
+    /// Returns the color, with the alpha it covers the framebuffer with.
+    //
+    pub fn blend (&self, cycle : usize, last_cycle : bool, inputs : &BlenderInputs) -> [f64; 4]
+    {
+        let cycle = cycle.min(1) as u32;
+        let p = other_mode::field(self.m_mode, 30 - cycle * 2, 2);
+        let a = other_mode::field(self.m_mode, 26 - cycle * 2, 2);
+        let m = other_mode::field(self.m_mode, 22 - cycle * 2, 2);
+        let b = other_mode::field(self.m_mode, 18 - cycle * 2, 2);
+
+        let first_color = p_color(p, inputs);
+        if last_cycle && !self.force_blend() {
+            return [first_color[0], first_color[1], first_color[2], 1.0];
+        }
+
+        // the selectors are 2 bits, so the last arm is the one value left
+        let a_value = match a {
+            other_mode::G_BL_A_FOG => inputs.fog_color[3],
+            other_mode::G_BL_A_SHADE => inputs.shade_alpha,
+            other_mode::G_BL_0 => 0.0,
+            _ => inputs.input[3]
+        };
+        let b_value = match b {
+            other_mode::G_BL_A_MEM => inputs.memory[3],
+            other_mode::G_BL_1 => 1.0,
+            other_mode::G_BL_0 => 0.0,
+            _ => 1.0 - a_value
+        };
+
+        let second_color = p_color(m, inputs);
+        let total = a_value + b_value;
+        if total <= 0.0 {
+            return [first_color[0], first_color[1], first_color[2], 1.0];
+        }
+
+        let mut out = [0.0; 4];
+        for channel in 0 .. 3 {
+            out[channel] = ((first_color[channel] * a_value + second_color[channel] * b_value) / total).clamp(0.0, 1.0);
+        }
+
+        // how much the result covers: blending over the framebuffer keeps what was already there
+        out[3] = if m == other_mode::G_BL_CLR_MEM && b == other_mode::G_BL_1MA {
+            (a_value + inputs.memory[3] * (1.0 - a_value)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        return out;
+    }
+}
+
+fn p_color (index : u32, inputs : &BlenderInputs) -> [f64; 4]
+{
+    return match index {
+        other_mode::G_BL_CLR_MEM => inputs.memory,
+        other_mode::G_BL_CLR_BL => inputs.blend_color,
+        other_mode::G_BL_CLR_FOG => inputs.fog_color,
+        _ => inputs.input
+    };
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::other_mode::*;
+
+    //
+    /// A render mode with the same P, A, M and B for both cycles
+    //
+    fn p_mode (p : u32, a : u32, m : u32, b : u32, flags : u32) -> RenderMode
+    {
+        let both = |value : u32, shift : u32| -> u32 { (value << (shift + 2)) | (value << shift) };
+        return RenderMode::new(both(p, 28) | both(a, 24) | both(m, 20) | both(b, 16) | flags);
+    }
+
+    fn p_assert_near (got : [f64; 4], expected : [f64; 4])
+    {
+        assert!((0 .. 4).all(|c| (got[c] - expected[c]).abs() < 1e-9), "{:?} != {:?}", got, expected);
+    }
+
+    fn p_inputs () -> BlenderInputs
+    {
+        return BlenderInputs {
+            input: [1.0, 0.0, 0.0, 0.25],
+            memory: [0.0, 0.0, 1.0, 1.0],
+            blend_color: [0.0, 1.0, 0.0, 0.5],
+            fog_color: [1.0, 1.0, 1.0, 0.5],
+            shade_alpha: 0.75
+        };
+    }
+
+    #[test]
+    fn opaque_surfaces_keep_the_input ()
+    {
+        // G_RM_AA_ZB_OPA_SURF: without FORCE_BL the last cycle only blends at the edges
+        let mode = p_mode(G_BL_CLR_IN, G_BL_A_IN, G_BL_CLR_MEM, G_BL_A_MEM, AA_EN | Z_CMP | Z_UPD);
+        p_assert_near(mode.blend(0, true, &p_inputs()), [1.0, 0.0, 0.0, 1.0]);
+
+        // but a first cycle always blends
+        p_assert_near(mode.blend(0, false, &p_inputs()), [0.2, 0.0, 0.8, 1.0]);
+    }
+
+    #[test]
+    fn translucent_surfaces_blend_over_memory ()
+    {
+        // G_RM_ZB_XLU_SURF: input * alpha + memory * (1 - alpha)
+        let mode = p_mode(G_BL_CLR_IN, G_BL_A_IN, G_BL_CLR_MEM, G_BL_1MA, Z_CMP | ZMODE_XLU | FORCE_BL);
+        p_assert_near(mode.blend(0, true, &p_inputs()), [0.25, 0.0, 0.75, 1.0]);
+
+        // over nothing the coverage is just the input's
+        let mut empty = p_inputs();
+        empty.memory = [0.0; 4];
+        p_assert_near(mode.blend(0, true, &empty), [0.25, 0.0, 0.0, 0.25]);
+    }
+
+    #[test]
+    fn fogs_with_the_shade_alpha ()
+    {
+        // G_RM_FOG_SHADE_A: fog * shade alpha + input * (1 - shade alpha)
+        let mode = p_mode(G_BL_CLR_FOG, G_BL_A_SHADE, G_BL_CLR_IN, G_BL_1MA, FORCE_BL);
+        p_assert_near(mode.blend(0, true, &p_inputs()), [1.0, 0.75, 0.75, 1.0]);
+
+        // the blend color, with the fog's alpha and all of memory
+        let mode = p_mode(G_BL_CLR_BL, G_BL_A_FOG, G_BL_CLR_MEM, G_BL_1, FORCE_BL);
+        p_assert_near(mode.blend(0, true, &p_inputs()), [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0]);
+    }
+
+    #[test]
+    fn nothing_to_blend_keeps_p ()
+    {
+        let mode = p_mode(G_BL_CLR_BL, G_BL_0, G_BL_CLR_MEM, G_BL_0, FORCE_BL);
+        p_assert_near(mode.blend(0, true, &p_inputs()), [0.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn reads_the_other_flags ()
+    {
+        let mode = p_mode(0, 0, 0, 0, Z_CMP | ZMODE_DEC | CVG_X_ALPHA | (G_AC_THRESHOLD << G_MDSFT_ALPHACOMPARE));
+        assert!(mode.z_compare() && !mode.z_update() && mode.coverage_times_alpha() && !mode.force_blend());
+        assert_eq!(mode.z_mode(), ZMODE_DEC);
+        assert_eq!(mode.alpha_compare(), G_AC_THRESHOLD);
+    }
+}
@@ -0,0 +1,369 @@
+// The RDP color combiner, set by G_SETCOMBINE.
+//
+// Each cycle works out (A - B) * C + D, once for the color and once for the alpha,
+// where A-D each pick an input (the texels, shade, primitive and environment colors...).
+// In two cycle mode the second cycle can use the first cycle's result (COMBINED).
+
+//
+/// The inputs the combiner can pick from, as 0-1 RGBA
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CombinerInputs
+{
+    pub texel0: [f64; 4],
+    pub texel1: [f64; 4],
+    pub shade: [f64; 4],
+    pub primitive: [f64; 4],
+    pub environment: [f64; 4],
+
+    //
+    /// How far between mipmap levels the pixel is
+    //
+    pub lod_fraction: f64,
+
+    //
+    /// The LOD fraction from G_SETPRIMCOLOR
+    //
+    pub primitive_lod_fraction: f64
+}
+
+//
+/// What to combine for one cycle: the A, B, C and D inputs for the color and for the alpha
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CombineCycle
+{
+    pub color: [u8; 4],
+    pub alpha: [u8; 4]
+}
+
+//
+/// The combiner settings for both cycles
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CombineMode
+{
+    pub cycles: [CombineCycle; 2]
+}
+
+impl Default for CombineMode
+{
+    //
+    /// G_CC_MODULATERGBA for both cycles: texel0 * shade
+    //
+    fn default () -> CombineMode
+    {
+        let modulate = CombineCycle { color: [1, 15, 4, 7], alpha: [1, 7, 4, 7] };
+        return CombineMode { cycles: [modulate, modulate] };
+    }
+}
+
+impl CombineMode
+{
+    //
+    /// Decode the combiner settings from a G_SETCOMBINE command
+    //
+    pub fn from_command (cmd : [u8; 8]) -> CombineMode
+    {
+        // FC [aaaa ccccc AAA CCC aaaa ccccc] [bbbb bbbb AAA CCC ddd BBB DDD ddd BBB DDD]
+        // lower case are color, upper case alpha. Each pair is cycle 1 then cycle 2 (b is b1 b2)
+        let high = u32::from_be_bytes([0, cmd[1], cmd[2], cmd[3]]);
+        let low = u32::from_be_bytes([cmd[4], cmd[5], cmd[6], cmd[7]]);
+        let bits = |word : u32, shift : u32, length : u32| -> u8 { ((word >> shift) & ((1 << length) - 1)) as u8 };
+
+        let first = CombineCycle {
+            color: [bits(high, 20, 4), bits(low, 28, 4), bits(high, 15, 5), bits(low, 15, 3)],
+            alpha: [bits(high, 12, 3), bits(low, 12, 3), bits(high, 9, 3), bits(low, 9, 3)]
+        };
+        let second = CombineCycle {
+            color: [bits(high, 5, 4), bits(low, 24, 4), bits(high, 0, 5), bits(low, 6, 3)],
+            alpha: [bits(low, 21, 3), bits(low, 3, 3), bits(low, 18, 3), bits(low, 0, 3)]
+        };
+
+        return CombineMode { cycles: [first, second] };
+    }
+
+    //
+    /// Run the combiner for a cycle (0 or 1). combined is what the first cycle produced, for the second cycle to use.
+    //
+    pub fn evaluate (&self, cycle : usize, inputs : &CombinerInputs, combined : [f64; 4]) -> [f64; 4]
+    {
+        let settings = &self.cycles[cycle.min(1)];
+
+        let mut out = [0.0; 4];
+        for (channel, value) in out.iter_mut().enumerate().take(3) {
+            let a = p_color_a(settings.color[0], inputs, combined)[channel];
+            let b = p_color_b(settings.color[1], inputs, combined)[channel];
+            let c = p_color_c(settings.color[2], inputs, combined)[channel];
+            let d = p_color_d(settings.color[3], inputs, combined)[channel];
+            *value = ((a - b) * c + d).clamp(0.0, 1.0);
+        }
+
+        let a = p_alpha_abd(settings.alpha[0], inputs, combined);
+        let b = p_alpha_abd(settings.alpha[1], inputs, combined);
+        let c = p_alpha_c(settings.alpha[2], inputs, combined);
+        let d = p_alpha_abd(settings.alpha[3], inputs, combined);
+        out[3] = ((a - b) * c + d).clamp(0.0, 1.0);
+
+        return out;
+    }
+
+    //
+    /// If either cycle reads TEXEL1
+    //
+    pub fn uses_texel1 (&self, two_cycle : bool) -> bool
+    {
+        let cycles = if two_cycle { &self.cycles[..] } else { &self.cycles[.. 1] };
+        return cycles.iter().any(|c| {
+            c.color[0] == 2 || c.color[1] == 2 || c.color[2] == 2 || c.color[2] == 9 || c.color[3] == 2
+                || c.alpha.contains(&2)
+        });
+    }
+
+    //
+    /// The equations as text, ex. (TEXEL0 - 0) * SHADE + 0
+    //
+    pub fn describe (&self) -> String
+    {
+        let mut parts = Vec::<String>::new();
+        for (i, cycle) in self.cycles.iter().enumerate() {
+            parts.push(format!("color{}:({} - {}) * {} + {}", i + 1,
+                p_color_a_name(cycle.color[0]), p_color_b_name(cycle.color[1]),
+                p_color_c_name(cycle.color[2]), p_color_d_name(cycle.color[3])
+            ));
+            parts.push(format!("alpha{}:({} - {}) * {} + {}", i + 1,
+                p_alpha_abd_name(cycle.alpha[0]), p_alpha_abd_name(cycle.alpha[1]),
+                p_alpha_c_name(cycle.alpha[2]), p_alpha_abd_name(cycle.alpha[3])
+            ));
+        }
+        return parts.join(" ");
+    }
+}
+
+// --- inputs
+
+const COMMON_NAMES : [&str; 6] = ["COMBINED", "TEXEL0", "TEXEL1", "PRIMITIVE", "SHADE", "ENVIRONMENT"];
+
+fn p_common (index : u8, inputs : &CombinerInputs, combined : [f64; 4]) -> Option<[f64; 4]>
+{
+    return match index {
+        0 => Some(combined),
+        1 => Some(inputs.texel0),
+        2 => Some(inputs.texel1),
+        3 => Some(inputs.primitive),
+        4 => Some(inputs.shade),
+        5 => Some(inputs.environment),
+        _ => None
+    };
+}
+
+fn p_splat (value : f64) -> [f64; 4]
+{
+    return [value; 4];
+}
+
+fn p_color_a (index : u8, inputs : &CombinerInputs, combined : [f64; 4]) -> [f64; 4]
+{
+    return p_common(index, inputs, combined).unwrap_or_else(|| p_splat(if index == 6 { 1.0 } else { 0.0 }));
+}
+
+fn p_color_a_name (index : u8) -> &'static str
+{
+    return match index {
+        0 ..= 5 => COMMON_NAMES[index as usize],
+        6 => "1",
+        7 => "NOISE",
+        _ => "0"
+    };
+}
+
+fn p_color_b (index : u8, inputs : &CombinerInputs, combined : [f64; 4]) -> [f64; 4]
+{
+    // 6 and 7 are the chroma key center and K4, which we dont support
+    return p_common(index, inputs, combined).unwrap_or_else(|| p_splat(0.0));
+}
+
+fn p_color_b_name (index : u8) -> &'static str
+{
+    return match index {
+        0 ..= 5 => COMMON_NAMES[index as usize],
+        6 => "CENTER",
+        7 => "K4",
+        _ => "0"
+    };
+}
+
+fn p_color_c (index : u8, inputs : &CombinerInputs, combined : [f64; 4]) -> [f64; 4]
+{
+    if let Some(color) = p_common(index, inputs, combined) {
+        return color;
+    }
+
+    return match index {
+        7 => p_splat(combined[3]),
+        8 => p_splat(inputs.texel0[3]),
+        9 => p_splat(inputs.texel1[3]),
+        10 => p_splat(inputs.primitive[3]),
+        11 => p_splat(inputs.shade[3]),
+        12 => p_splat(inputs.environment[3]),
+        13 => p_splat(inputs.lod_fraction),
+        14 => p_splat(inputs.primitive_lod_fraction),
+        _ => p_splat(0.0) // 6 and 15 are the chroma key scale and K5
+    };
+}
+
+fn p_color_c_name (index : u8) -> &'static str
+{
+    return match index {
+        0 ..= 5 => COMMON_NAMES[index as usize],
+        6 => "SCALE",
+        7 => "COMBINED_ALPHA",
+        8 => "TEXEL0_ALPHA",
+        9 => "TEXEL1_ALPHA",
+        10 => "PRIMITIVE_ALPHA",
+        11 => "SHADE_ALPHA",
+        12 => "ENV_ALPHA",
+        13 => "LOD_FRACTION",
+        14 => "PRIM_LOD_FRAC",
+        15 => "K5",
+        _ => "0"
+    };
+}
+
+fn p_color_d (index : u8, inputs : &CombinerInputs, combined : [f64; 4]) -> [f64; 4]
+{
+    return p_common(index, inputs, combined).unwrap_or_else(|| p_splat(if index == 6 { 1.0 } else { 0.0 }));
+}
+
+fn p_color_d_name (index : u8) -> &'static str
+{
+    return match index {
+        0 ..= 5 => COMMON_NAMES[index as usize],
+        6 => "1",
+        _ => "0"
+    };
+}
+
+fn p_alpha_abd (index : u8, inputs : &CombinerInputs, combined : [f64; 4]) -> f64
+{
+    return match p_common(index, inputs, combined) {
+        Some(color) => color[3],
+        None => if index == 6 { 1.0 } else { 0.0 }
+    };
+}
+
+fn p_alpha_abd_name (index : u8) -> &'static str
+{
+    return p_color_d_name(index);
+}
+
+fn p_alpha_c (index : u8, inputs : &CombinerInputs, combined : [f64; 4]) -> f64
+{
+    return match index {
+        0 => inputs.lod_fraction,
+        6 => inputs.primitive_lod_fraction,
+        7 => 0.0,
+        _ => p_common(index, inputs, combined).map(|c| c[3]).unwrap_or(0.0)
+    };
+}
+
+fn p_alpha_c_name (index : u8) -> &'static str
+{
+    return match index {
+        0 => "LOD_FRACTION",
+        1 ..= 5 => COMMON_NAMES[index as usize],
+        6 => "PRIM_LOD_FRAC",
+        _ => "0"
+    };
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn p_assert_near (got : [f64; 4], expected : [f64; 4])
+    {
+        assert!((0 .. 4).all(|c| (got[c] - expected[c]).abs() < 1e-9), "{:?} != {:?}", got, expected);
+    }
+
+    fn p_inputs () -> CombinerInputs
+    {
+        return CombinerInputs {
+            texel0: [0.1, 0.1, 0.1, 0.3],
+            texel1: [0.9, 0.8, 0.7, 0.6],
+            shade: [0.5, 0.25, 1.0, 0.5],
+            primitive: [0.8, 0.6, 0.4, 1.0],
+            environment: [0.2, 0.2, 0.2, 0.25],
+            lod_fraction: 0.75,
+            primitive_lod_fraction: 0.125
+        };
+    }
+
+    #[test]
+    fn evaluates_a_minus_b_times_c_plus_d ()
+    {
+        // color (PRIMITIVE - ENVIRONMENT) * SHADE + TEXEL0, alpha (1 - TEXEL0) * SHADE + ENVIRONMENT
+        let cycle = CombineCycle { color: [3, 5, 4, 1], alpha: [6, 1, 4, 5] };
+        let mode = CombineMode { cycles: [cycle, cycle] };
+        p_assert_near(mode.evaluate(0, &p_inputs(), [0.0; 4]), [0.4, 0.2, 0.3, 0.6]);
+    }
+
+    #[test]
+    fn clamps_the_result ()
+    {
+        // (1 - 0) * 1 + 1 and (0 - 1) * 1 + 0
+        let over = CombineCycle { color: [6, 8, 6, 6], alpha: [6, 7, 6, 6] };
+        let under = CombineCycle { color: [8, 3, 6, 7], alpha: [7, 6, 6, 7] };
+        p_assert_near(CombineMode { cycles: [over, over] }.evaluate(0, &p_inputs(), [0.0; 4]), [1.0; 4]);
+        p_assert_near(CombineMode { cycles: [under, under] }.evaluate(0, &p_inputs(), [0.0; 4]), [0.0; 4]);
+    }
+
+    #[test]
+    fn reads_alphas_and_lod_fractions_as_c ()
+    {
+        // (TEXEL1 - TEXEL0) * LOD_FRACTION + TEXEL0, then TEXEL0_ALPHA, PRIM_LOD_FRAC and (for the alpha) LOD_FRACTION
+        let inputs = p_inputs();
+        let lerp = CombineCycle { color: [2, 1, 13, 1], alpha: [2, 1, 0, 1] };
+        p_assert_near(CombineMode { cycles: [lerp, lerp] }.evaluate(0, &inputs, [0.0; 4]), [0.7, 0.625, 0.55, 0.525]);
+
+        let alpha = CombineCycle { color: [6, 8, 8, 7], alpha: [6, 7, 6, 7] };
+        p_assert_near(CombineMode { cycles: [alpha, alpha] }.evaluate(0, &inputs, [0.0; 4]), [0.3, 0.3, 0.3, 0.125]);
+
+        let primitive_lod = CombineCycle { color: [6, 8, 14, 7], alpha: [6, 7, 7, 7] };
+        p_assert_near(CombineMode { cycles: [primitive_lod, primitive_lod] }.evaluate(0, &inputs, [0.0; 4]), [0.125, 0.125, 0.125, 0.0]);
+    }
+
+    #[test]
+    fn second_cycle_reads_the_first ()
+    {
+        // cycle 1 TEXEL0 * SHADE, cycle 2 COMBINED * PRIMITIVE
+        let first = CombineCycle { color: [1, 15, 4, 7], alpha: [1, 7, 4, 7] };
+        let second = CombineCycle { color: [0, 15, 3, 7], alpha: [0, 7, 3, 7] };
+        let mode = CombineMode { cycles: [first, second] };
+
+        let inputs = p_inputs();
+        let combined = mode.evaluate(0, &inputs, [0.0; 4]);
+        p_assert_near(combined, [0.05, 0.025, 0.1, 0.15]);
+        p_assert_near(mode.evaluate(1, &inputs, combined), [0.04, 0.015, 0.04, 0.15]);
+    }
+
+    #[test]
+    fn decodes_g_setcombine ()
+    {
+        // G_CC_MODULATERGBA for both cycles
+        let mode = CombineMode::from_command([0xFC, 0x12, 0x18, 0x24, 0xFF, 0x33, 0xFF, 0xFF]);
+        assert_eq!(mode, CombineMode::default());
+        assert_eq!(mode.describe(),
+            "color1:(TEXEL0 - 0) * SHADE + 0 alpha1:(TEXEL0 - 0) * SHADE + 0 \
+             color2:(TEXEL0 - 0) * SHADE + 0 alpha2:(TEXEL0 - 0) * SHADE + 0");
+        assert!(!mode.uses_texel1(true));
+
+        // G_CC_TRILERP, G_CC_MODULATERGBA2 (the mipmap blend, then COMBINED * SHADE)
+        let mode = CombineMode::from_command([0xFC, 0x26, 0xA0, 0x04, 0x1F, 0x10, 0x93, 0xFF]);
+        assert_eq!(mode.cycles[0], CombineCycle { color: [2, 1, 13, 1], alpha: [2, 1, 0, 1] });
+        assert_eq!(mode.cycles[1], CombineCycle { color: [0, 15, 4, 7], alpha: [0, 7, 4, 7] });
+        assert!(mode.uses_texel1(true));
+        assert!(mode.uses_texel1(false));
+    }
+}
@@ -5,13 +5,31 @@ use clap::Clap;
 #[clap(version="1.0", author="Kenneth Perry <thothonegan@gmail.com>")]
 pub struct CommandLineOptions {
 
+    /// The model (or other asset) to work on
     #[clap(short, long)]
-    pub filename: String,
+    pub filename: Option<String>,
+
+    #[clap(flatten)]
+    pub memory: MemoryOptions,
 
     #[clap(subcommand)]
     pub sub_command: SubCommand
 }
 
+// For loading the model out of an RDRAM dump instead of a model file
+#[derive(Clap)]
+pub struct MemoryOptions
+{
+    /// Treat --filename as an RDRAM dump, and load the model at this address (ex. 0x80201230)
+    #[clap(long)]
+    pub address: Option<String>,
+
+    /// Where a segment pointed when the model was drawn, as segment=address (ex. 1=0x80201400).
+    /// Can be given more than once.
+    #[clap(long, number_of_values=1)]
+    pub segment: Vec<String>
+}
+
 #[derive(Clap)]
 pub enum SubCommand {
     #[clap()]
@@ -22,12 +40,71 @@ pub enum SubCommand {
 
     #[clap()]
     ViewVertexStore(ViewVertexStore),
+
+    #[clap()]
+    ImportUvs(ImportUvs),
+
+    #[clap()]
+    RecomputeBounds(RecomputeBounds),
+
+    #[clap()]
+    Decompress(Decompress),
+
+    #[clap()]
+    Compress(Compress),
+
+    #[clap()]
+    Rom(Rom),
+
+    #[clap()]
+    ExtractModels(ExtractModels),
+
+    #[clap()]
+    ExportTextures(ExportTextures),
+
+    #[clap()]
+    ExportPalette(ExportPalette),
+
+    #[clap()]
+    ViewTexture(ViewTexture),
+
+    #[clap()]
+    ExportObj(ExportObj),
+
+    #[clap()]
+    ExportSvg(ExportSvg),
+
+    #[clap()]
+    Render(Render),
+
+    #[clap()]
+    DiffRender(DiffRender),
+
+    #[clap()]
+    ReplaceTexture(ReplaceTexture),
+
+    #[clap()]
+    TextureFormat(TextureFormat),
+
+    #[clap()]
+    MakePatch(MakePatch),
+
+    #[clap()]
+    ApplyPatch(ApplyPatch),
 }
 
 // Subcommand with inspects the file
 #[derive(Clap)]
 pub struct Inspect
-{}
+{
+    /// Draw each texture in the terminal
+    #[clap(long)]
+    pub preview: bool,
+
+    /// Colors for --preview: truecolor or 256 (default: from $COLORTERM)
+    #[clap(long)]
+    pub color: Option<String>
+}
 
 #[derive(Clap)]
 pub struct ViewDisplayList
@@ -35,4 +112,413 @@ pub struct ViewDisplayList
 
 #[derive(Clap)]
 pub struct ViewVertexStore
-{}
\ No newline at end of file
+{
+    /// Show each vertex's index in the vertex store
+    #[clap(long)]
+    pub indices: bool,
+
+    /// Show the UVs normalized to the texture each vertex is drawn with
+    #[clap(long)]
+    pub normalized_uvs: bool,
+
+    /// Show which triangles use each vertex
+    #[clap(long)]
+    pub triangles: bool,
+
+    /// Only list verticies with an index in this range, as first-last (ex. 100-200)
+    #[clap(long)]
+    pub range: Option<String>,
+
+    /// Only list verticies inside this box, as x1,y1,z1,x2,y2,z2
+    #[clap(long, allow_hyphen_values=true)]
+    pub within: Option<String>,
+
+    /// Only list verticies used this way: unused, colored, lit or both (loaded with G_LIGHTING on and off)
+    #[clap(long)]
+    pub kind: Option<String>,
+
+    /// Sort by position (x, then y, then z) instead of by index
+    #[clap(long)]
+    pub sort: bool,
+
+    /// How to write the list: text, csv or json
+    #[clap(long, default_value="text")]
+    pub format: String
+}
+
+// Reads normalized UVs from a view-vertex-store csv (--format csv --normalized-uvs) back into the model
+#[derive(Clap)]
+pub struct ImportUvs
+{
+    /// The csv to read the UVs from
+    pub csv: String,
+
+    /// The file to write the updated model to
+    #[clap(short, long)]
+    pub out: String
+}
+
+// Recomputes the vertex store culling/collision values from the verticies and saves the model
+#[derive(Clap)]
+pub struct RecomputeBounds
+{
+    /// The file to write the updated model to
+    #[clap(short, long)]
+    pub out: String
+}
+
+// Decompresses a rarezip compressed asset (any type, not just models)
+#[derive(Clap)]
+pub struct Decompress
+{
+    /// The file to write the decompressed data to
+    #[clap(short, long)]
+    pub out: String
+}
+
+// Compresses a file into a rarezip container the game can load
+#[derive(Clap)]
+pub struct Compress
+{
+    /// The file to write the compressed data to
+    #[clap(short, long)]
+    pub out: String,
+
+    /// The deflate level (1-9). The default matches the retail assets.
+    #[clap(short, long, default_value="9")]
+    pub level: u32,
+
+    /// An original compressed asset to compare the output against
+    #[clap(long)]
+    pub verify: Option<String>
+}
+
+// Works with Banjo-Kazooie rom images
+#[derive(Clap)]
+pub struct Rom
+{
+    #[clap(subcommand)]
+    pub sub_command: RomSubCommand
+}
+
+#[derive(Clap)]
+pub enum RomSubCommand {
+    #[clap()]
+    List(RomList),
+
+    #[clap()]
+    Patch(RomPatch),
+}
+
+// Lists every asset in the rom's asset table
+#[derive(Clap)]
+pub struct RomList
+{
+    /// The rom image (.z64, .v64 or .n64)
+    #[clap(short, long)]
+    pub rom: String
+}
+
+// Replaces an asset in the rom and repairs the header checksum
+#[derive(Clap)]
+pub struct RomPatch
+{
+    /// The rom image (.z64, .v64 or .n64)
+    #[clap(short, long)]
+    pub rom: String,
+
+    /// The index of the asset to replace
+    #[clap(short, long)]
+    pub asset: usize,
+
+    /// The (decompressed) asset to insert, ex. a model. The rom isnt made bigger, so if it's larger than
+    /// the asset it replaces it has to fit in the padding after the last asset
+    #[clap(short, long)]
+    pub file: String,
+
+    /// The file to write the patched rom to
+    #[clap(short, long)]
+    pub out: String
+}
+
+// Extracts every model from a rom as standalone (decompressed) files
+#[derive(Clap)]
+pub struct ExtractModels
+{
+    /// The rom image (.z64, .v64 or .n64)
+    #[clap(short, long)]
+    pub rom: String,
+
+    /// The directory to write the models to
+    #[clap(short, long)]
+    pub out: String,
+
+    /// Also export the textures (png) and geometry (obj) of each model
+    #[clap(short, long)]
+    pub export: bool
+}
+
+// Exports every texture in the model as a png
+#[derive(Clap)]
+pub struct ExportTextures
+{
+    /// The directory to write the textures to
+    #[clap(short, long)]
+    pub out: String,
+
+    /// Only export this texture
+    #[clap(short, long)]
+    pub index: Option<usize>,
+
+    /// Decode CI textures with this palette instead of their own (.gpl, or raw RGBA5551)
+    #[clap(short, long)]
+    pub palette: Option<String>
+}
+
+// Exports the palette of a CI texture as a swatch png or a GIMP palette
+#[derive(Clap)]
+pub struct ExportPalette
+{
+    /// The texture to export the palette of
+    #[clap(short, long)]
+    pub index: usize,
+
+    /// The file to write the palette to (.png for a swatch, .gpl for a GIMP palette)
+    #[clap(short, long)]
+    pub out: String
+}
+
+// Replaces one of the model's textures with a png and saves the model
+#[derive(Clap)]
+pub struct ReplaceTexture
+{
+    /// The texture to replace
+    #[clap(short, long)]
+    pub index: usize,
+
+    /// The png to encode into the texture (it has to be the same size)
+    #[clap(short, long)]
+    pub png: String,
+
+    /// Dithering when reducing the colors for CI textures (none, ordered, floyd-steinberg)
+    #[clap(short, long, default_value="none")]
+    pub dither: String,
+
+    /// The file to write the updated model to
+    #[clap(short, long)]
+    pub out: String
+}
+
+// Picks the smallest texture type which stores a png losslessly, and checks it fits in TMEM
+#[derive(Clap)]
+pub struct TextureFormat
+{
+    /// The png to check
+    #[clap(short, long)]
+    pub png: String
+}
+
+// Draws textures (or a render of the model) in the terminal
+#[derive(Clap)]
+pub struct ViewTexture
+{
+    /// The texture to draw (default: all of them)
+    #[clap(short, long)]
+    pub index: Option<usize>,
+
+    /// Also draw a render of the whole model
+    #[clap(long)]
+    pub model: bool,
+
+    /// The most characters to use across
+    #[clap(long, default_value="64")]
+    pub columns: usize,
+
+    /// The most lines to use (each line is two pixels)
+    #[clap(long, default_value="32")]
+    pub rows: usize,
+
+    /// Colors to use: truecolor or 256 (default: from $COLORTERM)
+    #[clap(long)]
+    pub color: Option<String>
+}
+
+// Exports the model geometry as a Wavefront OBJ
+#[derive(Clap)]
+pub struct ExportObj
+{
+    /// The file to write the obj to
+    #[clap(short, long)]
+    pub out: String
+}
+
+// Exports the triangles the display list draws as an svg
+#[derive(Clap)]
+pub struct ExportSvg
+{
+    /// The file to write the svg to
+    #[clap(short, long)]
+    pub out: String,
+
+    /// What to look at the model from: front, side and top are orthographic, camera uses --yaw, --pitch and --fov
+    #[clap(long, default_value="front")]
+    pub view: String,
+
+    /// Width of the image in pixels
+    #[clap(long, default_value="512")]
+    pub width: usize,
+
+    /// Height of the image in pixels
+    #[clap(long, default_value="512")]
+    pub height: usize,
+
+    /// How far to turn around the model in degrees for --view camera (0 looks at the front)
+    #[clap(long, default_value="30")]
+    pub yaw: f64,
+
+    /// How far to look down on the model in degrees for --view camera
+    #[clap(long, default_value="20")]
+    pub pitch: f64,
+
+    /// Vertical field of view in degrees for --view camera
+    #[clap(long, default_value="45")]
+    pub fov: f64,
+
+    /// Label each vertex with its index in the vertex store
+    #[clap(long)]
+    pub labels: bool
+}
+
+// Renders the model to a png with the software renderer
+#[derive(Clap)]
+pub struct Render
+{
+    /// The png to write
+    #[clap(short, long)]
+    pub out: String,
+
+    /// Width of the image in pixels
+    #[clap(long, default_value="320")]
+    pub width: usize,
+
+    /// Height of the image in pixels
+    #[clap(long, default_value="240")]
+    pub height: usize,
+
+    /// How far to turn around the model in degrees (0 looks at the front)
+    #[clap(long, default_value="30")]
+    pub yaw: f64,
+
+    /// How far to look down on the model in degrees
+    #[clap(long, default_value="20")]
+    pub pitch: f64,
+
+    /// Vertical field of view in degrees
+    #[clap(long, default_value="45")]
+    pub fov: f64,
+
+    /// Background color as RRGGBB or RRGGBBAA (hex)
+    #[clap(long, default_value="00000000")]
+    pub background: String,
+
+    /// Render a contact sheet with this many views evenly spaced around the model (starting at --yaw)
+    #[clap(long)]
+    pub turntable: Option<usize>,
+
+    /// Orthographic views to add to the contact sheet, comma separated (front, side, top)
+    #[clap(long)]
+    pub views: Option<String>,
+
+    /// How many images go on each row of the contact sheet (default: about square)
+    #[clap(long)]
+    pub columns: Option<usize>
+}
+
+// Renders two models with the same camera and shows the differences
+#[derive(Clap)]
+pub struct DiffRender
+{
+    /// The original model
+    pub first: String,
+
+    /// The changed model
+    pub second: String,
+
+    /// The png to write the two renders to, side by side
+    #[clap(short, long)]
+    pub out: String,
+
+    /// The png to write the highlighted differences to (default: the --out name with _diff added)
+    #[clap(long)]
+    pub diff: Option<String>,
+
+    /// How much a channel has to change (0-255) for a pixel to count as changed
+    #[clap(long, default_value="0")]
+    pub threshold: u8,
+
+    /// Width of each render in pixels
+    #[clap(long, default_value="320")]
+    pub width: usize,
+
+    /// Height of each render in pixels
+    #[clap(long, default_value="240")]
+    pub height: usize,
+
+    /// How far to turn around the models in degrees (0 looks at the front)
+    #[clap(long, default_value="30")]
+    pub yaw: f64,
+
+    /// How far to look down on the models in degrees
+    #[clap(long, default_value="20")]
+    pub pitch: f64,
+
+    /// Vertical field of view in degrees
+    #[clap(long, default_value="45")]
+    pub fov: f64,
+
+    /// Background color as RRGGBB or RRGGBBAA (hex)
+    #[clap(long, default_value="00000000")]
+    pub background: String
+}
+
+// Creates an ips or bps patch from an original and a modified file (ex. roms)
+#[derive(Clap)]
+pub struct MakePatch
+{
+    /// The unmodified file
+    #[clap(long)]
+    pub original: String,
+
+    /// The modified file
+    #[clap(long)]
+    pub modified: String,
+
+    /// The file to write the patch to
+    #[clap(long)]
+    pub out: String,
+
+    /// The patch format (ips or bps). Defaults to the extension of --out, or bps.
+    #[clap(long)]
+    pub format: Option<String>
+}
+
+// Applies an ips or bps patch to a file
+#[derive(Clap)]
+pub struct ApplyPatch
+{
+    /// The unmodified file
+    #[clap(long)]
+    pub original: String,
+
+    /// The patch to apply
+    #[clap(long)]
+    pub patch: String,
+
+    /// The file to write the patched output to
+    #[clap(long)]
+    pub out: Option<String>,
+
+    /// A file the patched output should match, to check the patch
+    #[clap(long)]
+    pub verify: Option<String>
+}
@@ -0,0 +1,22 @@
+//
+/// The standard CRC-32 (IEEE 802.3, as used by png/zip/bps)
+//
+pub fn crc32 (data: &[u8]) -> u32
+{
+    return crc32_update(0, data);
+}
+
+//
+/// Continue a CRC-32 from a previous result
+//
+pub fn crc32_update (crc: u32, data: &[u8]) -> u32
+{
+    let mut c = !crc;
+    for b in data {
+        c ^= *b as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+        }
+    }
+    return !c;
+}
@@ -0,0 +1,1326 @@
+// Encoder for a raw deflate stream (RFC 1951) - no zlib/gzip wrapper.
+// The game assets were compressed with the classic gzip 1.2.4 deflate, so this follows
+// that implementation decision for decision (hash chains, lazy matching, when blocks
+// are flushed and how the trees are built). Deflate streams are not unique, and matching
+// that logic is what should let an unmodified asset compress back to the exact bytes in the
+// ROM - rarezip::compare shows whether it did.
+
+/// Size of the sliding window
+const WSIZE : usize = 0x8000;
+const WMASK : usize = WSIZE - 1;
+
+const HASH_BITS : u32 = 15;
+const HASH_SIZE : usize = 1 << HASH_BITS;
+const HASH_MASK : u32 = (HASH_SIZE as u32) - 1;
+const H_SHIFT : u32 = HASH_BITS.div_ceil(MIN_MATCH as u32);
+
+const MIN_MATCH : usize = 3;
+const MAX_MATCH : usize = 258;
+const MIN_LOOKAHEAD : usize = MAX_MATCH + MIN_MATCH + 1;
+const MAX_DIST : usize = WSIZE - MIN_LOOKAHEAD;
+
+/// Matches of length 3 are discarded if their distance exceeds this
+const TOO_FAR : usize = 4096;
+
+/// The "no match" position
+const NIL : usize = 0;
+
+const LIT_BUFSIZE : usize = 0x8000;
+const DIST_BUFSIZE : usize = LIT_BUFSIZE;
+
+const MAX_BITS : usize = 15;
+const MAX_BL_BITS : usize = 7;
+const LENGTH_CODES : usize = 29;
+const LITERALS : usize = 256;
+const END_BLOCK : usize = 256;
+const L_CODES : usize = LITERALS + 1 + LENGTH_CODES;
+const D_CODES : usize = 30;
+const BL_CODES : usize = 19;
+const HEAP_SIZE : usize = 2 * L_CODES + 1;
+
+const STORED_BLOCK : u32 = 0;
+const STATIC_TREES : u32 = 1;
+const DYN_TREES : u32 = 2;
+
+const REP_3_6 : usize = 16;
+const REPZ_3_10 : usize = 17;
+const REPZ_11_138 : usize = 18;
+
+const EXTRA_LBITS : [u32; LENGTH_CODES] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+
+const EXTRA_DBITS : [u32; D_CODES] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+
+const EXTRA_BLBITS : [u32; BL_CODES] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 3, 7
+];
+
+const BL_ORDER : [usize; BL_CODES] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15
+];
+
+//
+/// Tuning for each compression level: good length, max lazy, nice length, max chain
+//
+const CONFIGURATION_TABLE : [(usize, usize, usize, usize); 10] = [
+    (0, 0, 0, 0),
+    (4, 4, 8, 4),
+    (4, 5, 16, 8),
+    (4, 6, 32, 32),
+    (4, 4, 16, 16),
+    (8, 16, 32, 32),
+    (8, 16, 128, 128),
+    (8, 32, 128, 256),
+    (32, 128, 258, 1024),
+    (32, 258, 258, 4096)
+];
+
+//
+/// The compression level the retail assets were built with
+//
+pub const RETAIL_LEVEL : u32 = 9;
+
+//
+/// A huffman tree in the same shape gzip uses: frequency/code and dad/length share a slot
+//
+struct Tree
+{
+    m_fc: Vec<u16>, // frequency, or the code once generated
+    m_dl: Vec<u16> // parent node, or the length once generated
+}
+
+impl Tree
+{
+    fn new (size: usize) -> Tree
+    {
+        return Tree {
+            m_fc: vec![0; size],
+            m_dl: vec![0; size]
+        };
+    }
+}
+
+//
+/// Which tree a build is for (the extra bits/limits differ)
+//
+#[derive(Copy, Clone, PartialEq)]
+enum TreeKind
+{
+    Literal,
+    Distance,
+    BitLength
+}
+
+//
+/// Static tables shared by every block
+//
+struct StaticTables
+{
+    m_base_length: [u32; LENGTH_CODES],
+    m_length_code: [u8; 256],
+    m_base_dist: [u32; D_CODES],
+    m_dist_code: [u8; 512],
+    m_static_ltree: Tree,
+    m_static_dtree: Tree
+}
+
+impl StaticTables
+{
+    fn new () -> StaticTables
+    {
+        let mut base_length = [0u32; LENGTH_CODES];
+        let mut length_code = [0u8; 256];
+        let mut length = 0usize;
+        let mut code = 0usize;
+        while code < LENGTH_CODES - 1 {
+            base_length[code] = length as u32;
+            for _ in 0 .. (1 << EXTRA_LBITS[code]) {
+                length_code[length] = code as u8;
+                length += 1;
+            }
+            code += 1;
+        }
+        // length 258 has its own code, overwrite the last entry
+        length_code[length - 1] = code as u8;
+
+        let mut base_dist = [0u32; D_CODES];
+        let mut dist_code = [0u8; 512];
+        let mut dist = 0usize;
+        code = 0;
+        while code < 16 {
+            base_dist[code] = dist as u32;
+            for _ in 0 .. (1 << EXTRA_DBITS[code]) {
+                dist_code[dist] = code as u8;
+                dist += 1;
+            }
+            code += 1;
+        }
+        dist >>= 7;
+        while code < D_CODES {
+            base_dist[code] = (dist << 7) as u32;
+            for _ in 0 .. (1 << (EXTRA_DBITS[code] - 7)) {
+                dist_code[256 + dist] = code as u8;
+                dist += 1;
+            }
+            code += 1;
+        }
+
+        let mut static_ltree = Tree::new(L_CODES + 2);
+        let mut bl_count = [0u16; MAX_BITS + 1];
+        for n in 0 .. L_CODES + 2 {
+            let len = match n {
+                0 ..= 143 => 8,
+                144 ..= 255 => 9,
+                256 ..= 279 => 7,
+                _ => 8
+            };
+            static_ltree.m_dl[n] = len;
+            bl_count[len as usize] += 1;
+        }
+        p_gen_codes(&mut static_ltree, L_CODES + 1, &bl_count);
+
+        let mut static_dtree = Tree::new(D_CODES);
+        for n in 0 .. D_CODES {
+            static_dtree.m_dl[n] = 5;
+            static_dtree.m_fc[n] = p_bi_reverse(n as u32, 5) as u16;
+        }
+
+        return StaticTables {
+            m_base_length: base_length,
+            m_length_code: length_code,
+            m_base_dist: base_dist,
+            m_dist_code: dist_code,
+            m_static_ltree: static_ltree,
+            m_static_dtree: static_dtree
+        };
+    }
+
+    fn d_code (&self, dist: usize) -> usize
+    {
+        if dist < 256 {
+            return self.m_dist_code[dist] as usize;
+        }
+        return self.m_dist_code[256 + (dist >> 7)] as usize;
+    }
+}
+
+//
+/// Writes bits LSB first
+//
+struct BitWriter
+{
+    m_out: Vec<u8>,
+    m_bit_buffer: u32,
+    m_bit_count: u32
+}
+
+impl BitWriter
+{
+    fn send_bits (&mut self, value: u32, length: u32)
+    {
+        self.m_bit_buffer |= value << self.m_bit_count;
+        self.m_bit_count += length;
+        while self.m_bit_count >= 8 {
+            self.m_out.push(self.m_bit_buffer as u8);
+            self.m_bit_buffer >>= 8;
+            self.m_bit_count -= 8;
+        }
+    }
+
+    //
+    /// Flush any partial byte
+    //
+    fn windup (&mut self)
+    {
+        if self.m_bit_count > 0 {
+            self.m_out.push(self.m_bit_buffer as u8);
+        }
+        self.m_bit_buffer = 0;
+        self.m_bit_count = 0;
+    }
+}
+
+//
+/// All the compressor state
+//
+struct Deflater<'a>
+{
+    m_input: &'a [u8],
+    m_input_pos: usize,
+    m_level: usize,
+
+    // matcher
+    m_window: Vec<u8>,
+    m_prev: Vec<u16>,
+    m_head: Vec<u16>,
+    m_ins_h: u32,
+    m_strstart: usize,
+    m_block_start: i64,
+    m_match_start: usize,
+    m_lookahead: usize,
+    m_eofile: bool,
+    m_prev_length: usize,
+    m_good_match: usize,
+    m_max_lazy_match: usize,
+    m_nice_match: usize,
+    m_max_chain_length: usize,
+
+    // block
+    m_tables: StaticTables,
+    m_dyn_ltree: Tree,
+    m_dyn_dtree: Tree,
+    m_bl_tree: Tree,
+    m_l_max_code: i32,
+    m_d_max_code: i32,
+    m_bl_max_code: i32,
+    m_l_buf: Vec<u8>,
+    m_d_buf: Vec<u16>,
+    m_flag_buf: Vec<u8>,
+    m_last_lit: usize,
+    m_last_dist: usize,
+    m_last_flags: usize,
+    m_flags: u8,
+    m_flag_bit: u8,
+    m_opt_len: i64,
+    m_static_len: i64,
+
+    // tree building
+    m_heap: [i32; HEAP_SIZE],
+    m_heap_len: usize,
+    m_heap_max: usize,
+    m_depth: [u8; HEAP_SIZE],
+    m_bl_count: [u16; MAX_BITS + 1],
+
+    m_writer: BitWriter
+}
+
+//
+/// Compress data into a raw deflate stream.
+/// level is 1 (fastest) to 9 (smallest), RETAIL_LEVEL is the one the game assets were built with.
+//
+pub fn deflate (data: &[u8], level: u32) -> Vec<u8>
+{
+    let level = level.clamp(1, 9) as usize;
+    let mut deflater = Deflater::new(data, level);
+    deflater.lm_init();
+
+    if level <= 3 {
+        deflater.deflate_fast();
+    } else {
+        deflater.deflate_lazy();
+    }
+
+    return deflater.m_writer.m_out;
+}
+
+impl<'a> Deflater<'a>
+{
+    fn new (input: &'a [u8], level: usize) -> Deflater<'a>
+    {
+        let (good, lazy, nice, chain) = CONFIGURATION_TABLE[level];
+
+        return Deflater {
+            m_input: input,
+            m_input_pos: 0,
+            m_level: level,
+
+            // the extra MAX_MATCH bytes let the matcher run past the end of the window like the C version did
+            m_window: vec![0; 2 * WSIZE + MAX_MATCH + 1],
+            m_prev: vec![0; WSIZE],
+            m_head: vec![0; HASH_SIZE],
+            m_ins_h: 0,
+            m_strstart: 0,
+            m_block_start: 0,
+            m_match_start: 0,
+            m_lookahead: 0,
+            m_eofile: false,
+            m_prev_length: MIN_MATCH - 1,
+            m_good_match: good,
+            m_max_lazy_match: lazy,
+            m_nice_match: nice,
+            m_max_chain_length: chain,
+
+            m_tables: StaticTables::new(),
+            m_dyn_ltree: Tree::new(HEAP_SIZE),
+            m_dyn_dtree: Tree::new(2 * D_CODES + 1),
+            m_bl_tree: Tree::new(2 * BL_CODES + 1),
+            m_l_max_code: 0,
+            m_d_max_code: 0,
+            m_bl_max_code: 0,
+            m_l_buf: vec![0; LIT_BUFSIZE],
+            m_d_buf: vec![0; DIST_BUFSIZE],
+            m_flag_buf: vec![0; LIT_BUFSIZE / 8 + 1],
+            m_last_lit: 0,
+            m_last_dist: 0,
+            m_last_flags: 0,
+            m_flags: 0,
+            m_flag_bit: 1,
+            m_opt_len: 0,
+            m_static_len: 0,
+
+            m_heap: [0; HEAP_SIZE],
+            m_heap_len: 0,
+            m_heap_max: 0,
+            m_depth: [0; HEAP_SIZE],
+            m_bl_count: [0; MAX_BITS + 1],
+
+            m_writer: BitWriter { m_out: Vec::new(), m_bit_buffer: 0, m_bit_count: 0 }
+        };
+    }
+
+    // --- input/window
+
+    fn read_buf (&mut self, dest: usize, size: usize) -> usize
+    {
+        let available = self.m_input.len() - self.m_input_pos;
+        let n = size.min(available);
+        self.m_window[dest .. dest + n].copy_from_slice(&self.m_input[self.m_input_pos .. self.m_input_pos + n]);
+        self.m_input_pos += n;
+        return n;
+    }
+
+    fn lm_init (&mut self)
+    {
+        self.init_block();
+
+        self.m_lookahead = self.read_buf(0, 2 * WSIZE);
+        if self.m_lookahead == 0 {
+            self.m_eofile = true;
+            return;
+        }
+
+        self.m_eofile = false;
+        while self.m_lookahead < MIN_LOOKAHEAD && !self.m_eofile {
+            self.fill_window();
+        }
+
+        self.m_ins_h = 0;
+        for j in 0 .. MIN_MATCH - 1 {
+            self.update_hash(self.m_window[j]);
+        }
+    }
+
+    fn fill_window (&mut self)
+    {
+        let mut more = 2 * WSIZE - self.m_lookahead - self.m_strstart;
+
+        if self.m_strstart >= WSIZE + MAX_DIST {
+            self.m_window.copy_within(WSIZE .. 2 * WSIZE, 0);
+            self.m_match_start -= WSIZE;
+            self.m_strstart -= WSIZE;
+            self.m_block_start -= WSIZE as i64;
+
+            for h in self.m_head.iter_mut() {
+                *h = if *h as usize >= WSIZE { *h - WSIZE as u16 } else { NIL as u16 };
+            }
+            for p in self.m_prev.iter_mut() {
+                *p = if *p as usize >= WSIZE { *p - WSIZE as u16 } else { NIL as u16 };
+            }
+            more += WSIZE;
+        }
+
+        if !self.m_eofile {
+            let n = self.read_buf(self.m_strstart + self.m_lookahead, more);
+            if n == 0 {
+                self.m_eofile = true;
+            } else {
+                self.m_lookahead += n;
+            }
+        }
+    }
+
+    fn update_hash (&mut self, c: u8)
+    {
+        self.m_ins_h = ((self.m_ins_h << H_SHIFT) ^ c as u32) & HASH_MASK;
+    }
+
+    //
+    /// Insert the string at pos into the dictionary, returning the previous head of the hash chain
+    //
+    fn insert_string (&mut self, pos: usize) -> usize
+    {
+        self.update_hash(self.m_window[pos + MIN_MATCH - 1]);
+        let match_head = self.m_head[self.m_ins_h as usize];
+        self.m_prev[pos & WMASK] = match_head;
+        self.m_head[self.m_ins_h as usize] = pos as u16;
+        return match_head as usize;
+    }
+
+    fn longest_match (&mut self, mut cur_match: usize) -> usize
+    {
+        let mut chain_length = self.m_max_chain_length;
+        let scan_start = self.m_strstart;
+        let mut best_len = self.m_prev_length;
+        let limit = if self.m_strstart > MAX_DIST { self.m_strstart - MAX_DIST } else { NIL };
+        let strend = self.m_strstart + MAX_MATCH;
+        let mut scan_end1 = self.m_window[scan_start + best_len - 1];
+        let mut scan_end = self.m_window[scan_start + best_len];
+
+        if self.m_prev_length >= self.m_good_match {
+            chain_length >>= 2;
+        }
+
+        loop {
+            let w = &self.m_window;
+            let m = cur_match;
+
+            if w[m + best_len] == scan_end && w[m + best_len - 1] == scan_end1
+                && w[m] == w[scan_start] && w[m + 1] == w[scan_start + 1]
+            {
+                // the hash guarantees the third byte matches, so start comparing after it
+                let mut scan = scan_start + 2;
+                let mut mat = m + 2;
+                loop {
+                    let mut mismatch = false;
+                    for _ in 0 .. 8 {
+                        scan += 1;
+                        mat += 1;
+                        if w[scan] != w[mat] {
+                            mismatch = true;
+                            break;
+                        }
+                    }
+                    if mismatch || scan >= strend {
+                        break;
+                    }
+                }
+
+                let len = MAX_MATCH - (strend - scan);
+                if len > best_len {
+                    self.m_match_start = cur_match;
+                    best_len = len;
+                    if len >= self.m_nice_match {
+                        break;
+                    }
+                    scan_end1 = self.m_window[scan_start + best_len - 1];
+                    scan_end = self.m_window[scan_start + best_len];
+                }
+            }
+
+            cur_match = self.m_prev[cur_match & WMASK] as usize;
+            if cur_match <= limit {
+                break;
+            }
+            chain_length -= 1;
+            if chain_length == 0 {
+                break;
+            }
+        }
+
+        return best_len;
+    }
+
+    fn flush_block_at (&mut self, eof: bool)
+    {
+        let stored_len = (self.m_strstart as i64 - self.m_block_start) as usize;
+        let buf = if self.m_block_start >= 0 { Some(self.m_block_start as usize) } else { None };
+        self.flush_block(buf, stored_len, eof);
+    }
+
+    // --- the two matchers
+
+    fn deflate_fast (&mut self)
+    {
+        let mut match_length = 0usize;
+        self.m_prev_length = MIN_MATCH - 1;
+
+        while self.m_lookahead != 0 {
+            let hash_head = self.insert_string(self.m_strstart);
+
+            if hash_head != NIL && self.m_strstart.wrapping_sub(hash_head) <= MAX_DIST {
+                match_length = self.longest_match(hash_head);
+                if match_length > self.m_lookahead {
+                    match_length = self.m_lookahead;
+                }
+            }
+
+            let flush;
+            if match_length >= MIN_MATCH {
+                flush = self.ct_tally(self.m_strstart - self.m_match_start, match_length - MIN_MATCH);
+                self.m_lookahead -= match_length;
+
+                if match_length <= self.m_max_lazy_match {
+                    match_length -= 1;
+                    loop {
+                        self.m_strstart += 1;
+                        self.insert_string(self.m_strstart);
+                        match_length -= 1;
+                        if match_length == 0 {
+                            break;
+                        }
+                    }
+                    self.m_strstart += 1;
+                } else {
+                    self.m_strstart += match_length;
+                    match_length = 0;
+                    self.m_ins_h = self.m_window[self.m_strstart] as u32;
+                    self.update_hash(self.m_window[self.m_strstart + 1]);
+                }
+            } else {
+                flush = self.ct_tally(0, self.m_window[self.m_strstart] as usize);
+                self.m_lookahead -= 1;
+                self.m_strstart += 1;
+            }
+
+            if flush {
+                self.flush_block_at(false);
+                self.m_block_start = self.m_strstart as i64;
+            }
+
+            while self.m_lookahead < MIN_LOOKAHEAD && !self.m_eofile {
+                self.fill_window();
+            }
+        }
+
+        self.flush_block_at(true);
+    }
+
+    fn deflate_lazy (&mut self)
+    {
+        let mut match_available = false;
+        let mut match_length = MIN_MATCH - 1;
+
+        while self.m_lookahead != 0 {
+            let hash_head = self.insert_string(self.m_strstart);
+
+            self.m_prev_length = match_length;
+            let prev_match = self.m_match_start;
+            match_length = MIN_MATCH - 1;
+
+            if hash_head != NIL && self.m_prev_length < self.m_max_lazy_match
+                && self.m_strstart.wrapping_sub(hash_head) <= MAX_DIST
+            {
+                match_length = self.longest_match(hash_head);
+                if match_length > self.m_lookahead {
+                    match_length = self.m_lookahead;
+                }
+                if match_length == MIN_MATCH && self.m_strstart - self.m_match_start > TOO_FAR {
+                    match_length -= 1;
+                }
+            }
+
+            if self.m_prev_length >= MIN_MATCH && match_length <= self.m_prev_length {
+                let flush = self.ct_tally(self.m_strstart - 1 - prev_match, self.m_prev_length - MIN_MATCH);
+
+                self.m_lookahead -= self.m_prev_length - 1;
+                self.m_prev_length -= 2;
+                loop {
+                    self.m_strstart += 1;
+                    self.insert_string(self.m_strstart);
+                    self.m_prev_length -= 1;
+                    if self.m_prev_length == 0 {
+                        break;
+                    }
+                }
+                match_available = false;
+                match_length = MIN_MATCH - 1;
+                self.m_strstart += 1;
+
+                if flush {
+                    self.flush_block_at(false);
+                    self.m_block_start = self.m_strstart as i64;
+                }
+            } else if match_available {
+                if self.ct_tally(0, self.m_window[self.m_strstart - 1] as usize) {
+                    self.flush_block_at(false);
+                    self.m_block_start = self.m_strstart as i64;
+                }
+                self.m_strstart += 1;
+                self.m_lookahead -= 1;
+            } else {
+                match_available = true;
+                self.m_strstart += 1;
+                self.m_lookahead -= 1;
+            }
+
+            while self.m_lookahead < MIN_LOOKAHEAD && !self.m_eofile {
+                self.fill_window();
+            }
+        }
+
+        if match_available {
+            self.ct_tally(0, self.m_window[self.m_strstart - 1] as usize);
+        }
+
+        self.flush_block_at(true);
+    }
+
+    // --- blocks
+
+    fn init_block (&mut self)
+    {
+        for n in 0 .. L_CODES { self.m_dyn_ltree.m_fc[n] = 0; }
+        for n in 0 .. D_CODES { self.m_dyn_dtree.m_fc[n] = 0; }
+        for n in 0 .. BL_CODES { self.m_bl_tree.m_fc[n] = 0; }
+
+        self.m_dyn_ltree.m_fc[END_BLOCK] = 1;
+        self.m_opt_len = 0;
+        self.m_static_len = 0;
+        self.m_last_lit = 0;
+        self.m_last_dist = 0;
+        self.m_last_flags = 0;
+        self.m_flags = 0;
+        self.m_flag_bit = 1;
+    }
+
+    //
+    /// Record a literal (dist == 0) or a match. Returns true if the block should be flushed.
+    //
+    fn ct_tally (&mut self, dist: usize, lc: usize) -> bool
+    {
+        self.m_l_buf[self.m_last_lit] = lc as u8;
+        self.m_last_lit += 1;
+
+        if dist == 0 {
+            self.m_dyn_ltree.m_fc[lc] += 1;
+        } else {
+            let dist = dist - 1;
+            let code = self.m_tables.m_length_code[lc] as usize;
+            self.m_dyn_ltree.m_fc[code + LITERALS + 1] += 1;
+            let dcode = self.m_tables.d_code(dist);
+            self.m_dyn_dtree.m_fc[dcode] += 1;
+            self.m_d_buf[self.m_last_dist] = dist as u16;
+            self.m_last_dist += 1;
+            self.m_flags |= self.m_flag_bit;
+        }
+        self.m_flag_bit <<= 1;
+
+        if (self.m_last_lit & 7) == 0 {
+            self.m_flag_buf[self.m_last_flags] = self.m_flags;
+            self.m_last_flags += 1;
+            self.m_flags = 0;
+            self.m_flag_bit = 1;
+        }
+
+        // try to guess if it is profitable to stop the current block here
+        if self.m_level > 2 && (self.m_last_lit & 0xfff) == 0 {
+            let mut out_length = self.m_last_lit as u64 * 8;
+            let in_length = (self.m_strstart as i64 - self.m_block_start) as u64;
+            for (freq, extra) in self.m_dyn_dtree.m_fc.iter().zip(EXTRA_DBITS.iter()) {
+                out_length += *freq as u64 * (5 + *extra as u64);
+            }
+            out_length >>= 3;
+            if self.m_last_dist < self.m_last_lit / 2 && out_length < in_length / 2 {
+                return true;
+            }
+        }
+
+        return self.m_last_lit == LIT_BUFSIZE - 1 || self.m_last_dist == DIST_BUFSIZE;
+    }
+
+    fn flush_block (&mut self, buf: Option<usize>, stored_len: usize, eof: bool)
+    {
+        self.m_flag_buf[self.m_last_flags] = self.m_flags;
+
+        self.build_tree(TreeKind::Literal);
+        self.build_tree(TreeKind::Distance);
+        let max_blindex = self.build_bl_tree();
+
+        let mut opt_lenb = (self.m_opt_len + 3 + 7) >> 3;
+        let static_lenb = (self.m_static_len + 3 + 7) >> 3;
+        if static_lenb <= opt_lenb {
+            opt_lenb = static_lenb;
+        }
+
+        let eof_bit = if eof { 1 } else { 0 };
+
+        if let (true, Some(start)) = (stored_len as i64 + 4 <= opt_lenb, buf) {
+            self.m_writer.send_bits((STORED_BLOCK << 1) + eof_bit, 3);
+            self.m_writer.windup();
+            self.m_writer.m_out.extend_from_slice(&(stored_len as u16).to_le_bytes());
+            self.m_writer.m_out.extend_from_slice(&(!(stored_len as u16)).to_le_bytes());
+            let block = self.m_window[start .. start + stored_len].to_vec();
+            self.m_writer.m_out.extend_from_slice(&block);
+        } else if static_lenb == opt_lenb {
+            self.m_writer.send_bits((STATIC_TREES << 1) + eof_bit, 3);
+            self.compress_block(true);
+        } else {
+            self.m_writer.send_bits((DYN_TREES << 1) + eof_bit, 3);
+            self.send_all_trees(self.m_l_max_code as usize + 1, self.m_d_max_code as usize + 1, max_blindex + 1);
+            self.compress_block(false);
+        }
+
+        self.init_block();
+
+        if eof {
+            self.m_writer.windup();
+        }
+    }
+
+    fn compress_block (&mut self, use_static: bool)
+    {
+        let mut flag = 0u8;
+        let mut lx = 0;
+        let mut dx = 0;
+        let mut fx = 0;
+
+        while lx < self.m_last_lit {
+            if (lx & 7) == 0 {
+                flag = self.m_flag_buf[fx];
+                fx += 1;
+            }
+            let lc = self.m_l_buf[lx] as usize;
+            lx += 1;
+
+            if (flag & 1) == 0 {
+                self.send_code(lc, use_static, TreeKind::Literal);
+            } else {
+                let code = self.m_tables.m_length_code[lc] as usize;
+                self.send_code(code + LITERALS + 1, use_static, TreeKind::Literal);
+                let extra = EXTRA_LBITS[code];
+                if extra != 0 {
+                    self.m_writer.send_bits(lc as u32 - self.m_tables.m_base_length[code], extra);
+                }
+
+                let dist = self.m_d_buf[dx] as usize;
+                dx += 1;
+                let code = self.m_tables.d_code(dist);
+                self.send_code(code, use_static, TreeKind::Distance);
+                let extra = EXTRA_DBITS[code];
+                if extra != 0 {
+                    self.m_writer.send_bits(dist as u32 - self.m_tables.m_base_dist[code], extra);
+                }
+            }
+            flag >>= 1;
+        }
+
+        self.send_code(END_BLOCK, use_static, TreeKind::Literal);
+    }
+
+    fn send_code (&mut self, c: usize, use_static: bool, kind: TreeKind)
+    {
+        let tree = match (kind, use_static) {
+            (TreeKind::Literal, true) => &self.m_tables.m_static_ltree,
+            (TreeKind::Literal, false) => &self.m_dyn_ltree,
+            (TreeKind::Distance, true) => &self.m_tables.m_static_dtree,
+            (TreeKind::Distance, false) => &self.m_dyn_dtree,
+            (TreeKind::BitLength, _) => &self.m_bl_tree
+        };
+        let code = tree.m_fc[c] as u32;
+        let len = tree.m_dl[c] as u32;
+        self.m_writer.send_bits(code, len);
+    }
+
+    // --- trees
+
+    fn tree_mut (&mut self, kind: TreeKind) -> &mut Tree
+    {
+        return match kind {
+            TreeKind::Literal => &mut self.m_dyn_ltree,
+            TreeKind::Distance => &mut self.m_dyn_dtree,
+            TreeKind::BitLength => &mut self.m_bl_tree
+        };
+    }
+
+    fn smaller (tree: &Tree, depth: &[u8], n: usize, m: usize) -> bool
+    {
+        return tree.m_fc[n] < tree.m_fc[m] || (tree.m_fc[n] == tree.m_fc[m] && depth[n] <= depth[m]);
+    }
+
+    fn pqdownheap (&mut self, kind: TreeKind, mut k: usize)
+    {
+        let heap_len = self.m_heap_len;
+        let v = self.m_heap[k];
+        let mut j = k << 1;
+
+        let tree = match kind {
+            TreeKind::Literal => &self.m_dyn_ltree,
+            TreeKind::Distance => &self.m_dyn_dtree,
+            TreeKind::BitLength => &self.m_bl_tree
+        };
+
+        while j <= heap_len {
+            if j < heap_len && Deflater::smaller(tree, &self.m_depth, self.m_heap[j + 1] as usize, self.m_heap[j] as usize) {
+                j += 1;
+            }
+            if Deflater::smaller(tree, &self.m_depth, v as usize, self.m_heap[j] as usize) {
+                break;
+            }
+            self.m_heap[k] = self.m_heap[j];
+            k = j;
+            j <<= 1;
+        }
+        self.m_heap[k] = v;
+    }
+
+    fn build_tree (&mut self, kind: TreeKind)
+    {
+        let elems = match kind {
+            TreeKind::Literal => L_CODES,
+            TreeKind::Distance => D_CODES,
+            TreeKind::BitLength => BL_CODES
+        };
+
+        let mut max_code : i32 = -1;
+        let mut node = elems;
+        self.m_heap_len = 0;
+        self.m_heap_max = HEAP_SIZE;
+
+        for n in 0 .. elems {
+            if self.tree_mut(kind).m_fc[n] != 0 {
+                self.m_heap_len += 1;
+                self.m_heap[self.m_heap_len] = n as i32;
+                max_code = n as i32;
+                self.m_depth[n] = 0;
+            } else {
+                self.tree_mut(kind).m_dl[n] = 0;
+            }
+        }
+
+        // force at least two codes of non zero frequency
+        while self.m_heap_len < 2 {
+            let new = if max_code < 2 { max_code += 1; max_code } else { 0 };
+            self.m_heap_len += 1;
+            self.m_heap[self.m_heap_len] = new;
+            let new = new as usize;
+            self.tree_mut(kind).m_fc[new] = 1;
+            self.m_depth[new] = 0;
+            self.m_opt_len -= 1;
+            match kind {
+                TreeKind::Literal => { self.m_static_len -= self.m_tables.m_static_ltree.m_dl[new] as i64; },
+                TreeKind::Distance => { self.m_static_len -= self.m_tables.m_static_dtree.m_dl[new] as i64; },
+                TreeKind::BitLength => {}
+            }
+        }
+
+        match kind {
+            TreeKind::Literal => self.m_l_max_code = max_code,
+            TreeKind::Distance => self.m_d_max_code = max_code,
+            TreeKind::BitLength => self.m_bl_max_code = max_code
+        }
+
+        let mut n = self.m_heap_len / 2;
+        while n >= 1 {
+            self.pqdownheap(kind, n);
+            n -= 1;
+        }
+
+        loop {
+            // pqremove
+            let n = self.m_heap[1] as usize;
+            self.m_heap[1] = self.m_heap[self.m_heap_len];
+            self.m_heap_len -= 1;
+            self.pqdownheap(kind, 1);
+
+            let m = self.m_heap[1] as usize;
+
+            self.m_heap_max -= 1;
+            self.m_heap[self.m_heap_max] = n as i32;
+            self.m_heap_max -= 1;
+            self.m_heap[self.m_heap_max] = m as i32;
+
+            let freq = {
+                let tree = self.tree_mut(kind);
+                tree.m_fc[n] + tree.m_fc[m]
+            };
+            self.tree_mut(kind).m_fc[node] = freq;
+            self.m_depth[node] = self.m_depth[n].max(self.m_depth[m]) + 1;
+            {
+                let tree = self.tree_mut(kind);
+                tree.m_dl[n] = node as u16;
+                tree.m_dl[m] = node as u16;
+            }
+
+            self.m_heap[1] = node as i32;
+            node += 1;
+            self.pqdownheap(kind, 1);
+
+            if self.m_heap_len < 2 {
+                break;
+            }
+        }
+
+        self.m_heap_max -= 1;
+        self.m_heap[self.m_heap_max] = self.m_heap[1];
+
+        self.gen_bitlen(kind, max_code);
+
+        let bl_count = self.m_bl_count;
+        p_gen_codes(self.tree_mut(kind), max_code as usize, &bl_count);
+    }
+
+    fn gen_bitlen (&mut self, kind: TreeKind, max_code: i32)
+    {
+        let (extra, base, max_length) : (&[u32], usize, usize) = match kind {
+            TreeKind::Literal => (&EXTRA_LBITS, LITERALS + 1, MAX_BITS),
+            TreeKind::Distance => (&EXTRA_DBITS, 0, MAX_BITS),
+            TreeKind::BitLength => (&EXTRA_BLBITS, 0, MAX_BL_BITS)
+        };
+
+        for c in self.m_bl_count.iter_mut() {
+            *c = 0;
+        }
+
+        let root = self.m_heap[self.m_heap_max] as usize;
+        self.tree_mut(kind).m_dl[root] = 0;
+
+        let mut overflow = 0i32;
+        let mut h = self.m_heap_max + 1;
+        while h < HEAP_SIZE {
+            let n = self.m_heap[h] as usize;
+            let (mut bits, f) = {
+                let tree = self.tree_mut(kind);
+                (tree.m_dl[tree.m_dl[n] as usize] as usize + 1, tree.m_fc[n])
+            };
+            if bits > max_length {
+                bits = max_length;
+                overflow += 1;
+            }
+            self.tree_mut(kind).m_dl[n] = bits as u16;
+
+            h += 1;
+            if n as i32 > max_code {
+                continue; // not a leaf node
+            }
+
+            self.m_bl_count[bits] += 1;
+            let xbits = if n >= base { extra[n - base] as i64 } else { 0 };
+            self.m_opt_len += f as i64 * (bits as i64 + xbits);
+            match kind {
+                TreeKind::Literal => { self.m_static_len += f as i64 * (self.m_tables.m_static_ltree.m_dl[n] as i64 + xbits); },
+                TreeKind::Distance => { self.m_static_len += f as i64 * (self.m_tables.m_static_dtree.m_dl[n] as i64 + xbits); },
+                TreeKind::BitLength => {}
+            }
+        }
+
+        if overflow == 0 {
+            return;
+        }
+
+        // find the first bit length which could increase
+        loop {
+            let mut bits = max_length - 1;
+            while self.m_bl_count[bits] == 0 {
+                bits -= 1;
+            }
+            self.m_bl_count[bits] -= 1;
+            self.m_bl_count[bits + 1] += 2;
+            self.m_bl_count[max_length] -= 1;
+            overflow -= 2;
+            if overflow <= 0 {
+                break;
+            }
+        }
+
+        // recompute all the bit lengths, scanning in increasing frequency
+        let mut h = HEAP_SIZE;
+        let mut bits = max_length;
+        while bits != 0 {
+            let mut n = self.m_bl_count[bits];
+            while n != 0 {
+                h -= 1;
+                let m = self.m_heap[h] as usize;
+                if m as i32 > max_code {
+                    continue;
+                }
+                let (len, freq) = {
+                    let tree = self.tree_mut(kind);
+                    (tree.m_dl[m] as i64, tree.m_fc[m] as i64)
+                };
+                if len != bits as i64 {
+                    self.m_opt_len += (bits as i64 - len) * freq;
+                    self.tree_mut(kind).m_dl[m] = bits as u16;
+                }
+                n -= 1;
+            }
+            bits -= 1;
+        }
+    }
+
+    fn scan_tree (&mut self, kind: TreeKind, max_code: usize)
+    {
+        let mut prevlen : i32 = -1;
+        let mut nextlen = self.tree_mut(kind).m_dl[0] as i32;
+        let mut count = 0;
+        let (mut max_count, mut min_count) = if nextlen == 0 { (138, 3) } else { (7, 4) };
+
+        self.tree_mut(kind).m_dl[max_code + 1] = 0xffff; // guard
+
+        for n in 0 ..= max_code {
+            let curlen = nextlen;
+            nextlen = self.tree_mut(kind).m_dl[n + 1] as i32;
+            count += 1;
+            if count < max_count && curlen == nextlen {
+                continue;
+            } else if count < min_count {
+                self.m_bl_tree.m_fc[curlen as usize] += count as u16;
+            } else if curlen != 0 {
+                if curlen != prevlen {
+                    self.m_bl_tree.m_fc[curlen as usize] += 1;
+                }
+                self.m_bl_tree.m_fc[REP_3_6] += 1;
+            } else if count <= 10 {
+                self.m_bl_tree.m_fc[REPZ_3_10] += 1;
+            } else {
+                self.m_bl_tree.m_fc[REPZ_11_138] += 1;
+            }
+
+            count = 0;
+            prevlen = curlen;
+            if nextlen == 0 {
+                max_count = 138; min_count = 3;
+            } else if curlen == nextlen {
+                max_count = 6; min_count = 3;
+            } else {
+                max_count = 7; min_count = 4;
+            }
+        }
+    }
+
+    fn send_tree (&mut self, kind: TreeKind, max_code: usize)
+    {
+        let mut prevlen : i32 = -1;
+        let mut nextlen = self.tree_mut(kind).m_dl[0] as i32;
+        let mut count = 0;
+        let (mut max_count, mut min_count) = if nextlen == 0 { (138, 3) } else { (7, 4) };
+
+        for n in 0 ..= max_code {
+            let curlen = nextlen;
+            nextlen = self.tree_mut(kind).m_dl[n + 1] as i32;
+            count += 1;
+            if count < max_count && curlen == nextlen {
+                continue;
+            } else if count < min_count {
+                while count != 0 {
+                    self.send_code(curlen as usize, false, TreeKind::BitLength);
+                    count -= 1;
+                }
+            } else if curlen != 0 {
+                if curlen != prevlen {
+                    self.send_code(curlen as usize, false, TreeKind::BitLength);
+                    count -= 1;
+                }
+                self.send_code(REP_3_6, false, TreeKind::BitLength);
+                self.m_writer.send_bits(count as u32 - 3, 2);
+            } else if count <= 10 {
+                self.send_code(REPZ_3_10, false, TreeKind::BitLength);
+                self.m_writer.send_bits(count as u32 - 3, 3);
+            } else {
+                self.send_code(REPZ_11_138, false, TreeKind::BitLength);
+                self.m_writer.send_bits(count as u32 - 11, 7);
+            }
+
+            count = 0;
+            prevlen = curlen;
+            if nextlen == 0 {
+                max_count = 138; min_count = 3;
+            } else if curlen == nextlen {
+                max_count = 6; min_count = 3;
+            } else {
+                max_count = 7; min_count = 4;
+            }
+        }
+    }
+
+    fn build_bl_tree (&mut self) -> usize
+    {
+        self.scan_tree(TreeKind::Literal, self.m_l_max_code as usize);
+        self.scan_tree(TreeKind::Distance, self.m_d_max_code as usize);
+
+        self.build_tree(TreeKind::BitLength);
+
+        let mut max_blindex = BL_CODES - 1;
+        while max_blindex >= 3 {
+            if self.m_bl_tree.m_dl[BL_ORDER[max_blindex]] != 0 {
+                break;
+            }
+            max_blindex -= 1;
+        }
+
+        self.m_opt_len += 3 * (max_blindex as i64 + 1) + 5 + 5 + 4;
+        return max_blindex;
+    }
+
+    fn send_all_trees (&mut self, lcodes: usize, dcodes: usize, blcodes: usize)
+    {
+        self.m_writer.send_bits(lcodes as u32 - 257, 5);
+        self.m_writer.send_bits(dcodes as u32 - 1, 5);
+        self.m_writer.send_bits(blcodes as u32 - 4, 4);
+        for order in BL_ORDER.iter().take(blcodes) {
+            let len = self.m_bl_tree.m_dl[*order] as u32;
+            self.m_writer.send_bits(len, 3);
+        }
+        self.send_tree(TreeKind::Literal, lcodes - 1);
+        self.send_tree(TreeKind::Distance, dcodes - 1);
+    }
+}
+
+//
+/// Generate the (bit reversed) codes for a tree from the code lengths
+//
+fn p_gen_codes (tree: &mut Tree, max_code: usize, bl_count: &[u16; MAX_BITS + 1])
+{
+    let mut next_code = [0u32; MAX_BITS + 1];
+    let mut code = 0u32;
+    for bits in 1 ..= MAX_BITS {
+        code = (code + bl_count[bits - 1] as u32) << 1;
+        next_code[bits] = code;
+    }
+
+    for n in 0 ..= max_code {
+        let len = tree.m_dl[n] as usize;
+        if len == 0 {
+            continue;
+        }
+        tree.m_fc[n] = p_bi_reverse(next_code[len], len as u32) as u16;
+        next_code[len] += 1;
+    }
+}
+
+//
+/// Reverse the first len bits of a code
+//
+fn p_bi_reverse (mut code: u32, mut len: u32) -> u32
+{
+    let mut res = 0u32;
+    loop {
+        res |= code & 1;
+        code >>= 1;
+        res <<= 1;
+        len -= 1;
+        if len == 0 {
+            break;
+        }
+    }
+    return res >> 1;
+}
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::crc32;
+    use crate::inflate;
+
+    //
+    /// Text that compresses well, short enough that the window never slides
+    //
+    fn p_text () -> Vec<u8>
+    {
+        let mut text = b"The quick brown fox jumps over the lazy dog. ".repeat(40);
+        text.extend_from_slice(&b"banjo kazooie ".repeat(30));
+        return text;
+    }
+
+    //
+    /// Bytes that dont compress (xorshift), so deflate falls back to stored blocks
+    //
+    fn p_noise (length : usize) -> Vec<u8>
+    {
+        let mut state = 0x2545F491u32;
+        return (0 .. length).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            return (state >> 24) as u8;
+        }).collect();
+    }
+
+    //
+    /// A few letters and spaces, which compress but not to much: big enough for several blocks
+    //
+    fn p_letters (length : usize) -> Vec<u8>
+    {
+        return p_noise(length).iter().map(|b| b"abcdefgh "[*b as usize % 9]).collect();
+    }
+
+    //
+    /// Words picked from a small vocabulary (the common ones more often), so there are long hash
+    /// chains and plenty of matches to be lazy about
+    //
+    fn p_words (length : usize) -> Vec<u8>
+    {
+        let words : [&[u8]; 12] = [
+            b"the ", b"bear ", b"and ", b"bird ", b"jiggy ", b"note ",
+            b"honeycomb ", b"mumbo ", b"gruntilda ", b"jinjo ", b"spiral mountain ", b"\n"
+        ];
+
+        let mut text = Vec::<u8>::with_capacity(length + 16);
+        for b in p_noise(length) {
+            if text.len() >= length {
+                break;
+            }
+            text.extend_from_slice(words[(b as usize * b as usize) % (256 * words.len()) / 256]);
+        }
+        text.truncate(length);
+        return text;
+    }
+
+    #[test]
+    fn round_trips_at_every_level ()
+    {
+        let inputs = [
+            Vec::new(),
+            vec![0x42],
+            p_text(),
+            p_noise(WSIZE),
+            p_noise(0x11000),
+            p_letters(100000),
+            vec![0; 70000]
+        ];
+
+        for level in 1 ..= 9 {
+            for input in inputs.iter() {
+                let compressed = deflate(input, level);
+                let (decompressed, used) = inflate::inflate(&compressed).unwrap();
+                assert!(decompressed == *input, "level {} with {:#X} bytes", level, input.len());
+                assert_eq!(used, compressed.len());
+            }
+        }
+    }
+
+    #[test]
+    fn stores_what_does_not_compress ()
+    {
+        // a block is flushed every 0x8000 literals, and stored costs 5 bytes on top of the data (the header and the lengths)
+        let noise = p_noise(0x11000);
+        let compressed = deflate(&noise, RETAIL_LEVEL);
+        assert_eq!(compressed.len(), noise.len() + 5 * noise.len().div_ceil(0x8000));
+        assert_eq!(compressed[0] & 0x06, 0x00);
+    }
+
+    #[test]
+    fn matches_known_gzip_output ()
+    {
+        // from gzip with the 10 byte header and 8 byte trailer cut off. Later versions of gzip
+        // than 1.2.4 zero the window past the end of the input, which only matters once it has slid.
+        assert_eq!(deflate(&[], 1), [0x03, 0x00]);
+        assert_eq!(deflate(&[], RETAIL_LEVEL), [0x03, 0x00]);
+
+        let fast = [
+            0x0B, 0xC9, 0x48, 0x55, 0x28, 0x2C, 0xCD, 0x4C, 0xCE, 0x56, 0x48, 0x2A,
+            0xCA, 0x2F, 0xCF, 0x53, 0x48, 0xCB, 0xAF, 0x50, 0xC8, 0x2A, 0xCD, 0x2D,
+            0x28, 0x56, 0xC8, 0x2F, 0x4B, 0x2D, 0x52, 0x28, 0x01, 0x4A, 0xE7, 0x24,
+            0x56, 0x55, 0x2A, 0xA4, 0xE4, 0xA7, 0xEB, 0x29, 0x84, 0x8C, 0x2A, 0x1E,
+            0x0D, 0x8D, 0xD1, 0xB4, 0x31, 0x9A, 0x53, 0x46, 0x8B, 0x82, 0xD1, 0x82,
+            0x71, 0x78, 0x55, 0x13, 0x49, 0x89, 0x79, 0x59, 0xF9, 0x0A, 0xD9, 0x89,
+            0x55, 0xF9, 0xF9, 0x99, 0xA9, 0x0A, 0xA3, 0xBC, 0xC1, 0x18, 0x12, 0x00
+        ];
+        assert_eq!(deflate(&p_text(), 1), fast.to_vec());
+
+        let best = [
+            0xED, 0xCA, 0xD9, 0x11, 0x80, 0x20, 0x10, 0x04, 0xD1, 0x54, 0x26, 0x02,
+            0xA3, 0x21, 0x81, 0x45, 0x51, 0x01, 0x65, 0x14, 0xC5, 0x83, 0xE8, 0x35,
+            0x0C, 0xCB, 0xDA, 0xCF, 0xAE, 0x7E, 0x66, 0x74, 0x58, 0x8B, 0x6F, 0x23,
+            0x6C, 0xE6, 0x99, 0xD0, 0xF3, 0x42, 0x28, 0xF3, 0xB2, 0x81, 0x87, 0xCB,
+            0xD8, 0xDF, 0x3D, 0x49, 0xBD, 0xD1, 0x71, 0x68, 0x60, 0x14, 0x2B, 0x56,
+            0xAC, 0x58, 0xB1, 0xE2, 0x7F, 0x61, 0x2B, 0x29, 0x10, 0x51, 0x2A, 0xE9,
+            0x9D, 0xD6, 0x27, 0xEB, 0x01
+        ];
+        assert_eq!(deflate(&p_text(), RETAIL_LEVEL), best.to_vec());
+
+        // big enough for the chain, nice and good lengths of every level to matter: the size and crc32 of gzip's output
+        let expected : [(usize, u32); 9] = [
+            (9335, 0xCDDD1F49), (8818, 0x306F503F), (7727, 0x7A5140C3),
+            (7708, 0x891701FB), (7082, 0x7B2E903B), (6607, 0x2698D7A0),
+            (6424, 0x8B75B91F), (6356, 0x69A60363), (6356, 0x69A60363)
+        ];
+        let words = p_words(0xC000);
+        for (level, (length, crc)) in (1 ..= 9).zip(expected.iter()) {
+            let compressed = deflate(&words, level);
+            assert_eq!((compressed.len(), crc32::crc32(&compressed)), (*length, *crc), "level {}", level);
+        }
+    }
+}
@@ -4,10 +4,16 @@ use crate::dlcommands::g_dl::G_DL;
 use crate::dlcommands::g_enddl::G_ENDDL;
 use crate::dlcommands::g_popmtx::G_POPMTX;
 use crate::dlcommands::g_rdppipesync::G_RDPPIPESYNC;
+use crate::dlcommands::g_setblendcolor::G_SETBLENDCOLOR;
 use crate::dlcommands::g_setcombine::G_SETCOMBINE;
+use crate::dlcommands::g_setenvcolor::G_SETENVCOLOR;
+use crate::dlcommands::g_setfogcolor::G_SETFOGCOLOR;
 use crate::dlcommands::g_setothermode_l::G_SetOtherMode_L;
 use crate::dlcommands::g_setothermode_h::G_SetOtherMode_H;
 use crate::dlcommands::g_setgeometrymode::G_SETGEOMETRYMODE;
+use crate::dlcommands::g_setprimcolor::G_SETPRIMCOLOR;
+use crate::dlcommands::g_settile::G_SETTILE;
+use crate::dlcommands::g_settilesize::G_SETTILESIZE;
 use crate::dlcommands::g_settimg::G_SETTIMG;
 use crate::dlcommands::g_spnoop::G_SPNOOP;
 use crate::dlcommands::g_texture::G_TEXTURE;
@@ -29,10 +35,16 @@ pub enum DisplayListCommand
     G_ENDDL(G_ENDDL),
     G_POPMTX(G_POPMTX),
     G_RDPPIPESYNC(G_RDPPIPESYNC),
+    G_SETBLENDCOLOR(G_SETBLENDCOLOR),
     G_SETCOMBINE(G_SETCOMBINE),
+    G_SETENVCOLOR(G_SETENVCOLOR),
+    G_SETFOGCOLOR(G_SETFOGCOLOR),
     G_SETGEOMETRYMODE(G_SETGEOMETRYMODE),
+    G_SETPRIMCOLOR(G_SETPRIMCOLOR),
     G_SetOtherMode_L(G_SetOtherMode_L),
     G_SetOtherMode_H(G_SetOtherMode_H),
+    G_SETTILE(G_SETTILE),
+    G_SETTILESIZE(G_SETTILESIZE),
     G_SETTIMG(G_SETTIMG),
     G_SPNOOP(G_SPNOOP),
     G_TEXTURE(G_TEXTURE),
@@ -66,7 +78,15 @@ impl DisplayListCommand
             0xBD => Ok(DisplayListCommand::G_POPMTX(G_POPMTX::new(bytes)?)),
             // BE through E6
             0xE7 => Ok(DisplayListCommand::G_RDPPIPESYNC(G_RDPPIPESYNC::new(bytes)?)),
-            // E8 through FB
+            // E8 through F1
+            0xF2 => Ok(DisplayListCommand::G_SETTILESIZE(G_SETTILESIZE::new(bytes)?)),
+            // F3 through F4
+            0xF5 => Ok(DisplayListCommand::G_SETTILE(G_SETTILE::new(bytes)?)),
+            // F6 through F7
+            0xF8 => Ok(DisplayListCommand::G_SETFOGCOLOR(G_SETFOGCOLOR::new(bytes)?)),
+            0xF9 => Ok(DisplayListCommand::G_SETBLENDCOLOR(G_SETBLENDCOLOR::new(bytes)?)),
+            0xFA => Ok(DisplayListCommand::G_SETPRIMCOLOR(G_SETPRIMCOLOR::new(bytes)?)),
+            0xFB => Ok(DisplayListCommand::G_SETENVCOLOR(G_SETENVCOLOR::new(bytes)?)),
             0xFC => Ok(DisplayListCommand::G_SETCOMBINE(G_SETCOMBINE::new(bytes)?)),
             0xFD => Ok(DisplayListCommand::G_SETTIMG(G_SETTIMG::new(bytes)?)),
             // 0xFE - 0xFF
@@ -84,10 +104,16 @@ impl DisplayListCommand
             DisplayListCommand::G_ENDDL(s) => s.psuedo_code(),
             DisplayListCommand::G_POPMTX(s) => s.psuedo_code(),
             DisplayListCommand::G_RDPPIPESYNC(s) => s.psuedo_code(),
+            DisplayListCommand::G_SETBLENDCOLOR(s) => s.psuedo_code(),
             DisplayListCommand::G_SETCOMBINE(s) => s.psuedo_code(),
+            DisplayListCommand::G_SETENVCOLOR(s) => s.psuedo_code(),
+            DisplayListCommand::G_SETFOGCOLOR(s) => s.psuedo_code(),
             DisplayListCommand::G_SETGEOMETRYMODE(s) => s.psuedo_code(),
+            DisplayListCommand::G_SETPRIMCOLOR(s) => s.psuedo_code(),
             DisplayListCommand::G_SetOtherMode_L(s) => s.psuedo_code(),
             DisplayListCommand::G_SetOtherMode_H(s) => s.psuedo_code(),
+            DisplayListCommand::G_SETTILE(s) => s.psuedo_code(),
+            DisplayListCommand::G_SETTILESIZE(s) => s.psuedo_code(),
             DisplayListCommand::G_SETTIMG(s) => s.psuedo_code(),
             DisplayListCommand::G_SPNOOP(s) => s.psuedo_code(),
             DisplayListCommand::G_TEXTURE(s) => s.psuedo_code(),
@@ -125,9 +151,6 @@ impl DisplayList
         self.commands.push(command);
     }
 
-    pub fn command_count (&self) -> usize
-    { return self.commands.len(); }
-
     pub fn commands (&self) -> &Vec<DisplayListCommand>
     { return &self.commands; }
 
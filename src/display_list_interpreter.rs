@@ -0,0 +1,442 @@
+use crate::combiner::CombineMode;
+use crate::display_list::DisplayListCommand;
+use crate::geometry_mode;
+use crate::other_mode;
+use crate::model::Model;
+use crate::segment_map::ResolvedAddress;
+use crate::texture::TextureAddress;
+use crate::tile_descriptor::{TileDescriptor, TILE_COUNT};
+
+//
+/// The number of slots in the RSP vertex buffer (F3DEX)
+//
+pub const VERTEX_BUFFER_SIZE : usize = 32;
+
+//
+/// The geometry mode the game has set when it starts drawing a model (models can change it)
+//
+pub const DEFAULT_GEOMETRY_MODE : u32 = geometry_mode::G_ZBUFFER | geometry_mode::G_SHADE
+    | geometry_mode::G_SHADING_SMOOTH | geometry_mode::G_CULL_BACK;
+
+//
+/// The RSP state a triangle was drawn with
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderState
+{
+    //
+    /// The geometry mode flags (see geometry_mode)
+    //
+    pub geometry_mode: u32,
+
+    //
+    /// If texturing was turned on (G_TEXTURE)
+    //
+    pub texture_enabled: bool,
+
+    //
+    /// The S and T scale for texture coordinates from G_TEXTURE (0.16 fixed point)
+    //
+    pub texture_scale: [u16; 2],
+
+    //
+    /// The tile descriptor used for drawing (G_TEXTURE)
+    //
+    pub texture_tile: u8,
+
+    //
+    /// How many mipmap levels there are past the first (G_TEXTURE)
+    //
+    pub mipmap_levels: u8,
+
+    //
+    /// The higher word of the RDP other modes (see other_mode)
+    //
+    pub other_mode_high: u32,
+
+    //
+    /// The lower word of the RDP other modes: alpha compare and the render mode (see other_mode)
+    //
+    pub other_mode_low: u32,
+
+    //
+    /// The combiner settings (G_SETCOMBINE)
+    //
+    pub combine: CombineMode,
+
+    //
+    /// The colors the combiner and blender can use (RGBA)
+    //
+    pub primitive_color: [u8; 4],
+    pub environment_color: [u8; 4],
+    pub blend_color: [u8; 4],
+    pub fog_color: [u8; 4],
+
+    //
+    /// The LOD fraction from G_SETPRIMCOLOR (0.8 fixed point)
+    //
+    pub primitive_lod_fraction: u8,
+
+    //
+    /// The tile descriptors (None if the display list hasnt set them)
+    //
+    pub tiles: [Option<TileDescriptor>; TILE_COUNT]
+}
+
+impl Default for RenderState
+{
+    fn default () -> RenderState
+    {
+        return RenderState {
+            geometry_mode: DEFAULT_GEOMETRY_MODE,
+            texture_enabled: true,
+            texture_scale: [0xFFFF, 0xFFFF],
+            texture_tile: 0,
+            mipmap_levels: 0,
+            other_mode_high: other_mode::DEFAULT_OTHER_MODE_H,
+            other_mode_low: other_mode::DEFAULT_OTHER_MODE_L,
+            combine: CombineMode::default(),
+            primitive_color: [0xFF; 4],
+            environment_color: [0xFF; 4],
+            blend_color: [0; 4],
+            fog_color: [0; 4],
+            primitive_lod_fraction: 0,
+            tiles: [None; TILE_COUNT]
+        };
+    }
+}
+
+//
+/// A triangle produced by running the display list
+//
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle
+{
+    //
+    /// Indicies into the vertex store
+    //
+    pub verticies: [usize; 3],
+
+    //
+    /// The texture which was set (G_SETTIMG) when the triangle was drawn
+    //
+    pub texture: Option<usize>,
+
+    //
+    /// The state when the triangle was drawn
+    //
+    pub state: RenderState
+}
+
+//
+/// How a vertex's rgb_or_norm is used, from the geometry mode when G_VTX loaded it
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VertexKind
+{
+    //
+    /// No G_VTX loads it
+    //
+    Unused,
+
+    //
+    /// Loaded with G_LIGHTING off, so it holds a color
+    //
+    Colored,
+
+    //
+    /// Loaded with G_LIGHTING on, so it holds a normal
+    //
+    Lit,
+
+    //
+    /// Loaded both with G_LIGHTING on and off
+    //
+    Both
+}
+
+//
+/// Everything produced by running the display list
+//
+pub struct InterpreterResult
+{
+    //
+    /// Every triangle drawn, in order
+    //
+    pub triangles: Vec<Triangle>,
+
+    //
+    /// Every G_SETTIMG (command index) and where it points in the textures
+    //
+    pub texture_loads: Vec<(usize, TextureAddress)>,
+
+    //
+    /// How each vertex in the vertex store is used (by vertex store index)
+    //
+    pub vertex_kinds: Vec<VertexKind>,
+
+    //
+    /// Problems found while running (bad addresses, unloaded vertex slots, etc)
+    //
+    pub warnings: Vec<String>
+}
+
+//
+/// Runs a model's display list the way the RSP would, tracking the vertex buffer
+/// so triangles can be resolved back to vertex store entries.
+///
+/// The display list is a set of sub lists (each ending with G_ENDDL) which the
+/// geometry layout calls into, so everything is run in order and G_ENDDL just
+/// ends the current sub list. G_DL calls are not followed since their targets
+/// are already part of the walk.
+//
+pub struct DisplayListInterpreter<'a>
+{
+    m_model: &'a Model,
+
+    //
+    /// What vertex store entry is in each slot of the vertex buffer
+    //
+    m_vertex_buffer: [Option<usize>; VERTEX_BUFFER_SIZE],
+
+    //
+    /// The texture from the last G_SETTIMG
+    //
+    m_texture: Option<usize>,
+
+    //
+    /// Geometry mode and texture settings
+    //
+    m_state: RenderState,
+
+    m_texture_loads: Vec<(usize, TextureAddress)>,
+    m_vertex_kinds: Vec<VertexKind>,
+    m_triangles: Vec<Triangle>,
+    m_warnings: Vec<String>
+}
+
+impl<'a> DisplayListInterpreter<'a>
+{
+    //
+    /// Run the display list for the given model
+    //
+    pub fn run (model : &'a Model) -> InterpreterResult
+    {
+        let mut interpreter = DisplayListInterpreter {
+            m_model: model,
+            m_vertex_buffer: [None; VERTEX_BUFFER_SIZE],
+            m_texture: None,
+            m_state: RenderState::default(),
+            m_texture_loads: Vec::new(),
+            m_vertex_kinds: vec![VertexKind::Unused; model.vertex_store().verticies().len()],
+            m_triangles: Vec::new(),
+            m_warnings: Vec::new()
+        };
+
+        for (index, command) in model.display_list().commands().iter().enumerate() {
+            interpreter.p_execute(index, command);
+        }
+
+        return InterpreterResult {
+            triangles: interpreter.m_triangles,
+            texture_loads: interpreter.m_texture_loads,
+            vertex_kinds: interpreter.m_vertex_kinds,
+            warnings: interpreter.m_warnings
+        };
+    }
+
+    // --- private
+
+    fn p_execute (&mut self, index : usize, command : &DisplayListCommand)
+    {
+        match command {
+            DisplayListCommand::G_VTX(vtx) => {
+                let vertex_count = self.m_model.vertex_store().verticies().len();
+                let first = match self.m_model.resolve_command(command) {
+                    Some(ResolvedAddress::Verticies { first, .. }) => first,
+                    resolved => {
+                        let description = resolved.map(|r| r.describe()).unwrap_or_default();
+                        self.m_warnings.push(format!("command {}: G_VTX does not point at the vertex store ({})", index, description));
+                        for i in 0 .. vtx.count() as usize {
+                            if let Some(slot) = self.m_vertex_buffer.get_mut(vtx.vertex_start() as usize + i) {
+                                *slot = None;
+                            }
+                        }
+                        return;
+                    }
+                };
+
+                for i in 0 .. vtx.count() as usize {
+                    let slot = vtx.vertex_start() as usize + i;
+                    if slot >= VERTEX_BUFFER_SIZE {
+                        self.m_warnings.push(format!("command {}: G_VTX loads past the end of the vertex buffer", index));
+                        break;
+                    }
+
+                    let vertex = first + i;
+                    if vertex >= vertex_count {
+                        self.m_warnings.push(format!("command {}: G_VTX vertex {} is outside the vertex store", index, vertex));
+                        self.m_vertex_buffer[slot] = None;
+                    } else {
+                        self.m_vertex_buffer[slot] = Some(vertex);
+
+                        // the RSP lights verticies as they are loaded
+                        let lit = self.m_state.geometry_mode & geometry_mode::G_LIGHTING != 0;
+                        let kind = &mut self.m_vertex_kinds[vertex];
+                        *kind = match (*kind, lit) {
+                            (VertexKind::Unused, false) | (VertexKind::Colored, false) => VertexKind::Colored,
+                            (VertexKind::Unused, true) | (VertexKind::Lit, true) => VertexKind::Lit,
+                            _ => VertexKind::Both
+                        };
+                    }
+                }
+            },
+
+            DisplayListCommand::G_SETTIMG(settimg) => {
+                let texture = self.m_model.texture_for_address(settimg.segment_address_of_texture());
+                if texture.texture_index().is_none() {
+                    self.m_warnings.push(format!("command {}: G_SETTIMG {}", index, texture.describe()));
+                }
+
+                self.m_texture = texture.texture_index();
+                self.m_texture_loads.push((index, texture));
+            },
+
+            DisplayListCommand::G_SETGEOMETRYMODE(mode) => {
+                self.m_state.geometry_mode |= mode.flags();
+            },
+
+            DisplayListCommand::G_CLEARGEOMETRYMODE(mode) => {
+                self.m_state.geometry_mode &= !mode.flags();
+            },
+
+            DisplayListCommand::G_TEXTURE(texture) => {
+                self.m_state.texture_enabled = texture.enabled();
+                self.m_state.texture_scale = [texture.scale_s(), texture.scale_t()];
+                self.m_state.texture_tile = texture.tile();
+                self.m_state.mipmap_levels = texture.max_mipmap_levels();
+            },
+
+            DisplayListCommand::G_SetOtherMode_H(mode) => {
+                self.m_state.other_mode_high = mode.apply(self.m_state.other_mode_high);
+            },
+
+            DisplayListCommand::G_SetOtherMode_L(mode) => {
+                self.m_state.other_mode_low = mode.apply(self.m_state.other_mode_low);
+            },
+
+            DisplayListCommand::G_SETCOMBINE(combine) => {
+                self.m_state.combine = combine.mode();
+            },
+
+            DisplayListCommand::G_SETPRIMCOLOR(color) => {
+                self.m_state.primitive_color = color.color();
+                self.m_state.primitive_lod_fraction = color.lod_fraction();
+            },
+
+            DisplayListCommand::G_SETENVCOLOR(color) => {
+                self.m_state.environment_color = color.color();
+            },
+
+            DisplayListCommand::G_SETBLENDCOLOR(color) => {
+                self.m_state.blend_color = color.color();
+            },
+
+            DisplayListCommand::G_SETFOGCOLOR(color) => {
+                self.m_state.fog_color = color.color();
+            },
+
+            DisplayListCommand::G_SETTILE(settile) => {
+                self.m_state.tiles[settile.tile() as usize].get_or_insert_with(TileDescriptor::default).set_tile(settile);
+            },
+
+            DisplayListCommand::G_SETTILESIZE(settilesize) => {
+                self.m_state.tiles[settilesize.tile() as usize].get_or_insert_with(TileDescriptor::default).set_size(settilesize);
+            },
+
+            DisplayListCommand::G_TRI2(tri2) => {
+                for tri in tri2.triangles().iter() {
+                    self.p_add_triangle(index, tri);
+                }
+            },
+
+            _ => {}
+        }
+    }
+
+    fn p_add_triangle (&mut self, index : usize, slots : &[u8; 3])
+    {
+        // degenerate triangles (every corner the same slot) pad out a G_TRI2 which only draws one
+        if slots[0] == slots[1] && slots[1] == slots[2] {
+            return;
+        }
+
+        let mut verticies = [0usize; 3];
+        for (v, slot) in verticies.iter_mut().zip(slots.iter()) {
+            match self.m_vertex_buffer.get(*slot as usize).copied().flatten() {
+                Some(vertex) => *v = vertex,
+                None => {
+                    self.m_warnings.push(format!("command {}: triangle uses vertex slot {} which has nothing loaded", index, slot));
+                    return;
+                }
+            }
+        }
+
+        self.m_triangles.push(Triangle {
+            verticies,
+            texture: self.m_texture,
+            state: self.m_state
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_models::*;
+
+    #[test]
+    fn tracks_how_verticies_are_loaded ()
+    {
+        let mut builder = ModelBuilder::new();
+        for i in 0 .. 5 {
+            builder.vertex([i * 10, 0, 0], [0, 0], [0x7F, 0x00, 0x81], 0xFF);
+        }
+
+        // 0 and 1 as colors, 2 and 3 as normals, then 0 again as a normal and 2 again as a color
+        builder.command(g_vtx(0, 2, vertex_address(0)));
+        builder.command(g_setgeometrymode(geometry_mode::G_LIGHTING));
+        builder.command(g_vtx(0, 2, vertex_address(2)));
+        builder.command(g_vtx(0, 1, vertex_address(0)));
+        builder.command(g_cleargeometrymode(geometry_mode::G_LIGHTING));
+        builder.command(g_vtx(0, 1, vertex_address(2)));
+
+        let model = Model::from_bytes(builder.build()).unwrap();
+        let result = DisplayListInterpreter::run(&model);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+        assert_eq!(result.vertex_kinds, [VertexKind::Both, VertexKind::Colored, VertexKind::Both, VertexKind::Lit, VertexKind::Unused]);
+    }
+
+    #[test]
+    fn decodes_signed_normals ()
+    {
+        let mut builder = ModelBuilder::new();
+        builder.vertex([0, 0, 0], [0, 0], [0x7F, 0x00, 0x81], 0xFF);
+        builder.vertex([0, 0, 0], [0, 0], [0x00, 0x80, 0x00], 0xFF);
+        builder.vertex([0, 0, 0], [0, 0], [0x00, 0x00, 0x00], 0xFF);
+        builder.command(g_setgeometrymode(geometry_mode::G_LIGHTING));
+        builder.command(g_vtx(0, 3, vertex_address(0)));
+
+        let model = Model::from_bytes(builder.build()).unwrap();
+        assert_eq!(DisplayListInterpreter::run(&model).vertex_kinds, [VertexKind::Lit; 3]);
+
+        // 0x81 is -127 and 0x80 is -128, not 129 and 128
+        let verticies = model.vertex_store().verticies();
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let normal = verticies[0].normal();
+        assert!((normal[0] - half).abs() < 1e-9 && normal[1] == 0.0 && (normal[2] + half).abs() < 1e-9, "{:?}", normal);
+        assert_eq!(verticies[1].normal(), [0.0, -1.0, 0.0]);
+        assert_eq!(verticies[2].normal(), [0.0; 3]);
+    }
+}
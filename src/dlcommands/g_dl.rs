@@ -28,6 +28,12 @@ impl G_DL
         });
     }
     
+    //
+    /// The segmented address of the display list to run
+    //
+    pub fn branch_segmented_address (&self) -> u32
+    { return self.m_branch_segmented_address; }
+
     pub fn psuedo_code (&self) -> String
     {
         return format!("G_DL storeReturnAddr:{} branchAddr:{:#X}",
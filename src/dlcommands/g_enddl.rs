@@ -17,6 +17,6 @@ impl G_ENDDL
     
     pub fn psuedo_code (&self) -> String
     {
-        return format!("G_ENDDL");
+        return "G_ENDDL".to_string();
     }
 }
\ No newline at end of file
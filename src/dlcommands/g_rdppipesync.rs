@@ -19,6 +19,6 @@ impl G_RDPPIPESYNC
     
     pub fn psuedo_code (&self) -> String
     {
-        return format!("G_RDPPIPESYNC");
+        return "G_RDPPIPESYNC".to_string();
     }
 }
\ No newline at end of file
@@ -0,0 +1,33 @@
+use crate::errors::Result;
+
+//
+/// G_SETBLENDCOLOR: Sets the blend color (used by the blender and alpha compare)
+//
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+pub struct G_SETBLENDCOLOR
+{
+    m_color: [u8; 4]
+}
+
+impl G_SETBLENDCOLOR
+{
+    pub fn new (cmd : [u8;8]) -> Result<G_SETBLENDCOLOR>
+    {
+        // format is: F9 00 00 00 [rr gg bb aa]
+        return Ok(G_SETBLENDCOLOR{
+            m_color: [cmd[4], cmd[5], cmd[6], cmd[7]]
+        });
+    }
+
+    //
+    /// The color as RGBA
+    //
+    pub fn color (&self) -> [u8; 4]
+    { return self.m_color; }
+
+    pub fn psuedo_code (&self) -> String
+    {
+        return format!("G_SETBLENDCOLOR color:{:?}", self.m_color);
+    }
+}
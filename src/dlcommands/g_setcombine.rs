@@ -1,3 +1,4 @@
+use crate::combiner::CombineMode;
 use crate::errors::Result;
 
 //
@@ -6,18 +7,27 @@ use crate::errors::Result;
 #[allow(non_camel_case_types)]
 #[derive(Copy, Clone, Debug)]
 pub struct G_SETCOMBINE
-{}
+{
+    m_mode: CombineMode
+}
 
 impl G_SETCOMBINE
 {
-    pub fn new (_cmd : [u8;8]) -> Result<G_SETCOMBINE>
+    pub fn new (cmd : [u8;8]) -> Result<G_SETCOMBINE>
     {
-        // TODO: this has a bunch of flags for customing the combiner.
-        return Ok(G_SETCOMBINE{});
+        return Ok(G_SETCOMBINE{
+            m_mode: CombineMode::from_command(cmd)
+        });
     }
-    
+
+    //
+    /// The combiner settings for both cycles
+    //
+    pub fn mode (&self) -> CombineMode
+    { return self.m_mode; }
+
     pub fn psuedo_code (&self) -> String
     {
-        return format!("G_SETCOMBINE [TODO: flags]");
+        return format!("G_SETCOMBINE {}", self.m_mode.describe());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,33 @@
+use crate::errors::Result;
+
+//
+/// G_SETENVCOLOR: Sets the environment color
+//
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+pub struct G_SETENVCOLOR
+{
+    m_color: [u8; 4]
+}
+
+impl G_SETENVCOLOR
+{
+    pub fn new (cmd : [u8;8]) -> Result<G_SETENVCOLOR>
+    {
+        // format is: FB 00 00 00 [rr gg bb aa]
+        return Ok(G_SETENVCOLOR{
+            m_color: [cmd[4], cmd[5], cmd[6], cmd[7]]
+        });
+    }
+
+    //
+    /// The color as RGBA
+    //
+    pub fn color (&self) -> [u8; 4]
+    { return self.m_color; }
+
+    pub fn psuedo_code (&self) -> String
+    {
+        return format!("G_SETENVCOLOR color:{:?}", self.m_color);
+    }
+}
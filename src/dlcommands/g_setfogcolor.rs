@@ -0,0 +1,33 @@
+use crate::errors::Result;
+
+//
+/// G_SETFOGCOLOR: Sets the fog color
+//
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+pub struct G_SETFOGCOLOR
+{
+    m_color: [u8; 4]
+}
+
+impl G_SETFOGCOLOR
+{
+    pub fn new (cmd : [u8;8]) -> Result<G_SETFOGCOLOR>
+    {
+        // format is: F8 00 00 00 [rr gg bb aa]
+        return Ok(G_SETFOGCOLOR{
+            m_color: [cmd[4], cmd[5], cmd[6], cmd[7]]
+        });
+    }
+
+    //
+    /// The color as RGBA
+    //
+    pub fn color (&self) -> [u8; 4]
+    { return self.m_color; }
+
+    pub fn psuedo_code (&self) -> String
+    {
+        return format!("G_SETFOGCOLOR color:{:?}", self.m_color);
+    }
+}
@@ -1,4 +1,9 @@
 use crate::errors::Result;
+use crate::errors::FailedSliceError;
+use crate::geometry_mode;
+
+use snafu::ResultExt;
+use std::convert::TryInto;
 
 //
 /// G_SETGEOMETRYMODE: Enables certain geometry parameters (ex. lighting, front-/backface culling, Z-buffer).
@@ -7,18 +12,29 @@ use crate::errors::Result;
 #[derive(Copy, Clone, Debug)]
 pub struct G_SETGEOMETRYMODE
 {
+    m_flags: u32
 }
 
 impl G_SETGEOMETRYMODE
 {
-    pub fn new (_cmd : [u8;8]) -> Result<G_SETGEOMETRYMODE>
+    pub fn new (cmd : [u8;8]) -> Result<G_SETGEOMETRYMODE>
     {
-        // TODO store the flags
-        return Ok(G_SETGEOMETRYMODE{});
+        // format is: B7 [00 00 00] [ff ff ff ff] where f are the flags to set
+        let flags = u32::from_be_bytes(cmd[4..8].try_into().context(FailedSliceError)?);
+
+        return Ok(G_SETGEOMETRYMODE{
+            m_flags: flags
+        });
     }
-    
+
+    //
+    /// The geometry mode flags to set (see geometry_mode)
+    //
+    pub fn flags (&self) -> u32
+    { return self.m_flags; }
+
     pub fn psuedo_code (&self) -> String
     {
-        return format!("G_SETGEOMETRYMODE [TODO: flags]");
+        return format!("G_SETGEOMETRYMODE {}", geometry_mode::describe(self.m_flags));
     }
 }
\ No newline at end of file
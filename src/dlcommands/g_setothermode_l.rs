@@ -1,4 +1,9 @@
 use crate::errors::Result;
+use crate::errors::FailedSliceError;
+use crate::other_mode;
+
+use snafu::ResultExt;
+use std::convert::TryInto;
 
 //
 /// G_SetOtherMode_L: Sets the lower half of the RDP Other modes
@@ -6,18 +11,35 @@ use crate::errors::Result;
 #[allow(non_camel_case_types)]
 #[derive(Copy, Clone, Debug)]
 pub struct G_SetOtherMode_L
-{}
+{
+    m_shift: u8,
+    m_length: u8,
+    m_data: u32
+}
 
 impl G_SetOtherMode_L
 {
-    pub fn new (_cmd : [u8;8]) -> Result<G_SetOtherMode_L>
+    pub fn new (cmd : [u8;8]) -> Result<G_SetOtherMode_L>
     {
-        // TODO: flags. They're stored weirdly.
-        return Ok(G_SetOtherMode_L{});
+        // format is: B9 00 [ss] [nn] [dd dd dd dd]
+        // replaces the n bits at shift s with the same bits of d
+        let data = u32::from_be_bytes(cmd[4..8].try_into().context(FailedSliceError)?);
+
+        return Ok(G_SetOtherMode_L{
+            m_shift: cmd[2],
+            m_length: cmd[3],
+            m_data: data
+        });
     }
-    
+
+    //
+    /// Apply the command to the lower word of the other modes
+    //
+    pub fn apply (&self, mode : u32) -> u32
+    { return other_mode::apply(mode, self.m_shift as u32, self.m_length as u32, self.m_data); }
+
     pub fn psuedo_code (&self) -> String
     {
-        return format!("G_SetOtherMode_L [TODO:flags]");
+        return format!("G_SetOtherMode_L {}", other_mode::describe_low(self.m_shift as u32, self.m_length as u32, self.m_data));
     }
-}
\ No newline at end of file
+}
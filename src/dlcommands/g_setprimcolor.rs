@@ -0,0 +1,43 @@
+use crate::errors::Result;
+
+//
+/// G_SETPRIMCOLOR: Sets the primitive color (and the minimum/fraction LOD level)
+//
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+pub struct G_SETPRIMCOLOR
+{
+    m_minimum_level: u8,
+    m_lod_fraction: u8,
+    m_color: [u8; 4]
+}
+
+impl G_SETPRIMCOLOR
+{
+    pub fn new (cmd : [u8;8]) -> Result<G_SETPRIMCOLOR>
+    {
+        // format is: FA 00 [mm] [ll] [rr gg bb aa] where m is the minimum LOD level, l the LOD fraction
+        return Ok(G_SETPRIMCOLOR{
+            m_minimum_level: cmd[2],
+            m_lod_fraction: cmd[3],
+            m_color: [cmd[4], cmd[5], cmd[6], cmd[7]]
+        });
+    }
+
+    //
+    /// The LOD fraction given to the combiner (0.8 fixed point)
+    //
+    pub fn lod_fraction (&self) -> u8
+    { return self.m_lod_fraction; }
+
+    //
+    /// The color as RGBA
+    //
+    pub fn color (&self) -> [u8; 4]
+    { return self.m_color; }
+
+    pub fn psuedo_code (&self) -> String
+    {
+        return format!("G_SETPRIMCOLOR color:{:?} minLevel:{} lodFraction:{}", self.m_color, self.m_minimum_level, self.m_lod_fraction);
+    }
+}
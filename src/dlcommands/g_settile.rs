@@ -0,0 +1,109 @@
+use crate::dlcommands::g_settimg;
+use crate::errors::Result;
+use crate::errors::FailedSliceError;
+
+use snafu::ResultExt;
+use std::convert::TryInto;
+
+//
+/// G_SETTILE: Sets the parameters of a tile descriptor (format, where it is in TMEM, wrapping)
+//
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+pub struct G_SETTILE
+{
+    m_format: u8,
+    m_bit_size: u8,
+    m_line: u16, // in 64 bit words
+    m_tmem_address: u16, // in 64 bit words
+    m_tile: u8,
+    m_palette: u8,
+    m_clamp_mirror_t: u8,
+    m_mask_t: u8,
+    m_shift_t: u8,
+    m_clamp_mirror_s: u8,
+    m_mask_s: u8,
+    m_shift_s: u8
+}
+
+impl G_SETTILE
+{
+    pub fn new (cmd : [u8;8]) -> Result<G_SETTILE>
+    {
+        // format is: F5 [fffi i0nn nnnn nnnm mmmm mmmm] [0000 0ttt pppp cc aaaa ssss dd bbbb uuuu]
+        // f: format, i: bit size, n: line, m: tmem address, t: tile, p: palette
+        // c/a/s: clamp+mirror, mask, shift for T. d/b/u: clamp+mirror, mask, shift for S
+        let high = u32::from_be_bytes(cmd[0..4].try_into().context(FailedSliceError)?);
+        let low = u32::from_be_bytes(cmd[4..8].try_into().context(FailedSliceError)?);
+
+        return Ok(G_SETTILE{
+            m_format: ((high >> 21) & 0x07) as u8,
+            m_bit_size: ((high >> 19) & 0x03) as u8,
+            m_line: ((high >> 9) & 0x1FF) as u16,
+            m_tmem_address: (high & 0x1FF) as u16,
+            m_tile: ((low >> 24) & 0x07) as u8,
+            m_palette: ((low >> 20) & 0x0F) as u8,
+            m_clamp_mirror_t: ((low >> 18) & 0x03) as u8,
+            m_mask_t: ((low >> 14) & 0x0F) as u8,
+            m_shift_t: ((low >> 10) & 0x0F) as u8,
+            m_clamp_mirror_s: ((low >> 8) & 0x03) as u8,
+            m_mask_s: ((low >> 4) & 0x0F) as u8,
+            m_shift_s: (low & 0x0F) as u8
+        });
+    }
+
+    //
+    /// The size of a row in TMEM, in 64 bit words
+    //
+    pub fn line (&self) -> u16
+    { return self.m_line; }
+
+    //
+    /// Where the tile starts in TMEM, in 64 bit words
+    //
+    pub fn tmem_address (&self) -> u16
+    { return self.m_tmem_address; }
+
+    //
+    /// The tile descriptor (0-7) being set
+    //
+    pub fn tile (&self) -> u8
+    { return self.m_tile; }
+
+    //
+    /// The clamp (bit 1) and mirror (bit 0) flags for S and T
+    //
+    pub fn clamp_mirror (&self) -> [u8; 2]
+    { return [self.m_clamp_mirror_s, self.m_clamp_mirror_t]; }
+
+    //
+    /// The wrap size for S and T as a power of 2 (0 turns off wrapping)
+    //
+    pub fn mask (&self) -> [u8; 2]
+    { return [self.m_mask_s, self.m_mask_t]; }
+
+    //
+    /// How much the S and T coordinates are shifted (1-10 right, 11-15 left by 16-shift)
+    //
+    pub fn shift (&self) -> [u8; 2]
+    { return [self.m_shift_s, self.m_shift_t]; }
+
+    pub fn psuedo_code (&self) -> String
+    {
+        return format!("G_SETTILE tile:{} format:{}_{} line:{} tmem:{:#X} palette:{} s:[{} mask:{} shift:{}] t:[{} mask:{} shift:{}]",
+            self.m_tile, g_settimg::texture_format_name(self.m_format), g_settimg::texture_bit_size(self.m_bit_size), self.m_line, self.m_tmem_address, self.m_palette,
+            p_clamp_mirror_name(self.m_clamp_mirror_s), self.m_mask_s, self.m_shift_s,
+            p_clamp_mirror_name(self.m_clamp_mirror_t), self.m_mask_t, self.m_shift_t
+        );
+    }
+}
+
+fn p_clamp_mirror_name (flags : u8) -> &'static str
+{
+    return match flags & 0x03 {
+        0 => "wrap",
+        1 => "mirror",
+        2 => "clamp",
+        _ => "mirror|clamp"
+    };
+}
@@ -0,0 +1,67 @@
+use crate::errors::Result;
+use crate::errors::FailedSliceError;
+
+use snafu::ResultExt;
+use std::convert::TryInto;
+
+//
+/// G_SETTILESIZE: Sets the area of the texture a tile descriptor covers
+//
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+pub struct G_SETTILESIZE
+{
+    m_tile: u8,
+
+    // all 10.2 fixed point
+    m_upper_left_s: u16,
+    m_upper_left_t: u16,
+    m_lower_right_s: u16,
+    m_lower_right_t: u16
+}
+
+impl G_SETTILESIZE
+{
+    pub fn new (cmd : [u8;8]) -> Result<G_SETTILESIZE>
+    {
+        // format is: F2 [ssss ssss ssss tttt tttt tttt] [0000 0iii SSSS SSSS SSSS TTTT TTTT TTTT]
+        // s/t: upper left, i: tile, S/T: lower right
+        let high = u32::from_be_bytes(cmd[0..4].try_into().context(FailedSliceError)?);
+        let low = u32::from_be_bytes(cmd[4..8].try_into().context(FailedSliceError)?);
+
+        return Ok(G_SETTILESIZE{
+            m_tile: ((low >> 24) & 0x07) as u8,
+            m_upper_left_s: ((high >> 12) & 0xFFF) as u16,
+            m_upper_left_t: (high & 0xFFF) as u16,
+            m_lower_right_s: ((low >> 12) & 0xFFF) as u16,
+            m_lower_right_t: (low & 0xFFF) as u16
+        });
+    }
+
+    //
+    /// The tile descriptor (0-7) being set
+    //
+    pub fn tile (&self) -> u8
+    { return self.m_tile; }
+
+    //
+    /// The upper left S and T coordinate (10.2 fixed point)
+    //
+    pub fn upper_left (&self) -> [u16; 2]
+    { return [self.m_upper_left_s, self.m_upper_left_t]; }
+
+    //
+    /// The lower right S and T coordinate (10.2 fixed point)
+    //
+    pub fn lower_right (&self) -> [u16; 2]
+    { return [self.m_lower_right_s, self.m_lower_right_t]; }
+
+    pub fn psuedo_code (&self) -> String
+    {
+        return format!("G_SETTILESIZE tile:{} upperLeft:[{}, {}] lowerRight:[{}, {}]",
+            self.m_tile,
+            self.m_upper_left_s as f64 / 4.0, self.m_upper_left_t as f64 / 4.0,
+            self.m_lower_right_s as f64 / 4.0, self.m_lower_right_t as f64 / 4.0
+        );
+    }
+}
@@ -33,6 +33,12 @@ impl G_SETTIMG
         });
     }
     
+    //
+    /// The segmented address of the texture
+    //
+    pub fn segment_address_of_texture (&self) -> u32
+    { return self.m_segment_address_of_texture; }
+
     pub fn psuedo_code (&self) -> String
     {
         return format!("G_SETTIMG format:{}_{} segAddrOfTex:{:#X}",
@@ -43,24 +49,38 @@ impl G_SETTIMG
     }
 
     pub fn p_texture_format_name (&self) -> String {
-        return match self.m_texture_format_flag {
-            0 => "RGBA".to_string(),
-            1 => "YUV".to_string(),
-            2 => "CI".to_string(), // index + lookup
-            3 => "IA".to_string(), // grayscale + alpha
-            4 => "I".to_string(), // grayscale
-
-            _ => "UNK".to_string()
-        };
+        return texture_format_name(self.m_texture_format_flag);
     }
 
     pub fn p_texture_bitsize (&self) -> u8 {
-        return match self.m_texture_bit_size_flag {
-            0 => 4,
-            1 => 8,
-            2 => 16,
-            3 => 32,
-            _ => 0
-        }
+        return texture_bit_size(self.m_texture_bit_size_flag);
     }
-}
\ No newline at end of file
+}
+
+//
+/// The name of a texture format flag (G_SETTIMG, G_SETTILE)
+//
+pub fn texture_format_name (flag : u8) -> String {
+    return match flag {
+        0 => "RGBA".to_string(),
+        1 => "YUV".to_string(),
+        2 => "CI".to_string(), // index + lookup
+        3 => "IA".to_string(), // grayscale + alpha
+        4 => "I".to_string(), // grayscale
+
+        _ => "UNK".to_string()
+    };
+}
+
+//
+/// The bits per texel of a bit size flag (G_SETTIMG, G_SETTILE)
+//
+pub fn texture_bit_size (flag : u8) -> u8 {
+    return match flag {
+        0 => 4,
+        1 => 8,
+        2 => 16,
+        3 => 32,
+        _ => 0
+    }
+}
@@ -18,6 +18,6 @@ impl G_SPNOOP
     
     pub fn psuedo_code (&self) -> String
     {
-        return format!("G_SPNOOP");
+        return "G_SPNOOP".to_string();
     }
 }
\ No newline at end of file
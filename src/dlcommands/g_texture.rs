@@ -38,7 +38,37 @@ impl G_TEXTURE
             m_tile_descriptor_to_enable_disable : tile_descriptor_to_enable_disable
         });
     }
-    
+
+    //
+    /// If texturing is turned on
+    //
+    pub fn enabled (&self) -> bool
+    { return self.m_enable_or_disable_tile_descriptor; }
+
+    //
+    /// The S (horizontal) scale for texture coordinates, as 0.16 fixed point (0xFFFF is about 1.0)
+    //
+    pub fn scale_s (&self) -> u16
+    { return self.m_scaling_factor_s; }
+
+    //
+    /// The T (vertical) scale for texture coordinates, as 0.16 fixed point (0xFFFF is about 1.0)
+    //
+    pub fn scale_t (&self) -> u16
+    { return self.m_scaling_factor_t; }
+
+    //
+    /// The tile descriptor used for drawing
+    //
+    pub fn tile (&self) -> u8
+    { return self.m_tile_descriptor_to_enable_disable; }
+
+    //
+    /// How many mipmap levels there are past the first one
+    //
+    pub fn max_mipmap_levels (&self) -> u8
+    { return self.m_max_mipmap_levels_other_than_first; }
+
     pub fn psuedo_code (&self) -> String
     {
         if self.m_enable_or_disable_tile_descriptor {
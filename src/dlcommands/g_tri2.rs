@@ -40,6 +40,17 @@ impl G_TRI2
         });
     }
     
+    //
+    /// The two triangles, as vertex buffer slots (undoing the *2)
+    //
+    pub fn triangles (&self) -> [[u8; 3]; 2]
+    {
+        return [
+            [self.m_vert1/2, self.m_vert2/2, self.m_vert3/2],
+            [self.m_vert4/2, self.m_vert5/2, self.m_vert6/2]
+        ];
+    }
+
     pub fn psuedo_code (&self) -> String
     {
         // we display as the proper index (undoing the *2)
@@ -34,6 +34,24 @@ impl G_VTX
         });
     }
     
+    //
+    /// The first slot in the vertex buffer to load into
+    //
+    pub fn vertex_start (&self) -> u8
+    { return self.m_vertex_start / 2; }
+
+    //
+    /// How many verticies to load
+    //
+    pub fn count (&self) -> u8
+    { return self.m_count; }
+
+    //
+    /// The segmented address of the first vertex
+    //
+    pub fn segmented_address (&self) -> u32
+    { return self.m_segmented_address; }
+
     pub fn psuedo_code (&self) -> String
     {
         return format!("G_VTX start:{} count:{} dataLen:{:#X} segAddr:{:#X}",
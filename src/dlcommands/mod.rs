@@ -4,10 +4,16 @@ pub(crate) mod g_enddl;
 pub(crate) mod g_tri2;
 pub(crate) mod g_popmtx;
 pub(crate) mod g_rdppipesync;
+pub(crate) mod g_setblendcolor;
 pub(crate) mod g_setcombine;
+pub(crate) mod g_setenvcolor;
+pub(crate) mod g_setfogcolor;
 pub(crate) mod g_setgeometrymode;
 pub(crate) mod g_setothermode_l;
 pub(crate) mod g_setothermode_h;
+pub(crate) mod g_setprimcolor;
+pub(crate) mod g_settile;
+pub(crate) mod g_settilesize;
 pub(crate) mod g_settimg;
 pub(crate) mod g_spnoop;
 pub(crate) mod g_texture;
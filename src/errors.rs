@@ -1,18 +1,61 @@
+use crate::texture_type::TextureType;
+
 use snafu::Snafu;
 
 /// Main error type for model errors
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 /// Main error type
+// the variants double as the context selectors (errors::IOError etc) used throughout, so their names stay
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Snafu)]
 #[snafu(visibility="pub(crate)")]
 pub enum Error {
-    #[snafu(display("Unknown error occurred"))]
-    Unknown,
-
     #[snafu(display("Invalid magic in file: expected {:#X} got {:#X}", expected_magic, got_magic))]
     InvalidMagic { expected_magic: u32, got_magic: u32 },
     
+    #[snafu(display("Section '{}' at {:#X} runs past the end of the data ({:#X} bytes)", section, offset, length))]
+    SectionOutOfBounds { section: String, offset: usize, length: usize },
+
+    #[snafu(display("Failed to decompress data: {}", reason))]
+    DecompressionError { reason: String },
+
+    #[snafu(display("Invalid rom: {}", reason))]
+    InvalidRom { reason: String },
+
+    #[snafu(display("Asset {} does not exist (the rom has {} assets)", index, count))]
+    AssetIndexOutOfRange { index: usize, count: usize },
+
+    #[snafu(display("Asset {} needs {:#X} bytes but only {:#X} are available", index, size, available))]
+    AssetDoesNotFit { index: usize, size: usize, available: usize },
+
+    #[snafu(display("Vertex {} does not exist (the vertex store has {} verticies)", index, count))]
+    VertexIndexOutOfRange { index: usize, count: usize },
+
+    #[snafu(display("Invalid csv on line {}: {}", line, reason))]
+    InvalidCsv { line: usize, reason: String },
+
+    #[snafu(display("Invalid patch: {}", reason))]
+    PatchError { reason: String },
+
+    #[snafu(display("Invalid segment table: {}", reason))]
+    InvalidSegmentTable { reason: String },
+
+    #[snafu(display("Invalid palette: {}", reason))]
+    InvalidPalette { reason: String },
+
+    #[snafu(display("Invalid png: {}", reason))]
+    InvalidPng { reason: String },
+
+    #[snafu(display("texture_{} is {}x{} but the image is {}x{}", index, width, height, image_width, image_height))]
+    TextureSizeMismatch { index: usize, width: u32, height: u32, image_width: u32, image_height: u32 },
+
+    #[snafu(display("Could not encode texture: {}", reason))]
+    TextureEncodeError { reason: String },
+
+    #[snafu(display("Texture type {:?} is not supported", texture_type))]
+    UnsupportedTextureType { texture_type: TextureType },
+
     #[snafu(display("A general I/O error occurred: {}", source))]
     IOError {
         source: std::io::Error
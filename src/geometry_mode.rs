@@ -0,0 +1,91 @@
+// The geometry mode flags set by G_SETGEOMETRYMODE and cleared by G_CLEARGEOMETRYMODE (F3DEX)
+
+//
+/// Depth test and update against the Z buffer
+//
+pub const G_ZBUFFER : u32 = 0x00000001;
+
+//
+/// Use the vertex colors (or lighting) as the shade color
+//
+pub const G_SHADE : u32 = 0x00000004;
+
+//
+/// Interpolate the shade color across the triangle (Gouraud) instead of using the first vertex
+//
+pub const G_SHADING_SMOOTH : u32 = 0x00000200;
+
+//
+/// Don't draw triangles facing the camera
+//
+pub const G_CULL_FRONT : u32 = 0x00001000;
+
+//
+/// Don't draw triangles facing away from the camera
+//
+pub const G_CULL_BACK : u32 = 0x00002000;
+
+//
+/// Fog, using the vertex alpha as the fog amount
+//
+pub const G_FOG : u32 = 0x00010000;
+
+//
+/// Light the verticies: rgb_or_norm is a normal instead of a color
+//
+pub const G_LIGHTING : u32 = 0x00020000;
+
+//
+/// Generate the texture coordinates from the normals (environment mapping)
+//
+pub const G_TEXTURE_GEN : u32 = 0x00040000;
+
+//
+/// Linear version of G_TEXTURE_GEN
+//
+pub const G_TEXTURE_GEN_LINEAR : u32 = 0x00080000;
+
+//
+/// Level of detail for mipmapping
+//
+pub const G_LOD : u32 = 0x00100000;
+
+//
+/// Every flag with its name, for listings
+//
+const FLAG_NAMES : [(u32, &str); 10] = [
+    (G_ZBUFFER, "G_ZBUFFER"),
+    (G_SHADE, "G_SHADE"),
+    (G_SHADING_SMOOTH, "G_SHADING_SMOOTH"),
+    (G_CULL_FRONT, "G_CULL_FRONT"),
+    (G_CULL_BACK, "G_CULL_BACK"),
+    (G_FOG, "G_FOG"),
+    (G_LIGHTING, "G_LIGHTING"),
+    (G_TEXTURE_GEN, "G_TEXTURE_GEN"),
+    (G_TEXTURE_GEN_LINEAR, "G_TEXTURE_GEN_LINEAR"),
+    (G_LOD, "G_LOD")
+];
+
+//
+/// The names of the flags, separated by | (unknown bits are shown in hex)
+//
+pub fn describe (flags : u32) -> String
+{
+    let mut names = Vec::<String>::new();
+    let mut remaining = flags;
+    for (flag, name) in FLAG_NAMES.iter() {
+        if flags & flag != 0 {
+            names.push(name.to_string());
+            remaining &= !flag;
+        }
+    }
+
+    if remaining != 0 {
+        names.push(format!("{:#X}", remaining));
+    }
+    if names.is_empty() {
+        return "0".to_string();
+    }
+
+    return names.join("|");
+}
@@ -0,0 +1,173 @@
+use crate::render::Image;
+
+// Comparing two renders of the same size, ex. a model before and after an edit.
+
+//
+/// The color changed pixels are highlighted with
+//
+const HIGHLIGHT_COLOR : [u8; 4] = [0xFF, 0x00, 0xFF, 0xFF];
+
+//
+/// How bright the unchanged pixels are kept in the highlighted image, so the changes stand out
+//
+const UNCHANGED_BRIGHTNESS : f64 = 0.35;
+
+//
+/// How different two images are
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ImageDifference
+{
+    //
+    /// Pixels with a channel that changed by more than the threshold
+    //
+    pub changed_pixels: usize,
+
+    pub total_pixels: usize,
+
+    //
+    /// The average change of a channel over all pixels (0-255)
+    //
+    pub mean_difference: f64,
+
+    //
+    /// The largest change of any channel
+    //
+    pub max_difference: u8
+}
+
+impl ImageDifference
+{
+    //
+    /// The changed pixels as a percentage of all of them
+    //
+    pub fn changed_percent (&self) -> f64
+    {
+        if self.total_pixels == 0 {
+            return 0.0;
+        }
+        return self.changed_pixels as f64 * 100.0 / self.total_pixels as f64;
+    }
+}
+
+//
+/// Compare two images of the same size. A pixel counts as changed if any channel changed by more than threshold.
+///
+/// Returns the measurements, and an image of the first one darkened and in grey, with the
+/// changed pixels highlighted (brighter the more they changed).
+//
+pub fn compare (first : &Image, second : &Image, threshold : u8) -> (ImageDifference, Image)
+{
+    let width = first.width.min(second.width);
+    let height = first.height.min(second.height);
+
+    let mut highlighted = Image::new(width, height, [0, 0, 0, 0xFF]);
+    let mut difference = ImageDifference { total_pixels: width * height, ..Default::default() };
+    let mut total : u64 = 0;
+
+    for y in 0 .. height {
+        for x in 0 .. width {
+            let (a, b) = (first.pixel(x, y), second.pixel(x, y));
+            let change = (0 .. 4).map(|c| (a[c] as i32 - b[c] as i32).unsigned_abs() as u8).max().unwrap_or(0);
+            total += (0 .. 4).map(|c| (a[c] as i32 - b[c] as i32).unsigned_abs() as u64).sum::<u64>();
+            difference.max_difference = difference.max_difference.max(change);
+
+            let color = if change > threshold {
+                difference.changed_pixels += 1;
+
+                // at least half bright, so small changes are still easy to see
+                let strength = 0.5 + change as f64 / 255.0 / 2.0;
+                [
+                    (HIGHLIGHT_COLOR[0] as f64 * strength) as u8,
+                    (HIGHLIGHT_COLOR[1] as f64 * strength) as u8,
+                    (HIGHLIGHT_COLOR[2] as f64 * strength) as u8,
+                    0xFF
+                ]
+            } else {
+                let alpha = a[3] as f64 / 255.0;
+                let grey = (a[0] as f64 * 0.299 + a[1] as f64 * 0.587 + a[2] as f64 * 0.114) * alpha * UNCHANGED_BRIGHTNESS;
+                [grey as u8, grey as u8, grey as u8, 0xFF]
+            };
+            highlighted.set_pixel(x, y, color);
+        }
+    }
+
+    if difference.total_pixels > 0 {
+        difference.mean_difference = total as f64 / (difference.total_pixels as f64 * 4.0);
+    }
+
+    return (difference, highlighted);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::display_list_interpreter::DisplayListInterpreter;
+    use crate::model::Model;
+    use crate::render::{Camera, RenderOptions, View};
+    use crate::test_models::*;
+
+    const GREY : [u8; 4] = [10, 20, 30, 0xFF];
+
+    #[test]
+    fn identical_models_do_not_differ ()
+    {
+        let mut builder = ModelBuilder::new();
+        builder.vertex([-10, -10, 0], [0, 0], [0xFF, 0x00, 0x00], 0xFF);
+        builder.vertex([10, -10, 0], [0, 0], [0x00, 0xFF, 0x00], 0xFF);
+        builder.vertex([0, 10, 0], [0, 0], [0x00, 0x00, 0xFF], 0xFF);
+        builder.command(g_vtx(0, 3, vertex_address(0)));
+        builder.command(g_tri2([0, 1, 2], [0, 0, 0]));
+
+        let render = || -> Image {
+            let model = Model::from_bytes(builder.build()).unwrap();
+            let result = DisplayListInterpreter::run(&model);
+            let camera = Camera::for_model_orthographic(&model, View::Front);
+            return crate::render::render(&model, &result, &camera, &RenderOptions { width: 32, height: 32, background: [0, 0, 0, 0xFF] });
+        };
+
+        let (first, second) = (render(), render());
+        assert!(first != Image::new(32, 32, [0, 0, 0, 0xFF]));
+        let (difference, highlighted) = compare(&first, &second, 0);
+        assert_eq!(difference, ImageDifference { changed_pixels: 0, total_pixels: 32 * 32, mean_difference: 0.0, max_difference: 0 });
+        assert_eq!(difference.changed_percent(), 0.0);
+        assert!((0 .. 32 * 32).all(|i| highlighted.pixel(i % 32, i / 32) != HIGHLIGHT_COLOR));
+    }
+
+    #[test]
+    fn measures_a_one_pixel_change ()
+    {
+        let first = Image::new(4, 4, GREY);
+        let mut second = first.clone();
+        second.set_pixel(1, 2, [10, 20, 90, 0xFF]);
+
+        // 60 out of the 4 * 16 channels
+        let (difference, highlighted) = compare(&first, &second, 0);
+        assert_eq!(difference, ImageDifference { changed_pixels: 1, total_pixels: 16, mean_difference: 60.0 / 64.0, max_difference: 60 });
+        assert_eq!(difference.changed_percent(), 6.25);
+
+        // the changed pixel in the highlight color, the rest darkened grey
+        let strength = 0.5 + 60.0 / 255.0 / 2.0;
+        let highlight = (0xFF as f64 * strength) as u8;
+        assert_eq!(highlighted.pixel(1, 2), [highlight, 0x00, highlight, 0xFF]);
+        let grey = ((10.0 * 0.299 + 20.0 * 0.587 + 30.0 * 0.114) * UNCHANGED_BRIGHTNESS) as u8;
+        assert_eq!(highlighted.pixel(0, 0), [grey, grey, grey, 0xFF]);
+
+        // the change has to be more than the threshold
+        assert_eq!(compare(&first, &second, 59).0.changed_pixels, 1);
+        assert_eq!(compare(&first, &second, 60).0.changed_pixels, 0);
+    }
+
+    #[test]
+    fn compares_the_overlap_of_different_sizes ()
+    {
+        let (difference, highlighted) = compare(&Image::new(4, 2, GREY), &Image::new(3, 5, GREY), 0);
+        assert_eq!((difference.total_pixels, difference.changed_pixels), (6, 0));
+        assert_eq!((highlighted.width, highlighted.height), (3, 2));
+
+        let (empty, _) = compare(&Image::new(0, 0, GREY), &Image::new(0, 0, GREY), 0);
+        assert_eq!(empty, ImageDifference::default());
+        assert_eq!(empty.changed_percent(), 0.0);
+    }
+}
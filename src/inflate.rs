@@ -0,0 +1,456 @@
+use crate::errors;
+use crate::errors::Result;
+
+// Decoder for a raw deflate stream (RFC 1951) - no zlib/gzip wrapper.
+// This is a straight forward canonical huffman decoder. It is not the fastest
+// way to do it, but assets are small and it keeps us dependency free.
+
+/// Maximum bits in a huffman code
+const MAX_BITS : usize = 15;
+
+/// Base lengths for length codes 257..285
+const LENGTH_BASE : [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+
+/// Extra bits for length codes 257..285
+const LENGTH_EXTRA : [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+
+/// Base distances for distance codes 0..29
+const DIST_BASE : [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577
+];
+
+/// Extra bits for distance codes 0..29
+const DIST_EXTRA : [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+
+/// The order the code length code lengths are stored in a dynamic block
+const CODE_LENGTH_ORDER : [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15
+];
+
+//
+/// Reads bits LSB first from the input
+//
+struct BitReader<'a>
+{
+    m_data: &'a [u8],
+    m_pos: usize,
+    m_bit_buffer: u32,
+    m_bit_count: u32
+}
+
+impl<'a> BitReader<'a>
+{
+    fn new (data: &'a [u8]) -> BitReader<'a>
+    {
+        return BitReader {
+            m_data: data,
+            m_pos: 0,
+            m_bit_buffer: 0,
+            m_bit_count: 0
+        };
+    }
+
+    fn bits (&mut self, count: u32) -> Result<u32>
+    {
+        while self.m_bit_count < count {
+            if self.m_pos >= self.m_data.len() {
+                return errors::DecompressionError { reason: "unexpected end of deflate stream".to_string() }.fail();
+            }
+            self.m_bit_buffer |= (self.m_data[self.m_pos] as u32) << self.m_bit_count;
+            self.m_pos += 1;
+            self.m_bit_count += 8;
+        }
+
+        let v = if count == 32 { self.m_bit_buffer } else { self.m_bit_buffer & ((1u32 << count) - 1) };
+        self.m_bit_buffer = if count == 32 { 0 } else { self.m_bit_buffer >> count };
+        self.m_bit_count -= count;
+        return Ok(v);
+    }
+
+    //
+    /// Drop any bits left in the current byte
+    //
+    fn align_to_byte (&mut self)
+    {
+        self.m_bit_buffer = 0;
+        self.m_bit_count = 0;
+    }
+
+    //
+    /// How many bytes have been consumed from the input
+    //
+    fn consumed (&self) -> usize
+    {
+        return self.m_pos - (self.m_bit_count / 8) as usize;
+    }
+}
+
+//
+/// A canonical huffman table: how many codes of each length, and the symbols ordered by code
+//
+struct Huffman
+{
+    m_counts: [u16; MAX_BITS + 1],
+    m_symbols: Vec<u16>
+}
+
+impl Huffman
+{
+    fn new (lengths: &[u8]) -> Result<Huffman>
+    {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for l in lengths {
+            counts[*l as usize] += 1;
+        }
+
+        // check the code isnt over subscribed (incomplete codes are allowed)
+        let mut left : i32 = 1;
+        for count in counts.iter().skip(1) {
+            left <<= 1;
+            left -= *count as i32;
+            if left < 0 {
+                return errors::DecompressionError { reason: "over subscribed huffman code".to_string() }.fail();
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1 ..= MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, l) in lengths.iter().enumerate() {
+            if *l != 0 {
+                symbols[offsets[*l as usize] as usize] = symbol as u16;
+                offsets[*l as usize] += 1;
+            }
+        }
+
+        return Ok(Huffman {
+            m_counts: counts,
+            m_symbols: symbols
+        });
+    }
+
+    fn decode (&self, reader: &mut BitReader) -> Result<u16>
+    {
+        let mut code : i32 = 0; // bits read so far
+        let mut first : i32 = 0; // first code of the current length
+        let mut index : i32 = 0; // index of the first code of the current length in symbols
+
+        for len in 1 ..= MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.m_counts[len] as i32;
+            if code - count < first {
+                return Ok(self.m_symbols[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+
+        return errors::DecompressionError { reason: "invalid huffman code".to_string() }.fail();
+    }
+}
+
+//
+/// Inflate a raw deflate stream.
+/// Returns the decompressed data and how many bytes of the input the stream used.
+//
+pub fn inflate (data: &[u8]) -> Result<(Vec<u8>, usize)>
+{
+    let mut reader = BitReader::new(data);
+    let mut out = Vec::<u8>::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        let block_type = reader.bits(2)?;
+
+        match block_type {
+            0 => p_stored_block(&mut reader, &mut out)?,
+            1 => {
+                let (lengths, distances) = p_fixed_tables()?;
+                p_codes(&mut reader, &mut out, &lengths, &distances)?;
+            },
+            2 => {
+                let (lengths, distances) = p_dynamic_tables(&mut reader)?;
+                p_codes(&mut reader, &mut out, &lengths, &distances)?;
+            },
+            _ => {
+                return errors::DecompressionError { reason: "invalid block type 3".to_string() }.fail();
+            }
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    return Ok((out, reader.consumed()));
+}
+
+//
+/// Copy a stored (uncompressed) block
+//
+fn p_stored_block (reader: &mut BitReader, out: &mut Vec<u8>) -> Result<()>
+{
+    reader.align_to_byte();
+
+    let pos = reader.m_pos;
+    if pos + 4 > reader.m_data.len() {
+        return errors::DecompressionError { reason: "stored block header truncated".to_string() }.fail();
+    }
+
+    let len = u16::from_le_bytes([reader.m_data[pos], reader.m_data[pos+1]]);
+    let nlen = u16::from_le_bytes([reader.m_data[pos+2], reader.m_data[pos+3]]);
+    if len != !nlen {
+        return errors::DecompressionError { reason: "stored block length mismatch".to_string() }.fail();
+    }
+
+    let start = pos + 4;
+    let end = start + len as usize;
+    if end > reader.m_data.len() {
+        return errors::DecompressionError { reason: "stored block truncated".to_string() }.fail();
+    }
+
+    out.extend_from_slice(&reader.m_data[start..end]);
+    reader.m_pos = end;
+    return Ok(());
+}
+
+//
+/// The fixed huffman tables (block type 1)
+//
+fn p_fixed_tables () -> Result<(Huffman, Huffman)>
+{
+    let mut lengths = [0u8; 288];
+    for (i, l) in lengths.iter_mut().enumerate() {
+        *l = match i {
+            0 ..= 143 => 8,
+            144 ..= 255 => 9,
+            256 ..= 279 => 7,
+            _ => 8
+        };
+    }
+
+    return Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?));
+}
+
+//
+/// Read the huffman tables for a dynamic block (block type 2)
+//
+fn p_dynamic_tables (reader: &mut BitReader) -> Result<(Huffman, Huffman)>
+{
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    if literal_count > 286 || distance_count > 30 {
+        return errors::DecompressionError { reason: "too many codes in dynamic block".to_string() }.fail();
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for order in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[*order] = reader.bits(3)? as u8;
+    }
+    let code_length_huffman = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_length_huffman.decode(reader)?;
+        if symbol < 16 {
+            lengths[i] = symbol as u8;
+            i += 1;
+            continue;
+        }
+
+        let (value, repeat) = match symbol {
+            16 => {
+                if i == 0 {
+                    return errors::DecompressionError { reason: "repeat with no previous length".to_string() }.fail();
+                }
+                (lengths[i - 1], 3 + reader.bits(2)? as usize)
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize)
+        };
+
+        if i + repeat > lengths.len() {
+            return errors::DecompressionError { reason: "too many code lengths".to_string() }.fail();
+        }
+        for l in lengths[i .. i + repeat].iter_mut() {
+            *l = value;
+        }
+        i += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return errors::DecompressionError { reason: "missing end of block code".to_string() }.fail();
+    }
+
+    return Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?));
+}
+
+//
+/// Decode literals and length/distance pairs until the end of block
+//
+fn p_codes (reader: &mut BitReader, out: &mut Vec<u8>, lengths: &Huffman, distances: &Huffman) -> Result<()>
+{
+    loop {
+        let symbol = lengths.decode(reader)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return errors::DecompressionError { reason: "invalid length code".to_string() }.fail();
+        }
+        let length = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+        let dist_symbol = distances.decode(reader)? as usize;
+        if dist_symbol >= DIST_BASE.len() {
+            return errors::DecompressionError { reason: "invalid distance code".to_string() }.fail();
+        }
+        let distance = DIST_BASE[dist_symbol] as usize + reader.bits(DIST_EXTRA[dist_symbol] as u32)? as usize;
+
+        if distance > out.len() {
+            return errors::DecompressionError { reason: "distance too far back".to_string() }.fail();
+        }
+
+        // byte by byte since the copy can overlap itself
+        let start = out.len() - distance;
+        for i in 0 .. length {
+            let b = out[start + i];
+            out.push(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //
+    /// Pack (value, bit count) fields LSB first, the way deflate stores them
+    //
+    fn p_bits (fields : &[(u32, u32)]) -> Vec<u8>
+    {
+        let mut out = Vec::<u8>::new();
+        let mut bit = 0;
+        for (value, count) in fields {
+            for i in 0 .. *count {
+                if bit % 8 == 0 {
+                    out.push(0);
+                }
+                out[bit / 8] |= (((value >> i) & 1) as u8) << (bit % 8);
+                bit += 1;
+            }
+        }
+        return out;
+    }
+
+    //
+    /// A huffman code, which is stored starting from its most significant bit
+    //
+    fn p_code (code : u32, length : u32) -> (u32, u32)
+    {
+        return (code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn p_assert_fails (data : &[u8], reason : &str)
+    {
+        match inflate(data) {
+            Err(errors::Error::DecompressionError { reason: r }) => assert_eq!(r, reason),
+            other => panic!("expected DecompressionError, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn inflates_stored_blocks ()
+    {
+        // anything after the last block isnt used
+        let data = [0x01, 0x05, 0x00, 0xFA, 0xFF, b'b', b'a', b'n', b'j', b'o', 0x00, 0x00];
+        assert_eq!(inflate(&data).unwrap(), (b"banjo".to_vec(), 10));
+
+        p_assert_fails(&[0x01, 0x05, 0x00, 0xFB, 0xFF, b'b'], "stored block length mismatch");
+        p_assert_fails(&[0x01, 0x05, 0x00, 0xFA, 0xFF, b'b'], "stored block truncated");
+    }
+
+    #[test]
+    fn inflates_fixed_huffman_blocks ()
+    {
+        // 'a' (8 bits from 0x30), then 3 more copied from 1 back (length 257 is 7 bits from 0, distance 0 is 5 bits), then the end (256)
+        let data = p_bits(&[(1, 1), (1, 2), p_code(0x30 + b'a' as u32, 8), p_code(1, 7), p_code(0, 5), p_code(0, 7)]);
+        assert_eq!(inflate(&data).unwrap(), (b"aaaa".to_vec(), data.len()));
+    }
+
+    #[test]
+    fn inflates_dynamic_huffman_blocks ()
+    {
+        // from zlib (huffman only), which picks a dynamic block for so few different letters
+        let data = [
+            0x05, 0xC1, 0x01, 0x01, 0x00, 0x30, 0x0C, 0xC3, 0x20, 0xAD, 0xA4, 0xBB, 0x7F, 0x0B, 0x07, 0x99,
+            0x9C, 0x4C, 0x9E, 0x4C, 0x4E, 0x26, 0x4F, 0x26, 0x27, 0x93, 0x27, 0x93, 0x93, 0xC9, 0xFB
+        ];
+        assert_eq!((data[0] >> 1) & 0x03, 2);
+        assert_eq!(inflate(&data).unwrap(), (b"abacabadabacabae".repeat(4), data.len()));
+    }
+
+    #[test]
+    fn rejects_invalid_block_types ()
+    {
+        p_assert_fails(&[0x07], "invalid block type 3");
+    }
+
+    #[test]
+    fn rejects_over_subscribed_codes ()
+    {
+        // a dynamic block with all 19 code length codes 1 bit long
+        let mut fields = vec![(1, 1), (2, 2), (0, 5), (0, 5), (15, 4)];
+        fields.extend(std::iter::repeat((1, 3)).take(19));
+        p_assert_fails(&p_bits(&fields), "over subscribed huffman code");
+    }
+
+    #[test]
+    fn rejects_codes_missing_from_incomplete_tables ()
+    {
+        // only code length 0 has a code (0), so a 1 doesnt lead anywhere
+        let mut fields = vec![(1, 1), (2, 2), (0, 5), (0, 5), (0, 4), (0, 3), (0, 3), (0, 3), (1, 3)];
+        fields.extend(std::iter::repeat((1, 1)).take(15));
+        p_assert_fails(&p_bits(&fields), "invalid huffman code");
+    }
+
+    #[test]
+    fn rejects_distances_too_far_back ()
+    {
+        // 'a', then a copy from 2 back (distance 1 is 5 bits from 1)
+        let data = p_bits(&[(1, 1), (1, 2), p_code(0x30 + b'a' as u32, 8), p_code(1, 7), p_code(1, 5), p_code(0, 7)]);
+        p_assert_fails(&data, "distance too far back");
+    }
+
+    #[test]
+    fn rejects_truncated_streams ()
+    {
+        p_assert_fails(&[], "unexpected end of deflate stream");
+        let data = p_bits(&[(1, 1), (1, 2), p_code(0x30 + b'a' as u32, 8)]);
+        p_assert_fails(&data, "unexpected end of deflate stream");
+    }
+}
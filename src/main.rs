@@ -1,35 +1,84 @@
+// Functions end with an explicit return everywhere, which clippy would flag at every one
+#![allow(clippy::needless_return)]
+
+mod animated_texture;
+mod blender;
+mod combiner;
 mod commandline_options;
+mod crc32;
+mod deflate;
 mod dlcommands;
 mod display_list;
+mod display_list_interpreter;
 mod errors;
 mod geo_type;
+mod geometry_mode;
+mod image_diff;
+mod inflate;
 mod model;
+mod obj_export;
+mod other_mode;
+mod palette;
+mod patch;
+mod png;
+mod quantize;
+mod rarezip;
+mod render;
+mod rom;
+mod rom_checksum;
+mod segment_map;
+mod segment_table;
+mod svg_export;
+mod terminal_image;
+#[cfg(test)]
+mod test_models;
 mod texture;
+mod texture_coordinates;
 mod texture_type;
+mod tile_descriptor;
+mod vertex_report;
 mod vertex_store;
 
 use commandline_options::CommandLineOptions;
+use commandline_options::MemoryOptions;
+use commandline_options::RomSubCommand;
 use commandline_options::SubCommand;
+use display_list_interpreter::DisplayListInterpreter;
 use model::Model;
+use palette::Palette;
+use patch::PatchFormat;
+use quantize::Dither;
+use rarezip::CompressionMatch;
+use render::Camera;
+use render::RenderOptions;
+use render::View;
+use rom::AssetType;
+use rom::Rom;
+use segment_table::SegmentTable;
+use segment_table::SEGMENT_COUNT;
 use clap::Clap;
 use snafu::ErrorCompat;
+use snafu::ResultExt;
+use std::path::Path;
+use svg_export::SvgOptions;
+use terminal_image::ColorMode;
+use texture::Texture;
+use texture_type::TextureType;
+use vertex_report::ReportFormat;
+use vertex_report::ReportOptions;
 
 fn main() {
     let opts = CommandLineOptions::parse();
 
-    let model_file = opts.filename;
-
     match opts.sub_command {
-        SubCommand::Inspect(_inspect_options) => {
-            let model = match Model::load(model_file) {
-                Ok(m) => m,
-                Err(e) => {
-                    eprintln!("An error occurred on load: {}", e);
-                    if let Some(backtrace) = ErrorCompat::backtrace(&e) {
-                       eprintln!("{}", backtrace);
-                    }
-                    return;
-                }
+        SubCommand::Inspect(inspect_options) => {
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+            let color_mode = match p_color_mode(&inspect_options.color) {
+                Some(c) => c,
+                None => return
             };
 
             println! ("---- Model information -----------");
@@ -39,7 +88,7 @@ fn main() {
             for name in names {
                 println! ("- {}", name);
             }
-            println! ("");
+            println!();
 
             println! ("Textures:");
             let textures = model.textures();
@@ -51,9 +100,35 @@ fn main() {
                     texture.width(),
                     texture.height()
                 );
+
+                if !texture.fits_tmem() {
+                    println!("  WARNING: texture_{} needs {:#X} bytes of TMEM but only {:#X} are available, it will not load correctly",
+                        texture.index(), texture.tmem_size(), texture.texture_type().tmem_limit()
+                    );
+                }
+
+                if inspect_options.preview {
+                    if let Some(image) = p_texture_image(&model, texture) {
+                        print!("{}", terminal_image::draw(&image, 32, 16, color_mode));
+                    }
+                }
+            }
+
+            println!();
+
+            println! ("Animated textures:");
+            for animated_texture in model.animated_textures() {
+                println!("- slot_{}: frames={} frameSize={:#X} (total: {:#X}) frameRate={} loop={:.2}s",
+                    animated_texture.slot(),
+                    animated_texture.frame_count(),
+                    animated_texture.frame_size(),
+                    animated_texture.total_size(),
+                    animated_texture.frame_rate(),
+                    animated_texture.loop_duration()
+                );
             }
 
-            println!("");
+            println!();
 
             println! ("Internal information:");
             println! ("- Geometry Setup Offset: {:#X}", model.internal_geometry_setup_offset());
@@ -63,18 +138,23 @@ fn main() {
             println! ("- Vertex Store Setup Offset: {:#X}", model.internal_vertex_store_setup_offset());
             println! ("- Tri count: {}", model.internal_triangle_count());
             println! ("- Vert count: {}", model.internal_vert_count());
+            println! ("- Animated Texture Setup Offset: {:#X}", model.internal_animated_texture_setup_offset());
+
+            // where the display list's segmented addresses point, which differs from the file layout when loaded from memory
+            if let Some(base_address) = model.base_address() {
+                println! ("- Base Address: {:#X}", base_address);
+            }
+            for segment in 0 .. SEGMENT_COUNT as u8 {
+                if let Some(offset) = model.segment_map().get(segment) {
+                    println! ("- Segment {:#X}: model offset {}{:#X}", segment, if offset < 0 { "-" } else { "" }, offset.unsigned_abs());
+                }
+            }
         },
 
         SubCommand::ViewDisplayList(_options) => {
-            let model = match Model::load(model_file) {
-                Ok(m) => m,
-                Err(e) => {
-                    eprintln!("An error occurred on load: {}", e);
-                    if let Some(backtrace) = ErrorCompat::backtrace(&e) {
-                       eprintln!("{}", backtrace);
-                    }
-                    return;
-                }
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
             };
 
             let display_list = model.display_list();
@@ -82,40 +162,943 @@ fn main() {
 
             for command in commands
             {
-              println!("{}", command.psuedo_code());
+              match model.resolve_command(command) {
+                  Some(resolved) => println!("{} # {}", command.psuedo_code(), resolved.describe()),
+                  None => println!("{}", command.psuedo_code())
+              }
             }
 
-            println!("");
+            println!();
             println!("# total length: {} commands", commands.len());
         },
 
-        SubCommand::ViewVertexStore(_options) => {
-            let model = match Model::load(model_file) {
-                Ok(m) => m,
+        SubCommand::ViewVertexStore(options) => {
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            let format = match ReportFormat::from_name(&options.format) {
+                Some(f) => f,
+                None => {
+                    eprintln!("Unknown format '{}', expected text, csv or json", options.format);
+                    return;
+                }
+            };
+            let range = match options.range.as_deref().map(vertex_report::parse_range) {
+                Some(None) => {
+                    eprintln!("'{}' is not an index range, expected first-last", options.range.as_deref().unwrap_or(""));
+                    return;
+                },
+                range => range.flatten()
+            };
+            let within = match options.within.as_deref().map(vertex_report::parse_box) {
+                Some(None) => {
+                    eprintln!("'{}' is not a box, expected x1,y1,z1,x2,y2,z2", options.within.as_deref().unwrap_or(""));
+                    return;
+                },
+                within => within.flatten()
+            };
+            let kind = match options.kind.as_deref().map(vertex_report::parse_kind) {
+                Some(None) => {
+                    eprintln!("Unknown kind '{}', expected unused, colored, lit or both", options.kind.as_deref().unwrap_or(""));
+                    return;
+                },
+                kind => kind.flatten()
+            };
+
+            let report_options = ReportOptions {
+                format,
+                indices: options.indices,
+                normalized_uvs: options.normalized_uvs,
+                triangles: options.triangles,
+                range,
+                within,
+                kind,
+                sort: options.sort
+            };
+
+            let result = DisplayListInterpreter::run(&model);
+            print!("{}", vertex_report::report(&model, &result, &report_options));
+        }
+
+        SubCommand::ImportUvs(options) => {
+            let mut model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            let uvs = match std::fs::read_to_string(&options.csv).context(errors::IOError).and_then(|text| vertex_report::parse_uv_csv(&text)) {
+                Ok(u) => u,
+                Err(e) => {
+                    eprintln!("An error occurred loading {}: {}", options.csv, e);
+                    return;
+                }
+            };
+
+            // convert with the same mapping view-vertex-store normalized them with
+            let result = DisplayListInterpreter::run(&model);
+            let mappings = texture_coordinates::vertex_mappings(&model, &result);
+
+            let mut changed = 0;
+            for (index, uv) in uvs {
+                let mapping = match mappings.get(index) {
+                    Some(Some(m)) => m,
+                    Some(None) => {
+                        eprintln!("# vertex {} is not drawn with a texture, skipping it", index);
+                        continue;
+                    },
+                    None => {
+                        eprintln!("# vertex {} does not exist, skipping it", index);
+                        continue;
+                    }
+                };
+                let fixed = match mapping.denormalize(uv) {
+                    Some(f) => f,
+                    None => {
+                        eprintln!("# vertex {}: [{},{}] is too far outside the texture, skipping it", index, uv[0], uv[1]);
+                        continue;
+                    }
+                };
+
+                if model.vertex_store().verticies()[index].uv != fixed {
+                    if let Err(e) = model.set_vertex_uv(index, fixed) {
+                        eprintln!("An error occurred setting the uv of vertex {}: {}", index, e);
+                        return;
+                    }
+                    changed += 1;
+                }
+            }
+            println!("# changed the uv of {} verticies", changed);
+
+            if let Err(e) = model.save(options.out) {
+                eprintln!("An error occurred on save: {}", e);
+            }
+        }
+
+        SubCommand::RecomputeBounds(options) => {
+            let mut model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            let before = model.vertex_store().bounds();
+            model.recompute_vertex_store_bounds();
+            let after = model.vertex_store().bounds();
+
+            println!("before: {:?}", before);
+            println!("after:  {:?}", after);
+
+            if let Err(e) = model.save(options.out) {
+                eprintln!("An error occurred on save: {}", e);
+            }
+        }
+
+        SubCommand::Decompress(options) => {
+            let model_file = match p_require_filename(&opts.filename) {
+                Some(f) => f,
+                None => return
+            };
+
+            let data = match std::fs::read(&model_file) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("An error occurred on load: {}", e);
+                    return;
+                }
+            };
+
+            if !rarezip::is_compressed(&data) {
+                eprintln!("{} is not rarezip compressed", model_file);
+                return;
+            }
+
+            let decompressed = match rarezip::decompress(&data) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("An error occurred on decompress: {}", e);
+                    return;
+                }
+            };
+
+            if let Err(e) = std::fs::write(&options.out, &decompressed) {
+                eprintln!("An error occurred on save: {}", e);
+                return;
+            }
+
+            println!("Decompressed {:#X} bytes to {:#X} bytes", data.len(), decompressed.len());
+        }
+
+        SubCommand::Compress(options) => {
+            let model_file = match p_require_filename(&opts.filename) {
+                Some(f) => f,
+                None => return
+            };
+
+            let data = match std::fs::read(&model_file) {
+                Ok(d) => d,
                 Err(e) => {
                     eprintln!("An error occurred on load: {}", e);
-                    if let Some(backtrace) = ErrorCompat::backtrace(&e) {
-                       eprintln!("{}", backtrace);
+                    return;
+                }
+            };
+
+            if rarezip::is_compressed(&data) {
+                eprintln!("{} is already rarezip compressed", model_file);
+                return;
+            }
+
+            let compressed = rarezip::compress(&data, options.level);
+            if let Err(e) = std::fs::write(&options.out, &compressed) {
+                eprintln!("An error occurred on save: {}", e);
+                return;
+            }
+
+            println!("Compressed {:#X} bytes to {:#X} bytes", data.len(), compressed.len());
+
+            if let Some(verify) = options.verify {
+                let original = match std::fs::read(&verify) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        eprintln!("An error occurred loading {}: {}", verify, e);
+                        return;
+                    }
+                };
+
+                match rarezip::compare(&compressed, &original) {
+                    CompressionMatch::Identical => println!("Matches {}", verify),
+                    CompressionMatch::IdenticalWithPadding { padding } => {
+                        println!("Matches {} (original has {:#X} bytes of padding)", verify, padding)
+                    },
+                    CompressionMatch::Differs { offset } => {
+                        println!("Differs from {} starting at {:#X}", verify, offset)
+                    }
+                }
+            }
+        }
+
+        SubCommand::Rom(rom_options) => {
+            match rom_options.sub_command {
+                RomSubCommand::List(options) => {
+                    let rom = match Rom::load(options.rom) {
+                        Ok(r) => r,
+                        Err(e) => {
+                            eprintln!("An error occurred on load: {}", e);
+                            return;
+                        }
+                    };
+
+                    let checksum = rom.checksum();
+                    println!("# {} ({} v{}) byteOrder:{:?} checksum:{:08X} {:08X} assetTable:{:#X}",
+                        rom.internal_name(), rom.game_code(), rom.version(),
+                        rom.byte_order(), checksum.0, checksum.1, rom.asset_table_offset()
+                    );
+
+                    for asset in rom.assets() {
+                        println!("asset_{}: offset={:#X} size={:#X} compressed={} flags={:#X} type={:?}",
+                            asset.index(), asset.offset(), asset.size(),
+                            asset.compressed(), asset.flags(),
+                            rom.asset_type(asset.index())
+                        );
+                    }
+
+                    println!();
+                    println!("# total length: {} assets", rom.assets().len());
+                },
+
+                RomSubCommand::Patch(options) => {
+                    let mut rom = match Rom::load(options.rom) {
+                        Ok(r) => r,
+                        Err(e) => {
+                            eprintln!("An error occurred on load: {}", e);
+                            return;
+                        }
+                    };
+
+                    let data = match std::fs::read(&options.file) {
+                        Ok(d) => d,
+                        Err(e) => {
+                            eprintln!("An error occurred reading {}: {}", options.file, e);
+                            return;
+                        }
+                    };
+
+                    let old_size = match rom.assets().get(options.asset) {
+                        Some(a) => a.size(),
+                        None => 0
+                    };
+                    if let Err(e) = rom.replace_asset(options.asset, &data) {
+                        eprintln!("An error occurred on patch: {}", e);
+                        return;
                     }
+
+                    let (crc1, crc2) = match rom.fix_checksum() {
+                        Ok(crc) => crc,
+                        Err(e) => {
+                            eprintln!("An error occurred on checksum: {}", e);
+                            return;
+                        }
+                    };
+
+                    if let Err(e) = rom.save(options.out) {
+                        eprintln!("An error occurred on save: {}", e);
+                        return;
+                    }
+
+                    let new_size = rom.assets()[options.asset].size();
+                    println!("asset_{}: {:#X} -> {:#X} bytes", options.asset, old_size, new_size);
+                    println!("checksum: {:08X} {:08X} (cic: {:?})", crc1, crc2, rom.cic());
+                }
+            }
+        }
+
+        SubCommand::ExtractModels(options) => {
+            let rom = match Rom::load(options.rom) {
+                Ok(r) => r,
+                Err(e) => {
+                    eprintln!("An error occurred on load: {}", e);
                     return;
                 }
             };
 
-            let vs = model.vertex_store();
+            if let Err(e) = std::fs::create_dir_all(&options.out) {
+                eprintln!("An error occurred creating {}: {}", options.out, e);
+                return;
+            }
+
+            let mut model_count = 0;
+            let mut skipped_count = 0;
+            for asset in rom.assets() {
+                let data = match rom.asset_data(asset.index()) {
+                    Ok(d) => d,
+                    Err(_) if asset.size() == 0 => continue, // an empty slot
+                    Err(e) => {
+                        eprintln!("# asset_{}: {}, skipping it", asset.index(), e);
+                        skipped_count += 1;
+                        continue;
+                    }
+                };
 
-            for v in vs.verticies()
-            {
-              println!("pos:[{},{},{}] uv:[{},{}] rgborn:[{},{},{}] alpha:{} flag:{:#X}",
-                    v.pos[0], v.pos[1], v.pos[2],
-                    v.uv[0], v.uv[1],
-                    v.rgb_or_norm[0], v.rgb_or_norm[1], v.rgb_or_norm[2],
-                    v.alpha, // or nx, ny, nz, a
-                    v.flag
+                if AssetType::sniff(&data) != AssetType::Model {
+                    continue;
+                }
+
+                let model = match Model::from_bytes(data.clone()) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        eprintln!("# asset_{}: {}, skipping it", asset.index(), e);
+                        skipped_count += 1;
+                        continue;
+                    }
+                };
+
+                let out_dir = Path::new(&options.out);
+                let model_path = out_dir.join(format!("asset_{}.bin", asset.index()));
+                if let Err(e) = std::fs::write(&model_path, &data) {
+                    eprintln!("An error occurred on save: {}", e);
+                    return;
+                }
+                model_count += 1;
+                println!("asset_{}: {:#X} bytes", asset.index(), data.len());
+
+                if options.export {
+                    p_export_model(&model, out_dir, &format!("asset_{}", asset.index()));
+                }
+            }
+
+            println!();
+            println!("# extracted {} models", model_count);
+            if skipped_count > 0 {
+                println!("# skipped {} assets which could not be read", skipped_count);
+            }
+        },
+
+        SubCommand::ExportTextures(options) => {
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            if let Err(e) = std::fs::create_dir_all(&options.out) {
+                eprintln!("An error occurred creating {}: {}", options.out, e);
+                return;
+            }
+
+            let palette = match &options.palette {
+                Some(filename) => match Palette::load(filename) {
+                    Ok(p) => Some(p),
+                    Err(e) => {
+                        eprintln!("An error occurred loading the palette: {}", e);
+                        return;
+                    }
+                },
+                None => None
+            };
+
+            for texture in model.textures() {
+                if options.index.is_some() && options.index != Some(texture.index() as usize) {
+                    continue;
+                }
+
+                let rgba = match &palette {
+                    Some(palette) if texture.texture_type().palette_size() != 0 => {
+                        model.texture_data(texture).and_then(|data| texture.decode_rgba_with_palette(data, palette))
+                    },
+                    _ => model.texture_rgba(texture)
+                };
+                p_write_texture_png(texture, rgba, &Path::new(&options.out).join(format!("texture_{}.png", texture.index())));
+            }
+        },
+
+        SubCommand::ExportPalette(options) => {
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            let texture = match model.textures().get(options.index) {
+                Some(t) => t,
+                None => {
+                    eprintln!("There is no texture_{} (the model has {} textures)", options.index, model.textures().len());
+                    return;
+                }
+            };
+
+            let palette = match model.texture_palette(texture) {
+                Ok(Some(p)) => p,
+                Ok(None) => {
+                    eprintln!("texture_{} is {:?} which has no palette", options.index, texture.texture_type());
+                    return;
+                },
+                Err(e) => {
+                    eprintln!("An error occurred reading the palette: {}", e);
+                    return;
+                }
+            };
+
+            let data = if options.out.to_lowercase().ends_with(".gpl") {
+                palette.to_gpl(&format!("texture_{}", options.index)).into_bytes()
+            } else {
+                palette.to_swatch_png()
+            };
+
+            if let Err(e) = std::fs::write(&options.out, data) {
+                eprintln!("An error occurred on save: {}", e);
+            }
+        },
+
+        SubCommand::ReplaceTexture(options) => {
+            let mut model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            let image = match std::fs::read(&options.png).context(errors::IOError).and_then(|data| png::decode_rgba(&data)) {
+                Ok(i) => i,
+                Err(e) => {
+                    eprintln!("An error occurred loading {}: {}", options.png, e);
+                    return;
+                }
+            };
+
+            let dither = match Dither::from_name(&options.dither) {
+                Some(d) => d,
+                None => {
+                    eprintln!("Unknown dither '{}', expected none, ordered or floyd-steinberg", options.dither);
+                    return;
+                }
+            };
+
+            // let the user know if the texture type doesnt suit the image
+            if let Some(texture) = model.textures().get(options.index) {
+                let best = TextureType::smallest_lossless(&image.rgba);
+                let current = texture.texture_type();
+                if best != current {
+                    if current.tmem_size(image.width, image.height) > best.tmem_size(image.width, image.height) || current.palette_size() > best.palette_size() {
+                        println!("# note: the image would fit losslessly in {:?} which is smaller than {:?}", best, current);
+                    } else {
+                        println!("# note: the image needs {:?} to be stored losslessly, {:?} will lose detail", best, current);
+                    }
+                }
+            }
+
+            match model.replace_texture(options.index, image.width, image.height, &image.rgba, dither) {
+                Ok(Some(error)) => {
+                    println!("# quantization error: mse:{:.2} psnr:{:.2}dB max:{} alphaChanged:{}",
+                        error.mean_squared, error.psnr, error.max_squared, error.alpha_changed
+                    );
+                },
+                Ok(None) => {},
+                Err(e) => {
+                    eprintln!("An error occurred replacing the texture: {}", e);
+                    return;
+                }
+            }
+
+            if let Err(e) = model.save(options.out) {
+                eprintln!("An error occurred on save: {}", e);
+            }
+        },
+
+        SubCommand::TextureFormat(options) => {
+            let image = match std::fs::read(&options.png).context(errors::IOError).and_then(|data| png::decode_rgba(&data)) {
+                Ok(i) => i,
+                Err(e) => {
+                    eprintln!("An error occurred loading {}: {}", options.png, e);
+                    return;
+                }
+            };
+
+            let texture_type = TextureType::smallest_lossless(&image.rgba);
+            let pixel_bytes = (image.width * image.height * texture_type.bits_per_pixel()).div_ceil(8);
+            let tmem_size = texture_type.tmem_size(image.width, image.height);
+
+            println!("{}x{} -> {:?}", image.width, image.height, texture_type);
+            println!("data size: {:#X} bytes (palette: {:#X})", pixel_bytes + texture_type.palette_size(), texture_type.palette_size());
+            println!("tmem: {:#X} of {:#X} bytes", tmem_size, texture_type.tmem_limit());
+
+            if tmem_size > texture_type.tmem_limit() {
+                println!("WARNING: the image is too big for TMEM as {:?}, it needs to be smaller or split", texture_type);
+            }
+            if image.width > 0xFF || image.height > 0xFF {
+                println!("WARNING: the model texture header only holds sizes up to 255");
+            }
+        },
+
+        SubCommand::ViewTexture(options) => {
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+            let color_mode = match p_color_mode(&options.color) {
+                Some(c) => c,
+                None => return
+            };
+
+            let textures : Vec<&Texture> = match options.index {
+                Some(index) => match model.textures().get(index) {
+                    Some(t) => vec![t],
+                    None => {
+                        eprintln!("There is no texture_{}, the model has {} textures", index, model.textures().len());
+                        return;
+                    }
+                },
+                None => model.textures().iter().collect()
+            };
+
+            for texture in textures {
+                println!("texture_{}: type={:?} width={} height={}",
+                    texture.index(), texture.texture_type(), texture.width(), texture.height()
                 );
+                if let Some(image) = p_texture_image(&model, texture) {
+                    print!("{}", terminal_image::draw(&image, options.columns, options.rows, color_mode));
+                }
+            }
+
+            if options.model {
+                let result = DisplayListInterpreter::run(&model);
+
+                // low res is plenty, the terminal cant show more
+                let render_options = RenderOptions { width: options.columns, height: options.rows * 2, background: [0, 0, 0, 0] };
+                let camera = Camera::for_model(&model, 30.0, 20.0, 45.0);
+                let image = render::render(&model, &result, &camera, &render_options);
+
+                println!("model: {} triangles", result.triangles.len());
+                print!("{}", terminal_image::draw(&image, options.columns, options.rows, color_mode));
+            }
+        },
+
+        SubCommand::ExportObj(options) => {
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            let result = DisplayListInterpreter::run(&model);
+            for warning in &result.warnings {
+                eprintln!("warning: {}", warning);
+            }
+
+            if let Err(e) = std::fs::write(&options.out, obj_export::export_obj(&model, &result, None)) {
+                eprintln!("An error occurred on save: {}", e);
+            }
+        },
+
+        SubCommand::ExportSvg(options) => {
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            if options.width == 0 || options.height == 0 {
+                eprintln!("The image needs a width and height");
+                return;
+            }
+
+            let camera = match options.view.to_lowercase().as_str() {
+                "camera" => Camera::for_model(&model, options.yaw, options.pitch, options.fov),
+                name => match View::from_name(name) {
+                    Some(view) => Camera::for_model_orthographic(&model, view),
+                    None => {
+                        eprintln!("Unknown view '{}', expected front, side, top or camera", options.view);
+                        return;
+                    }
+                }
+            };
+
+            let result = DisplayListInterpreter::run(&model);
+            for warning in &result.warnings {
+                eprintln!("warning: {}", warning);
+            }
+
+            let svg_options = SvgOptions { width: options.width, height: options.height, label_verticies: options.labels };
+            if let Err(e) = std::fs::write(&options.out, svg_export::export_svg(&model, &result, &camera, &svg_options)) {
+                eprintln!("An error occurred on save: {}", e);
+            }
+        },
+
+        SubCommand::Render(options) => {
+            let model = match p_load_model(&opts.filename, &opts.memory) {
+                Some(m) => m,
+                None => return
+            };
+
+            let background = match render::parse_color(&options.background) {
+                Some(c) => c,
+                None => {
+                    eprintln!("Unknown color {}, expected RRGGBB or RRGGBBAA", options.background);
+                    return;
+                }
+            };
+            if options.width == 0 || options.height == 0 {
+                eprintln!("The image needs a width and height");
+                return;
+            }
+
+            let result = DisplayListInterpreter::run(&model);
+            for warning in &result.warnings {
+                eprintln!("warning: {}", warning);
+            }
+
+            let mut views = Vec::<View>::new();
+            for name in options.views.as_deref().unwrap_or("").split(',').filter(|n| !n.trim().is_empty()) {
+                match View::from_name(name) {
+                    Some(view) => views.push(view),
+                    None => {
+                        eprintln!("Unknown view '{}', expected front, side or top", name);
+                        return;
+                    }
+                }
+            }
+
+            // a single view, or a contact sheet of the turntable then the orthographic views
+            let mut cameras = Vec::<Camera>::new();
+            match options.turntable {
+                Some(0) => {
+                    eprintln!("The turntable needs at least one view");
+                    return;
+                },
+                Some(count) => {
+                    for i in 0 .. count {
+                        let yaw = options.yaw + 360.0 * i as f64 / count as f64;
+                        cameras.push(Camera::for_model(&model, yaw, options.pitch, options.fov));
+                    }
+                },
+                None if views.is_empty() => cameras.push(Camera::for_model(&model, options.yaw, options.pitch, options.fov)),
+                None => {}
+            }
+            cameras.extend(views.iter().map(|v| Camera::for_model_orthographic(&model, *v)));
+
+            let render_options = RenderOptions { width: options.width, height: options.height, background };
+            let images : Vec<render::Image> = cameras.iter().map(|c| render::render(&model, &result, c, &render_options)).collect();
+            let image = if images.len() == 1 {
+                images[0].clone()
+            } else {
+                let columns = options.columns.unwrap_or_else(|| (images.len() as f64).sqrt().ceil() as usize);
+                render::contact_sheet(&images, columns, background)
+            };
+
+            if let Err(e) = std::fs::write(&options.out, image.to_png()) {
+                eprintln!("An error occurred on save: {}", e);
+                return;
+            }
+            if images.len() == 1 {
+                println!("Rendered {} triangles to {}", result.triangles.len(), options.out);
+            } else {
+                println!("Rendered {} triangles from {} views to {}", result.triangles.len(), images.len(), options.out);
+            }
+        },
+
+        SubCommand::DiffRender(options) => {
+            let (first, second) = match (
+                p_load_model(&Some(options.first.clone()), &opts.memory),
+                p_load_model(&Some(options.second.clone()), &opts.memory)
+            ) {
+                (Some(a), Some(b)) => (a, b),
+                _ => return
+            };
+
+            let background = match render::parse_color(&options.background) {
+                Some(c) => c,
+                None => {
+                    eprintln!("Unknown color {}, expected RRGGBB or RRGGBBAA", options.background);
+                    return;
+                }
+            };
+            if options.width == 0 || options.height == 0 {
+                eprintln!("The image needs a width and height");
+                return;
+            }
+
+            // one camera framing both, so moved verticies show up as a difference
+            let camera = Camera::for_models(&[&first, &second], options.yaw, options.pitch, options.fov);
+            let render_options = RenderOptions { width: options.width, height: options.height, background };
+
+            let mut images = Vec::<render::Image>::new();
+            for (name, model) in [(&options.first, &first), (&options.second, &second)] {
+                let result = DisplayListInterpreter::run(model);
+                for warning in &result.warnings {
+                    eprintln!("warning: {}: {}", name, warning);
+                }
+                images.push(render::render(model, &result, &camera, &render_options));
+            }
+
+            let (difference, highlighted) = image_diff::compare(&images[0], &images[1], options.threshold);
+            let diff_filename = options.diff.clone().unwrap_or_else(|| {
+                let path = Path::new(&options.out);
+                let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                path.with_file_name(format!("{}_diff.png", stem)).to_string_lossy().to_string()
+            });
+
+            let side_by_side = render::contact_sheet(&images, 2, background);
+            for (filename, image) in [(&options.out, &side_by_side), (&diff_filename, &highlighted)] {
+                if let Err(e) = std::fs::write(filename, image.to_png()) {
+                    eprintln!("An error occurred on save: {}", e);
+                    return;
+                }
+            }
+
+            println!("Changed pixels: {} of {} ({:.2}%)", difference.changed_pixels, difference.total_pixels, difference.changed_percent());
+            println!("Mean difference: {:.3} Max difference: {}", difference.mean_difference, difference.max_difference);
+            println!("Wrote {} and {}", options.out, diff_filename);
+        },
+
+        SubCommand::MakePatch(options) => {
+            let (original, modified) = match (std::fs::read(&options.original), std::fs::read(&options.modified)) {
+                (Ok(o), Ok(m)) => (o, m),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("An error occurred on load: {}", e);
+                    return;
+                }
+            };
+
+            let format = match options.format.as_deref() {
+                Some("ips") => PatchFormat::Ips,
+                Some("bps") => PatchFormat::Bps,
+                Some(other) => {
+                    eprintln!("Unknown patch format '{}', expected ips or bps", other);
+                    return;
+                },
+                None => PatchFormat::from_filename(&options.out).unwrap_or(PatchFormat::Bps)
+            };
+
+            let patch = match patch::create(format, &original, &modified) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("An error occurred creating the patch: {}", e);
+                    return;
+                }
+            };
+
+            if let Err(e) = std::fs::write(&options.out, &patch) {
+                eprintln!("An error occurred on save: {}", e);
+                return;
+            }
+
+            println!("{:?} patch: {:#X} bytes", format, patch.len());
+        },
+
+        SubCommand::ApplyPatch(options) => {
+            let (original, patch_data) = match (std::fs::read(&options.original), std::fs::read(&options.patch)) {
+                (Ok(o), Ok(p)) => (o, p),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("An error occurred on load: {}", e);
+                    return;
+                }
+            };
+
+            let patched = match patch::apply(&original, &patch_data) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("An error occurred applying the patch: {}", e);
+                    return;
+                }
+            };
+
+            if let Some(out) = options.out {
+                if let Err(e) = std::fs::write(&out, &patched) {
+                    eprintln!("An error occurred on save: {}", e);
+                    return;
+                }
+            }
+
+            if let Some(verify) = options.verify {
+                let expected = match std::fs::read(&verify) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        eprintln!("An error occurred on load: {}", e);
+                        return;
+                    }
+                };
+
+                match patched.iter().zip(expected.iter()).position(|(a, b)| a != b) {
+                    None if patched.len() == expected.len() => println!("The patched output matches {}", verify),
+                    None => println!("The patched output is {:#X} bytes, {} is {:#X} bytes", patched.len(), verify, expected.len()),
+                    Some(offset) => println!("The patched output differs from {} at {:#X}", verify, offset)
+                }
+            }
+        }
+    }
+}
+
+//
+/// The --filename option, which most commands need. Prints an error if it is missing.
+//
+fn p_require_filename (filename : &Option<String>) -> Option<String>
+{
+    if filename.is_none() {
+        eprintln!("This command needs a file, pass it with --filename");
+    }
+
+    return filename.clone();
+}
+
+//
+/// Load the model given by --filename (or out of the RDRAM dump given by --filename and --address), printing any errors
+//
+fn p_load_model (filename : &Option<String>, memory : &MemoryOptions) -> Option<Model>
+{
+    let model_file = p_require_filename(filename)?;
+
+    let result = match &memory.address {
+        None => Model::load(model_file),
+        Some(address_text) => {
+            let address = match segment_table::parse_number(address_text) {
+                Some(a) => a,
+                None => {
+                    eprintln!("'{}' is not an address", address_text);
+                    return None;
+                }
+            };
+
+            let table = match SegmentTable::parse(&memory.segment) {
+                Ok(t) => t,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return None;
+                }
+            };
+
+            match std::fs::read(&model_file) {
+                Ok(dump) => Model::load_from_memory(&dump, address, table),
+                Err(e) => {
+                    eprintln!("An error occurred on load: {}", e);
+                    return None;
+                }
             }
+        }
+    };
+
+    return match result {
+        Ok(m) => Some(m),
+        Err(e) => {
+            eprintln!("An error occurred on load: {}", e);
+            if let Some(backtrace) = ErrorCompat::backtrace(&e) {
+               eprintln!("{}", backtrace);
+            }
+            None
+        }
+    };
+}
+
+//
+/// Export every texture in the model as <dir>/<prefix>_<index>.png
+//
+fn p_export_textures (model : &Model, dir : &Path, prefix : &str)
+{
+    for texture in model.textures() {
+        let path = dir.join(format!("{}_{}.png", prefix, texture.index()));
+        p_write_texture_png(texture, model.texture_rgba(texture), &path);
+    }
+}
+
+//
+/// The --color option for drawing in the terminal, or what the terminal supports if it wasnt given. Prints an error if it is unknown.
+//
+fn p_color_mode (color : &Option<String>) -> Option<ColorMode>
+{
+    return match color {
+        None => Some(ColorMode::detect()),
+        Some(name) => {
+            let mode = ColorMode::from_name(name);
+            if mode.is_none() {
+                eprintln!("Unknown color mode '{}', expected truecolor or 256", name);
+            }
+            mode
+        }
+    };
+}
+
+//
+/// Decode a texture into an image, printing any errors
+//
+fn p_texture_image (model : &Model, texture : &Texture) -> Option<render::Image>
+{
+    return match model.texture_rgba(texture) {
+        Ok(rgba) => Some(render::Image { width: texture.width() as usize, height: texture.height() as usize, rgba }),
+        Err(e) => {
+            eprintln!("texture_{}: could not be decoded: {}", texture.index(), e);
+            None
+        }
+    };
+}
 
-            println!("");
-            println!("# total length: {} verts", vs.verticies().len());
+//
+/// Write a decoded texture as a png, printing any errors
+//
+fn p_write_texture_png (texture : &Texture, rgba : errors::Result<Vec<u8>>, path : &Path)
+{
+    let rgba = match rgba {
+        Ok(rgba) => rgba,
+        Err(e) => {
+            eprintln!("texture_{}: could not be decoded: {}", texture.index(), e);
+            return;
         }
+    };
+
+    let png = png::encode_rgba(texture.width() as u32, texture.height() as u32, &rgba);
+    if let Err(e) = std::fs::write(path, png) {
+        eprintln!("An error occurred writing {}: {}", path.display(), e);
+    }
+}
+
+//
+/// Export the textures and geometry of a model into dir, with every file starting with name
+//
+fn p_export_model (model : &Model, dir : &Path, name : &str)
+{
+    let texture_prefix = format!("{}_texture", name);
+    p_export_textures(model, dir, &texture_prefix);
+
+    let mtl_filename = format!("{}.mtl", name);
+    let path = dir.join(&mtl_filename);
+    if let Err(e) = std::fs::write(&path, obj_export::export_mtl(model, &texture_prefix)) {
+        eprintln!("An error occurred writing {}: {}", path.display(), e);
+    }
+
+    let result = DisplayListInterpreter::run(model);
+    let path = dir.join(format!("{}.obj", name));
+    if let Err(e) = std::fs::write(&path, obj_export::export_obj(model, &result, Some(&mtl_filename))) {
+        eprintln!("An error occurred writing {}: {}", path.display(), e);
     }
 }
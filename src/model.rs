@@ -1,15 +1,23 @@
+use crate::animated_texture::{AnimatedTexture, ANIMATED_TEXTURE_SLOT_COUNT, ANIMATED_TEXTURE_SLOT_SIZE};
 use crate::display_list::DisplayList;
 use crate::display_list::DisplayListCommand;
 use crate::errors;
 use crate::errors::Result;
 use crate::geo_type::GeoType;
-use crate::texture::Texture;
+use crate::palette::Palette;
+use crate::quantize::{Dither, QuantizationError};
+use crate::rarezip;
+use crate::segment_map::{ResolvedAddress, SegmentMap};
+use crate::segment_table;
+use crate::segment_table::SegmentTable;
+use crate::texture::{Texture, TextureAddress};
 use crate::texture_type::TextureType;
-use crate::vertex_store::{Vertex,VertexStore};
+use crate::vertex_store::{Vertex,VertexStore,VertexStoreBounds,VERTEX_STORE_HEADER_SIZE};
 
 use snafu::ResultExt;
 use std::fs::File;
 use std::io::Read;
+use std::io::Write;
 use std::convert::TryInto;
 
 //
@@ -46,6 +54,9 @@ pub struct Model
     /// The number of verticies (according to the header)
     m_internal_vert_count: u32,
 
+    /// The offset the animated texture section starts (0 if the model has none)
+    m_internal_animated_texture_setup_offset: u32,
+
     /// Texture headers - information about each texture
     m_textures: Vec<Texture>,
 
@@ -55,17 +66,37 @@ pub struct Model
     /// The vertex store
     m_vertex_store: VertexStore,
 
+    /// Animated texture slots (only the active ones)
+    m_animated_textures: Vec<AnimatedTexture>,
+
+    /// Where the model was in RDRAM (only for models loaded from memory)
+    m_base_address: Option<u32>,
+
+    /// Where each segment points in the model
+    m_segment_map: SegmentMap,
+
     /// The raw data buffer
     /// NOTE: Most of this is in big endian, so convert as needed
     m_data : Vec<u8>
 }
 
+//
+/// Size of the model header
+//
+const MODEL_HEADER_SIZE : usize = 0x38;
+
+//
+/// Size of a vertex in the vertex store
+//
+const VERTEX_SIZE : usize = 16;
+
 impl Model
 {
     // --- public static
 
     //
-    /// Loads a model from the given file, returning the model or an error
+    /// Loads a model from the given file, returning the model or an error.
+    /// Rarezip compressed models (straight from the ROM) are decompressed automatically.
     //
     pub fn load (filename : String) -> Result<Model> {
         let mut file = File::open(filename).context(errors::IOError)?;
@@ -73,6 +104,24 @@ impl Model
         let mut data = Vec::<u8>::new();
         file.read_to_end(&mut data).context(errors::IOError)?;
 
+        return Model::from_bytes(data);
+    }
+
+    //
+    /// Loads a model from a buffer, returning the model or an error.
+    /// Rarezip compressed models are decompressed automatically.
+    //
+    pub fn from_bytes (data : Vec<u8>) -> Result<Model> {
+        let data = if rarezip::is_compressed(&data) {
+            rarezip::decompress(&data)?
+        } else {
+            data
+        };
+
+        if data.len() < MODEL_HEADER_SIZE {
+            return errors::SectionOutOfBounds { section: "header".to_string(), offset: 0usize, length: data.len() }.fail();
+        }
+
         // magic
         let mut cur = 0;
         let magic = u32::from_be_bytes(data[cur..cur+4].try_into().context(errors::FailedSliceError)?);
@@ -81,7 +130,7 @@ impl Model
         let expected_magic : u32 = 0x0000000B;
         if magic != expected_magic
         {
-            return errors::InvalidMagic{expected_magic:expected_magic, got_magic:magic}.fail();
+            return errors::InvalidMagic{expected_magic, got_magic:magic}.fail();
         }
 
         let geometry_setup_offset = u32::from_be_bytes(data[cur..cur+4].try_into().context(errors::FailedSliceError)?); //file.read_u32::<BigEndian>().context(errors::IOError)?;
@@ -118,7 +167,8 @@ impl Model
         /* unknown */
         cur += 4;
 
-        /* some animation setup offset? (vertex clipping?) */
+        /* animated texture setup */
+        let animated_texture_setup_offset = u32::from_be_bytes(data[cur..cur+4].try_into().context(errors::FailedSliceError)?);
         cur += 4;
 
         /* triangle count [0000kkkk]*/
@@ -128,8 +178,6 @@ impl Model
 
         /* vert count [llll0000]*/
         let vert_count = u16::from_be_bytes(data[cur..cur+2].try_into().context(errors::FailedSliceError)?);
-        cur += 2;
-        cur += 2;
 
 
         let mut model = Model {
@@ -140,22 +188,161 @@ impl Model
             m_internal_vertex_store_setup_offset : vertex_store_setup_offset,
             m_internal_triangle_count : tri_count as u32,
             m_internal_vert_count : vert_count as u32, // doesnt sound right
+            m_internal_animated_texture_setup_offset : animated_texture_setup_offset,
 
             m_textures : Vec::new(),
             m_display_list: DisplayList::new(),
             m_vertex_store: VertexStore::new(),
+            m_animated_textures: Vec::new(),
+            m_base_address: None,
+            m_segment_map: SegmentMap::new(),
             m_data: data
         };
 
         model.p_parse_displaylist()?;
         model.p_parse_textures()?;
         model.p_parse_vertexstore()?;
+        model.p_parse_animated_textures()?;
+
+        model.m_segment_map = SegmentMap::for_model_layout(
+            model.vertex_data_offset(), model.texture_data_offset(), model.display_list_commands_offset()
+        );
+
+        return Ok(model);
+    }
+
+    //
+    /// Loads a model which is already in memory (ex. from an RDRAM dump or save state).
+    ///
+    /// base_address is the (virtual) address of the model header, and segment_table is where
+    /// the game had each segment pointing when the model was drawn. The segmented addresses
+    /// in the display list are resolved through the table, falling back to the model's own
+    /// layout for any segment which isnt in it.
+    ///
+    /// The header doesnt store the size of the model, so the model is taken to end at the
+    /// furthest section we know the size of (sections after that are not kept).
+    //
+    pub fn load_from_memory (bytes : &[u8], base_address : u32, segment_table : SegmentTable) -> Result<Model>
+    {
+        let start = segment_table::physical_address(base_address) as usize;
+        if start + MODEL_HEADER_SIZE > bytes.len() {
+            return errors::SectionOutOfBounds { section: "header".to_string(), offset: start, length: bytes.len() }.fail();
+        }
 
+        let size = Model::p_size_in_memory(&bytes[start..])?;
+
+        let mut model = Model::from_bytes(bytes[start .. start + size].to_vec())?;
+        model.m_base_address = Some(base_address);
+        model.m_segment_map.apply_segment_table(&segment_table, base_address);
         return Ok(model);
     }
 
     // --- public helpers
 
+    //
+    /// Resolve a segmented address (from G_VTX, G_SETTIMG, G_DL) to an offset in the model data,
+    /// using the segment map. Returns None if the address doesnt land inside the model.
+    //
+    pub fn resolve_address (&self, segmented_address : u32) -> Option<usize>
+    {
+        let offset = self.m_segment_map.resolve(segmented_address)?;
+        if offset < 0 || offset as usize >= self.m_data.len() {
+            return None;
+        }
+        return Some(offset as usize);
+    }
+
+    //
+    /// Resolve the address used by a display list command to what it points at
+    /// (a vertex range for G_VTX, a texture for G_SETTIMG, a command for G_DL).
+    /// Returns None for commands which dont use an address.
+    //
+    pub fn resolve_command (&self, command : &DisplayListCommand) -> Option<ResolvedAddress>
+    {
+        let segmented_address = match command {
+            DisplayListCommand::G_VTX(c) => c.segmented_address(),
+            DisplayListCommand::G_SETTIMG(c) => c.segment_address_of_texture(),
+            DisplayListCommand::G_DL(c) => c.branch_segmented_address(),
+            _ => return None
+        };
+
+        if let DisplayListCommand::G_SETTIMG(_) = command {
+            return Some(ResolvedAddress::Texture(self.texture_for_address(segmented_address)));
+        }
+
+        let offset = match self.resolve_address(segmented_address) {
+            Some(o) => o,
+            None => return Some(ResolvedAddress::Unresolved { segmented_address })
+        };
+
+        let resolved = match command {
+            DisplayListCommand::G_VTX(c) => {
+                let vertex_data_offset = self.vertex_data_offset();
+                let first = offset.wrapping_sub(vertex_data_offset) / VERTEX_SIZE;
+                if offset >= vertex_data_offset && (offset - vertex_data_offset) % VERTEX_SIZE == 0
+                    && first + c.count() as usize <= self.m_vertex_store.verticies().len() {
+                    Some(ResolvedAddress::Verticies { first, count: c.count() as usize })
+                } else {
+                    None
+                }
+            },
+
+            _ => {
+                let commands_offset = self.display_list_commands_offset();
+                let command_index = offset.wrapping_sub(commands_offset) / 8;
+                if offset >= commands_offset && (offset - commands_offset) % 8 == 0 && command_index < self.m_display_list.commands().len() {
+                    Some(ResolvedAddress::DisplayList { command_index })
+                } else {
+                    None
+                }
+            }
+        };
+
+        return Some(resolved.unwrap_or(ResolvedAddress::Offset { offset }));
+    }
+
+    //
+    /// Find which texture a segmented address (ex. from G_SETTIMG) points at
+    //
+    pub fn texture_for_address (&self, segmented_address : u32) -> TextureAddress
+    {
+        let offset = match self.resolve_address(segmented_address) {
+            Some(o) => o,
+            None => return TextureAddress::NotTextureData { segmented_address }
+        };
+
+        let mut previous = None;
+        let mut data_end = self.texture_data_offset();
+        for texture in &self.m_textures {
+            let index = texture.index() as usize;
+            let start = texture.absolute_address() as usize;
+            let end = start + texture.data_size() as usize;
+            let palette_size = texture.texture_type().palette_size() as usize;
+
+            if offset == start {
+                return TextureAddress::Start { index };
+            }
+            if palette_size != 0 && offset == start + palette_size {
+                return TextureAddress::Pixels { index };
+            }
+            if offset > start && offset < end {
+                return TextureAddress::Inside { index, offset: offset - start };
+            }
+
+            if start < offset && previous.map_or(true, |p : &Texture| p.absolute_address() < texture.absolute_address()) {
+                previous = Some(texture);
+            }
+            data_end = data_end.max(end);
+        }
+
+        let data_start = self.texture_data_offset();
+        if offset >= data_start && offset < data_end {
+            return TextureAddress::Between { offset: offset - data_start, previous: previous.map(|t| t.index() as usize) };
+        }
+
+        return TextureAddress::NotTextureData { segmented_address };
+    }
+
     //
     /// Returns a list of valid subjects on the
     //
@@ -180,8 +367,164 @@ impl Model
         return v;
     }
 
+    //
+    /// Recompute the vertex store culling/collision values from the verticies,
+    /// so they are correct when the model is saved
+    //
+    pub fn recompute_vertex_store_bounds (&mut self)
+    {
+        self.m_vertex_store.recompute_bounds();
+    }
+
+    //
+    /// Change the S10.5 texture coordinates of a vertex in the vertex store
+    //
+    pub fn set_vertex_uv (&mut self, index : usize, uv : [i16; 2]) -> Result<()>
+    {
+        let count = self.m_vertex_store.verticies().len();
+        if index >= count {
+            return errors::VertexIndexOutOfRange { index, count }.fail();
+        }
+
+        // [xxxx yyyy zzzz ffff] [uuuu vvvv] [rgba]
+        let start = self.vertex_data_offset() + index * VERTEX_SIZE + 8;
+        if start + 4 > self.m_data.len() {
+            return errors::SectionOutOfBounds { section: "vertex store".to_string(), offset: start, length: self.m_data.len() }.fail();
+        }
+        self.m_data[start .. start + 2].copy_from_slice(&uv[0].to_be_bytes());
+        self.m_data[start + 2 .. start + 4].copy_from_slice(&uv[1].to_be_bytes());
+        self.m_vertex_store.set_uv(index, uv);
+
+        return Ok(());
+    }
+
+    //
+    /// Saves the model to the given file (uncompressed)
+    //
+    pub fn save (&mut self, filename : String) -> Result<()>
+    {
+        self.p_write_vertexstore_header();
+
+        let mut file = File::create(filename).context(errors::IOError)?;
+        file.write_all(&self.m_data).context(errors::IOError)?;
+
+        return Ok(());
+    }
+
+    //
+    /// The data (palette + pixels) for one of the model's textures
+    //
+    pub fn texture_data (&self, texture : &Texture) -> Result<&[u8]>
+    {
+        let start = texture.absolute_address() as usize;
+        let end = start + texture.data_size() as usize;
+        if end > self.m_data.len() {
+            return errors::SectionOutOfBounds {
+                section: format!("texture_{}", texture.index()), offset: start, length: self.m_data.len()
+            }.fail();
+        }
+
+        return Ok(&self.m_data[start..end]);
+    }
+
+    //
+    /// Decode one of the model's textures to 8 bit RGBA
+    //
+    pub fn texture_rgba (&self, texture : &Texture) -> Result<Vec<u8>>
+    {
+        return texture.decode_rgba(self.texture_data(texture)?);
+    }
+
+    //
+    /// Decode part of a texture's data as an image of its own (ex. a mipmap level after the first image).
+    /// offset is from the start of the pixels, and CI textures use the texture's palette.
+    //
+    pub fn texture_region_rgba (&self, texture : &Texture, offset : u32, width : u8, height : u8) -> Result<Vec<u8>>
+    {
+        // a texture at the same place as this one's pixels would be if the palette was in front of them
+        let region = Texture::new(texture.index(),
+            texture.segment_address() + offset, texture.absolute_address() + offset,
+            texture.texture_type(), width, height
+        );
+
+        let data = self.texture_data(&region)?;
+        return match self.texture_palette(texture)? {
+            Some(palette) => region.decode_rgba_with_palette(data, &palette),
+            None => region.decode_rgba(data)
+        };
+    }
+
+    //
+    /// Replace the image of one of the model's textures with an 8 bit RGBA image,
+    /// encoding it into the texture's type. The image has to be the same size as the texture.
+    /// CI textures are quantized with the given dithering, and return how far the result is from the image.
+    //
+    pub fn replace_texture (&mut self, index : usize, width : u32, height : u32, rgba : &[u8], dither : Dither) -> Result<Option<QuantizationError>>
+    {
+        let texture = match self.m_textures.get(index) {
+            Some(t) => t,
+            None => {
+                return errors::TextureEncodeError { reason: format!("there is no texture_{}", index) }.fail();
+            }
+        };
+
+        if texture.width() as u32 != width || texture.height() as u32 != height {
+            return errors::TextureSizeMismatch {
+                index, width: texture.width() as u32, height: texture.height() as u32, image_width: width, image_height: height
+            }.fail();
+        }
+
+        let (encoded, error) = texture.encode_rgba(rgba, dither)?;
+
+        let start = texture.absolute_address() as usize;
+        if start + encoded.len() > self.m_data.len() {
+            return errors::SectionOutOfBounds { section: format!("texture_{}", index), offset: start, length: self.m_data.len() }.fail();
+        }
+        self.m_data[start .. start + encoded.len()].copy_from_slice(&encoded);
+
+        return Ok(error);
+    }
+
+    //
+    /// The palette of one of the model's CI textures (None for other types)
+    //
+    pub fn texture_palette (&self, texture : &Texture) -> Result<Option<Palette>>
+    {
+        return texture.palette(self.texture_data(texture)?);
+    }
+
     // --- public properties
 
+    //
+    /// Where the model was in RDRAM (None if it wasnt loaded from memory)
+    //
+    pub fn base_address (&self) -> Option<u32>
+    { return self.m_base_address; }
+
+    //
+    /// Where each segment points in the model
+    //
+    pub fn segment_map (&self) -> &SegmentMap
+    { return &self.m_segment_map; }
+
+    //
+    /// The offset in the data where the verticies start (after the vertex store header)
+    //
+    pub fn vertex_data_offset (&self) -> usize
+    { return self.m_internal_vertex_store_setup_offset as usize + VERTEX_STORE_HEADER_SIZE; }
+
+    //
+    /// The offset in the data where the texture data starts (after the texture headers)
+    //
+    pub fn texture_data_offset (&self) -> usize
+    { return self.m_internal_texture_setup_offset as usize + 0x8 + 0x10 * self.m_textures.len(); }
+
+    //
+    /// The offset in the data where the display list commands start (after the count)
+    //
+    pub fn display_list_commands_offset (&self) -> usize
+    { return self.m_internal_display_list_setup_offset as usize + 0x8; }
+
     //
     /// The list of textures inside the model
     //
@@ -204,6 +547,12 @@ impl Model
     pub fn vertex_store (&self) -> &VertexStore
     { return &self.m_vertex_store; }
 
+    //
+    /// The animated texture slots which are in use
+    //
+    pub fn animated_textures (&self) -> &Vec<AnimatedTexture>
+    { return &self.m_animated_textures; }
+
     //
     /// The offset within the model that the geometry section starts
     //
@@ -246,19 +595,75 @@ impl Model
     pub fn internal_vert_count (&self) -> u32
     { return self.m_internal_vert_count; }
 
+    //
+    /// The animated texture section offset in the file (0 if there is none)
+    //
+    pub fn internal_animated_texture_setup_offset (&self) -> u32
+    { return self.m_internal_animated_texture_setup_offset; }
+
     // --- private
 
+    //
+    /// Work out how big a model in memory is, from the sections we know the size of
+    //
+    fn p_size_in_memory (data : &[u8]) -> Result<usize>
+    {
+        let read_u32 = |offset : usize| -> Result<u32> {
+            if offset + 4 > data.len() {
+                return errors::SectionOutOfBounds { section: "model in memory".to_string(), offset, length: data.len() }.fail();
+            }
+            return Ok(u32::from_be_bytes(data[offset..offset+4].try_into().context(errors::FailedSliceError)?));
+        };
+
+        let mut end = MODEL_HEADER_SIZE;
+
+        // textures: [bbbb bbbb] [00cc] [0000] followed by c 16 byte headers and b bytes of data
+        let texture_setup_offset = (read_u32(0x08)? >> 16) as usize;
+        if texture_setup_offset != 0 {
+            let texture_bytes = read_u32(texture_setup_offset)? as usize;
+            let texture_count = ((read_u32(texture_setup_offset + 4)? >> 16) & 0xFF) as usize;
+            end = end.max(texture_setup_offset + 0x8 + 0x10 * texture_count + texture_bytes);
+        }
+
+        // display list: [cccc cccc] [0000 0000] followed by c commands
+        let display_list_setup_offset = read_u32(0x0C)? as usize;
+        if display_list_setup_offset != 0 {
+            end = end.max(display_list_setup_offset + 0x8 + 8 * read_u32(display_list_setup_offset)? as usize);
+        }
+
+        // vertex store: header, followed by count / 2 verticies of 16 bytes
+        let vertex_store_setup_offset = read_u32(0x10)? as usize;
+        if vertex_store_setup_offset != 0 {
+            let vertex_count = (read_u32(vertex_store_setup_offset + VERTEX_STORE_HEADER_SIZE - 2)? >> 16) as usize;
+            end = end.max(vertex_store_setup_offset + VERTEX_STORE_HEADER_SIZE + 16 * (vertex_count / 2));
+        }
+
+        // animated textures: fixed size
+        let animated_texture_setup_offset = read_u32(0x2C)? as usize;
+        if animated_texture_setup_offset != 0 {
+            end = end.max(animated_texture_setup_offset + ANIMATED_TEXTURE_SLOT_COUNT * ANIMATED_TEXTURE_SLOT_SIZE);
+        }
+
+        if end > data.len() {
+            return errors::SectionOutOfBounds { section: "model in memory".to_string(), offset: 0usize, length: data.len() }.fail();
+        }
+
+        return Ok(end);
+    }
+
     //
     /// Parse the texture section, generating the basic header info
     //
     fn p_parse_textures (&mut self) -> Result<()> {
         let mut cur = (self.m_internal_texture_setup_offset) as usize;
+        self.p_check_section("textures", cur, 8)?;
         let _load_bytes = u32::from_be_bytes(self.m_data[cur..cur+4].try_into().context(errors::FailedSliceError)?);
         cur += 4;
 
         cur += 1;
         let tex_count = self.m_data[cur];
         cur += 3; // skip the rest of the byte
+        self.p_check_section("textures", self.m_internal_texture_setup_offset as usize, 8 + 0x10 * tex_count as usize)?;
 
         for i in 0 .. tex_count
         {
@@ -301,7 +706,9 @@ impl Model
     fn p_parse_displaylist (&mut self) -> Result<()>
     {
         let mut cur = self.m_internal_display_list_setup_offset as usize;
+        self.p_check_section("display list", cur, 8)?;
         let command_count = u32::from_be_bytes(self.m_data[cur..cur+4].try_into().context(errors::FailedSliceError)?);
+        self.p_check_section("display list", cur, 8 + 8 * command_count as usize)?;
         cur += 4;
 
         /* blank*/ cur += 4;
@@ -325,24 +732,40 @@ impl Model
     fn p_parse_vertexstore (&mut self) -> Result<()>
     {
         let mut cur = self.m_internal_vertex_store_setup_offset as usize;
+        self.p_check_section("vertex store", cur, VERTEX_STORE_HEADER_SIZE)?;
+        let vertex_count = u16::from_be_bytes(self.m_data[cur+VERTEX_STORE_HEADER_SIZE-2 .. cur+VERTEX_STORE_HEADER_SIZE].try_into().context(errors::FailedSliceError)?);
+        self.p_check_section("vertex store", cur, VERTEX_STORE_HEADER_SIZE + 16 * (vertex_count / 2) as usize)?;
+
+        let mut read_i16 = |data: &Vec<u8>| -> Result<i16> {
+            let v = i16::from_be_bytes(data[cur..cur+2].try_into().context(errors::FailedSliceError)?);
+            cur += 2;
+            return Ok(v);
+        };
 
         // draw distance - negative coords
-        cur += 6;
+        let draw_distance_negative = [read_i16(&self.m_data)?, read_i16(&self.m_data)?, read_i16(&self.m_data)?];
 
         // draw distance - pos coords
-        cur += 6;
+        let draw_distance_positive = [read_i16(&self.m_data)?, read_i16(&self.m_data)?, read_i16(&self.m_data)?];
 
         // object coordinate range
-        cur += 4;
+        let object_coordinate_range = [read_i16(&self.m_data)?, read_i16(&self.m_data)?];
 
         // collision range? (enemies/objects)
-        cur += 2;
+        let object_collision_range = read_i16(&self.m_data)? as u16;
 
         // collission range? (banjo)
-        cur += 2;
+        let banjo_collision_range = read_i16(&self.m_data)? as u16;
 
-        // vertex count
-        let vertex_count = u16::from_be_bytes(self.m_data[cur..cur+2].try_into().context(errors::FailedSliceError)?);
+        self.m_vertex_store.set_bounds(VertexStoreBounds {
+            draw_distance_negative,
+            draw_distance_positive,
+            object_coordinate_range,
+            object_collision_range,
+            banjo_collision_range
+        });
+
+        // vertex count (read above)
         cur += 2;
 
         // verticies
@@ -385,4 +808,324 @@ impl Model
 
         return Ok(());
     }
+
+    //
+    /// Parse the animated texture section, if the model has one
+    //
+    fn p_parse_animated_textures (&mut self) -> Result<()>
+    {
+        if self.m_internal_animated_texture_setup_offset == 0 {
+            return Ok(());
+        }
+
+        let mut cur = self.m_internal_animated_texture_setup_offset as usize;
+        self.p_check_section("animated textures", cur, ANIMATED_TEXTURE_SLOT_COUNT * ANIMATED_TEXTURE_SLOT_SIZE)?;
+
+        for slot in 0 .. ANIMATED_TEXTURE_SLOT_COUNT
+        {
+            // [ssss] [cccc] [ffff ffff]
+            let frame_size = u16::from_be_bytes(self.m_data[cur..cur+2].try_into().context(errors::FailedSliceError)?);
+            cur += 2;
+            let frame_count = u16::from_be_bytes(self.m_data[cur..cur+2].try_into().context(errors::FailedSliceError)?);
+            cur += 2;
+            let frame_rate = f32::from_be_bytes(self.m_data[cur..cur+4].try_into().context(errors::FailedSliceError)?);
+            cur += 4;
+
+            let animated_texture = AnimatedTexture::new(slot as u8, frame_size, frame_count, frame_rate);
+            if animated_texture.is_active() {
+                self.m_animated_textures.push(animated_texture);
+            }
+        }
+
+        return Ok(());
+    }
+
+    //
+    /// Fail with SectionOutOfBounds if length bytes at offset dont fit in the data
+    //
+    fn p_check_section (&self, section : &str, offset : usize, length : usize) -> Result<()>
+    {
+        if offset.saturating_add(length) > self.m_data.len() {
+            return errors::SectionOutOfBounds { section: section.to_string(), offset, length: self.m_data.len() }.fail();
+        }
+        return Ok(());
+    }
+
+    //
+    /// Write the vertex store bounds back into the data buffer
+    //
+    fn p_write_vertexstore_header (&mut self)
+    {
+        let bounds = self.m_vertex_store.bounds();
+        let values = [
+            bounds.draw_distance_negative[0], bounds.draw_distance_negative[1], bounds.draw_distance_negative[2],
+            bounds.draw_distance_positive[0], bounds.draw_distance_positive[1], bounds.draw_distance_positive[2],
+            bounds.object_coordinate_range[0], bounds.object_coordinate_range[1],
+            bounds.object_collision_range as i16,
+            bounds.banjo_collision_range as i16
+        ];
+
+        let mut cur = self.m_internal_vertex_store_setup_offset as usize;
+        for v in values.iter() {
+            self.m_data[cur..cur+2].copy_from_slice(&v.to_be_bytes());
+            cur += 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::segment_map::{DISPLAY_LIST_SEGMENT, TEXTURE_SEGMENT, VERTEX_SEGMENT};
+    use crate::test_models;
+    use crate::test_models::ModelBuilder;
+
+    #[test]
+    fn parses_animated_textures ()
+    {
+        let mut builder = ModelBuilder::new();
+        builder.animated_textures(&[(0x200, 4, 8.0), (0, 0, 0.0), (0x80, 2, 15.5)]);
+        let model = Model::from_bytes(builder.build()).unwrap();
+
+        // the empty slots are left out
+        let slots : Vec<(u8, u16, u16, f32)> = model.animated_textures().iter()
+            .map(|a| (a.slot(), a.frame_size(), a.frame_count(), a.frame_rate()))
+            .collect();
+        assert_eq!(slots, vec![(0, 0x200, 4, 8.0), (2, 0x80, 2, 15.5)]);
+        assert_eq!(model.animated_textures()[0].total_size(), 0x800);
+    }
+
+    #[test]
+    fn animated_textures_are_optional ()
+    {
+        let model = Model::from_bytes(ModelBuilder::new().build()).unwrap();
+        assert_eq!(model.internal_animated_texture_setup_offset(), 0);
+        assert!(model.animated_textures().is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_animated_textures ()
+    {
+        let mut builder = ModelBuilder::new();
+        builder.animated_textures(&[(0x200, 4, 8.0)]);
+        let mut data = builder.build();
+        data.truncate(data.len() - 4);
+
+        match Model::from_bytes(data) {
+            Err(errors::Error::SectionOutOfBounds { section, .. }) => assert_eq!(section, "animated textures"),
+            other => panic!("expected SectionOutOfBounds, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    fn p_assert_out_of_bounds (data : Vec<u8>, expected : &str)
+    {
+        match Model::from_bytes(data) {
+            Err(errors::Error::SectionOutOfBounds { section, .. }) => assert_eq!(section, expected),
+            other => panic!("expected SectionOutOfBounds, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    //
+    /// A model with something in every section
+    //
+    fn p_full_model () -> Vec<u8>
+    {
+        let mut builder = ModelBuilder::new();
+        builder.texture(TextureType::RGBA16, 2, 2, vec![0xFF; 8]);
+        builder.vertex([0, 0, 0], [0, 0], [0xFF; 3], 0xFF);
+        builder.vertex([10, 0, 0], [0, 0], [0xFF; 3], 0xFF);
+        builder.vertex([0, 10, 0], [0, 0], [0xFF; 3], 0xFF);
+        builder.command(test_models::g_vtx(0, 3, test_models::vertex_address(0)));
+        builder.command(test_models::g_tri2([0, 1, 2], [0, 0, 0]));
+        return builder.build();
+    }
+
+    fn p_read_offset (data : &[u8], offset : usize) -> usize
+    {
+        return u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) as usize;
+    }
+
+    #[test]
+    fn rejects_sections_past_the_end ()
+    {
+        let data = p_full_model();
+        assert!(Model::from_bytes(data.clone()).is_ok());
+        let texture_offset = p_read_offset(&data, 0x08) >> 16;
+        let display_list_offset = p_read_offset(&data, 0x0C);
+
+        p_assert_out_of_bounds(data[.. MODEL_HEADER_SIZE - 2].to_vec(), "header");
+        p_assert_out_of_bounds(data[.. data.len() - 4].to_vec(), "vertex store");
+
+        // counts bigger than the data
+        let mut textures = data.clone();
+        textures[texture_offset + 5] = 0xFF;
+        p_assert_out_of_bounds(textures, "textures");
+
+        let mut display_list = data.clone();
+        display_list[display_list_offset .. display_list_offset + 4].copy_from_slice(&0x1000u32.to_be_bytes());
+        p_assert_out_of_bounds(display_list, "display list");
+
+        let mut verticies = data.clone();
+        let count = p_read_offset(&data, 0x10) + VERTEX_STORE_HEADER_SIZE - 2;
+        verticies[count .. count + 2].copy_from_slice(&0x100u16.to_be_bytes());
+        p_assert_out_of_bounds(verticies, "vertex store");
+
+        // offsets past the end
+        let mut far = data.clone();
+        far[0x0C .. 0x10].copy_from_slice(&0xFFFFFFFFu32.to_be_bytes());
+        p_assert_out_of_bounds(far, "display list");
+
+        let mut far = data;
+        far[0x08 .. 0x0A].copy_from_slice(&0xFFF0u16.to_be_bytes());
+        p_assert_out_of_bounds(far, "textures");
+    }
+
+    #[test]
+    fn save_rewrites_the_vertex_store_header ()
+    {
+        // move the second vertex from (10, 0, 0) to (-50, 20, 30), leaving the header as it was
+        let mut data = p_full_model();
+        let vertex = Model::from_bytes(data.clone()).unwrap().vertex_data_offset() + VERTEX_SIZE;
+        for (axis, value) in [-50i16, 20, 30].iter().enumerate() {
+            data[vertex + axis * 2 .. vertex + axis * 2 + 2].copy_from_slice(&value.to_be_bytes());
+        }
+
+        let mut model = Model::from_bytes(data.clone()).unwrap();
+        let stale = model.vertex_store().bounds();
+        model.recompute_vertex_store_bounds();
+
+        // the box is (-50, 0, 0) to (0, 20, 30), centered on (-25, 10, 15)
+        let expected = VertexStoreBounds {
+            draw_distance_negative: [-50, 0, 0],
+            draw_distance_positive: [0, 20, 30],
+            object_coordinate_range: [-50, 30],
+            object_collision_range: 31,
+            banjo_collision_range: 62
+        };
+        assert_eq!(model.vertex_store().bounds(), expected);
+        assert_ne!(stale, expected);
+
+        let path = std::env::temp_dir().join(format!("bkmodel_save_{}.bin", std::process::id()));
+        model.save(path.to_string_lossy().to_string()).unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let reloaded = Model::from_bytes(saved.clone()).unwrap();
+        assert_eq!(reloaded.vertex_store().bounds(), expected);
+        assert_eq!(reloaded.vertex_store().verticies()[1].pos, [-50, 20, 30]);
+
+        // only the bounds changed, not the vertex count after them or anything else
+        let header = p_read_offset(&data, 0x10);
+        let bounds_size = VERTEX_STORE_HEADER_SIZE - 2;
+        assert_eq!(saved.len(), data.len());
+        assert_eq!(saved[.. header], data[.. header]);
+        assert_eq!(saved[header + bounds_size ..], data[header + bounds_size ..]);
+    }
+
+
+    //
+    /// An RDRAM dump with the model at a physical address, with filler either side of it
+    //
+    fn p_memory_dump (model : &[u8], physical_address : usize) -> Vec<u8>
+    {
+        let mut dump = vec![0xEE; physical_address];
+        dump.extend_from_slice(model);
+        dump.extend_from_slice(&[0xEE; 0x100]);
+        return dump;
+    }
+
+    #[test]
+    fn loads_from_memory ()
+    {
+        let data = p_full_model();
+        let file = Model::from_bytes(data.clone()).unwrap();
+        let dump = p_memory_dump(&data, 0x1000);
+
+        // point the vertex segment at the second vertex, the rest keep the file layout
+        let mut table = SegmentTable::new();
+        table.set(VERTEX_SEGMENT, 0x1000 + file.vertex_data_offset() as u32 + VERTEX_SIZE as u32);
+        let model = Model::load_from_memory(&dump, 0x1000, table).unwrap();
+
+        assert_eq!(model.base_address(), Some(0x1000));
+        assert_eq!(model.vertex_store().verticies().len(), 3);
+        assert_eq!(model.textures().len(), 1);
+        assert_eq!(model.display_list().commands().len(), 2);
+        assert_eq!(model.segment_map().get(VERTEX_SEGMENT), Some((file.vertex_data_offset() + VERTEX_SIZE) as i64));
+        assert_eq!(model.segment_map().get(TEXTURE_SEGMENT), Some(file.texture_data_offset() as i64));
+        assert_eq!(model.resolve_address(test_models::vertex_address(0)), Some(file.vertex_data_offset() + VERTEX_SIZE));
+
+        // which leaves the G_VTX loading 3 verticies from the second, one past the end
+        assert_eq!(model.resolve_command(&model.display_list().commands()[0]), Some(ResolvedAddress::Offset { offset: file.vertex_data_offset() + VERTEX_SIZE }));
+    }
+
+    #[test]
+    fn loads_from_memory_at_a_kseg0_address ()
+    {
+        let data = p_full_model();
+        let file = Model::from_bytes(data.clone()).unwrap();
+        let dump = p_memory_dump(&data, 0x2000);
+
+        let mut table = SegmentTable::new();
+        table.set(TEXTURE_SEGMENT, 0x8000_2000 + file.texture_data_offset() as u32);
+        let model = Model::load_from_memory(&dump, 0x8000_2000, table).unwrap();
+
+        assert_eq!(model.base_address(), Some(0x8000_2000));
+        assert_eq!(model.segment_map().get(TEXTURE_SEGMENT), Some(file.texture_data_offset() as i64));
+        assert_eq!(model.texture_for_address(0x0200_0000), TextureAddress::Start { index: 0 });
+
+        // plain virtual addresses are relative to where the model was
+        let vertex = 0x8000_2000 + file.vertex_data_offset() as u32;
+        assert_eq!(model.resolve_address(vertex), Some(file.vertex_data_offset()));
+        assert_eq!(model.resolve_address(vertex | 0x2000_0000), Some(file.vertex_data_offset()));
+    }
+
+    #[test]
+    fn rejects_models_past_the_end_of_memory ()
+    {
+        let data = p_full_model();
+
+        // the vertex store runs off the end, then the header does, then the whole model is past the end
+        let mut dump = vec![0xEE; 0x1000];
+        dump.extend_from_slice(&data[.. data.len() - 4]);
+        for (dump, base_address) in &[(dump, 0x1000u32), (vec![0xEE; 0x1010], 0x1000), (vec![0xEE; 0x100], 0x8000_1000)] {
+            match Model::load_from_memory(dump, *base_address, SegmentTable::new()) {
+                Err(errors::Error::SectionOutOfBounds { .. }) => {},
+                other => panic!("expected SectionOutOfBounds at {:#X}, got {:?}", base_address, other.map(|_| ()))
+            }
+        }
+    }
+
+
+    #[test]
+    fn resolves_command_addresses ()
+    {
+        // the second G_VTX runs past the last vertex, and the G_DL branches to the G_TRI2
+        let mut builder = ModelBuilder::new();
+        let texture = builder.texture(TextureType::RGBA16, 2, 2, vec![0xFF; 8]);
+        for i in 0 .. 3 {
+            builder.vertex([i, 0, 0], [0, 0], [0xFF; 3], 0xFF);
+        }
+        builder.command(test_models::g_vtx(0, 3, test_models::vertex_address(0)));
+        builder.command(test_models::g_tri2([0, 1, 2], [0, 0, 0]));
+        builder.command(test_models::g_settimg(texture));
+        builder.command(test_models::g_dl((DISPLAY_LIST_SEGMENT as u32) << 24 | 8));
+        builder.command(test_models::g_vtx(0, 2, test_models::vertex_address(2)));
+        builder.command(test_models::g_dl(0x0500_0000));
+        builder.command(test_models::g_dl((DISPLAY_LIST_SEGMENT as u32) << 24 | 0x0010_0000));
+        builder.animated_textures(&[(0x200, 4, 8.0)]);
+        let model = Model::from_bytes(builder.build()).unwrap();
+
+        let resolved : Vec<Option<ResolvedAddress>> = model.display_list().commands().iter().map(|c| model.resolve_command(c)).collect();
+        assert_eq!(resolved, vec![
+            Some(ResolvedAddress::Verticies { first: 0, count: 3 }),
+            None,
+            Some(ResolvedAddress::Texture(TextureAddress::Start { index: 0 })),
+            Some(ResolvedAddress::DisplayList { command_index: 1 }),
+            Some(ResolvedAddress::Offset { offset: model.vertex_data_offset() + 2 * VERTEX_SIZE }),
+            Some(ResolvedAddress::Unresolved { segmented_address: 0x0500_0000 }),
+            Some(ResolvedAddress::Unresolved { segmented_address: 0x0310_0000 })
+        ]);
+    }
+
 }
@@ -0,0 +1,67 @@
+use crate::display_list_interpreter::InterpreterResult;
+use crate::model::Model;
+
+use std::fmt::Write;
+
+//
+/// Export the model geometry as a Wavefront OBJ.
+///
+/// Every vertex in the vertex store is written (in order, so OBJ index n+1 is
+/// vertex store index n) followed by the triangles the display list draws,
+/// grouped by the texture (material texture_<index>) they were drawn with.
+/// mtl_filename is the material library to reference (see export_mtl), if there is one.
+//
+pub fn export_obj (model : &Model, result : &InterpreterResult, mtl_filename : Option<&str>) -> String
+{
+    let mut out = String::new();
+
+    writeln!(out, "# exported by bkmodel").unwrap();
+    for (command_index, texture) in &result.texture_loads {
+        writeln!(out, "# command {}: G_SETTIMG {}", command_index, texture.describe()).unwrap();
+    }
+
+    if let Some(mtl_filename) = mtl_filename {
+        writeln!(out, "mtllib {}", mtl_filename).unwrap();
+    }
+    writeln!(out, "o model").unwrap();
+
+    for v in model.vertex_store().verticies() {
+        writeln!(out, "v {} {} {}", v.pos[0], v.pos[1], v.pos[2]).unwrap();
+    }
+
+    let mut material = None;
+    for (i, triangle) in result.triangles.iter().enumerate() {
+        if i == 0 || triangle.texture != material {
+            material = triangle.texture;
+            match material {
+                Some(index) => writeln!(out, "usemtl texture_{}", index).unwrap(),
+                None => writeln!(out, "usemtl none").unwrap()
+            }
+        }
+
+        writeln!(out, "f {} {} {}",
+            triangle.verticies[0] + 1, triangle.verticies[1] + 1, triangle.verticies[2] + 1
+        ).unwrap();
+    }
+
+    return out;
+}
+
+//
+/// Export a material library with a material for each texture (texture_<index>),
+/// using the images written as <texture_prefix>_<index>.png
+//
+pub fn export_mtl (model : &Model, texture_prefix : &str) -> String
+{
+    let mut out = String::new();
+
+    writeln!(out, "# exported by bkmodel").unwrap();
+    for texture in model.textures() {
+        writeln!(out, "newmtl texture_{}", texture.index()).unwrap();
+        writeln!(out, "map_Kd {}_{}.png", texture_prefix, texture.index()).unwrap();
+        writeln!(out).unwrap();
+    }
+
+    writeln!(out, "newmtl none").unwrap();
+    return out;
+}
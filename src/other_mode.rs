@@ -0,0 +1,258 @@
+// The RDP other mode settings, set a few bits at a time by G_SetOtherMode_H (higher word)
+// and G_SetOtherMode_L (lower word). Each setting is a field at a shift in the word.
+
+//
+/// The fields of the higher word: (name, shift, length)
+//
+const HIGH_FIELDS : [(&str, u32, u32); 10] = [
+    ("alphaDither", G_MDSFT_ALPHADITHER, 2),
+    ("rgbDither", G_MDSFT_RGBDITHER, 2),
+    ("combineKey", G_MDSFT_COMBKEY, 1),
+    ("textureConvert", G_MDSFT_TEXTCONV, 3),
+    ("textureFilter", G_MDSFT_TEXTFILT, 2),
+    ("textureLut", G_MDSFT_TEXTLUT, 2),
+    ("textureLod", G_MDSFT_TEXTLOD, 1),
+    ("textureDetail", G_MDSFT_TEXTDETAIL, 2),
+    ("texturePerspective", G_MDSFT_TEXTPERSP, 1),
+    ("cycleType", G_MDSFT_CYCLETYPE, 2)
+];
+
+//
+/// The fields of the lower word: (name, shift, length). The render mode is described on its own.
+//
+const LOW_FIELDS : [(&str, u32, u32); 2] = [
+    ("alphaCompare", G_MDSFT_ALPHACOMPARE, 2),
+    ("zSource", G_MDSFT_ZSRCSEL, 1)
+];
+
+//
+/// The render mode flags in the lower word, with their names
+//
+const RENDER_MODE_FLAGS : [(u32, &str); 8] = [
+    (AA_EN, "AA_EN"),
+    (Z_CMP, "Z_CMP"),
+    (Z_UPD, "Z_UPD"),
+    (IM_RD, "IM_RD"),
+    (CLR_ON_CVG, "CLR_ON_CVG"),
+    (CVG_X_ALPHA, "CVG_X_ALPHA"),
+    (ALPHA_CVG_SEL, "ALPHA_CVG_SEL"),
+    (FORCE_BL, "FORCE_BL")
+];
+
+// --- higher word shifts
+
+pub const G_MDSFT_ALPHADITHER : u32 = 4;
+pub const G_MDSFT_RGBDITHER : u32 = 6;
+pub const G_MDSFT_COMBKEY : u32 = 8;
+pub const G_MDSFT_TEXTCONV : u32 = 9;
+pub const G_MDSFT_TEXTFILT : u32 = 12;
+pub const G_MDSFT_TEXTLUT : u32 = 14;
+pub const G_MDSFT_TEXTLOD : u32 = 16;
+pub const G_MDSFT_TEXTDETAIL : u32 = 17;
+pub const G_MDSFT_TEXTPERSP : u32 = 19;
+pub const G_MDSFT_CYCLETYPE : u32 = 20;
+
+// --- higher word values
+
+//
+/// Texture filter: nearest texel
+//
+pub const G_TF_POINT : u32 = 0;
+
+//
+/// Texture filter: three point bilinear
+//
+pub const G_TF_BILERP : u32 = 2 << G_MDSFT_TEXTFILT;
+
+//
+/// Texture filter: average of four texels
+//
+pub const G_TF_AVERAGE : u32 = 3 << G_MDSFT_TEXTFILT;
+
+//
+/// Pick the tile from the level of detail (mipmapping)
+//
+pub const G_TL_LOD : u32 = 1 << G_MDSFT_TEXTLOD;
+
+//
+/// Perspective correct texture coordinates
+//
+pub const G_TP_PERSP : u32 = 1 << G_MDSFT_TEXTPERSP;
+
+//
+/// One cycle mode
+//
+pub const G_CYC_1CYCLE : u32 = 0;
+
+//
+/// Two cycle mode (the combiner and blender run twice per pixel)
+//
+pub const G_CYC_2CYCLE : u32 = 1 << G_MDSFT_CYCLETYPE;
+
+//
+/// What the game sets in the higher word before drawing models
+//
+pub const DEFAULT_OTHER_MODE_H : u32 = G_TF_BILERP | G_TP_PERSP | G_CYC_1CYCLE;
+
+// --- lower word shifts
+
+pub const G_MDSFT_ALPHACOMPARE : u32 = 0;
+pub const G_MDSFT_ZSRCSEL : u32 = 2;
+pub const G_MDSFT_RENDERMODE : u32 = 3;
+
+// --- lower word values
+
+//
+/// Alpha compare: only write pixels with more alpha than the blend color
+//
+pub const G_AC_THRESHOLD : u32 = 1;
+
+//
+/// Alpha compare: write pixels randomly, more often the higher the alpha
+//
+pub const G_AC_DITHER : u32 = 3;
+
+// --- render mode flags (lower word)
+
+pub const AA_EN : u32 = 0x0008;
+pub const Z_CMP : u32 = 0x0010;
+pub const Z_UPD : u32 = 0x0020;
+pub const IM_RD : u32 = 0x0040;
+pub const CLR_ON_CVG : u32 = 0x0080;
+pub const CVG_DST_CLAMP : u32 = 0;
+pub const CVG_DST_WRAP : u32 = 0x0100;
+pub const CVG_DST_FULL : u32 = 0x0200;
+pub const CVG_DST_SAVE : u32 = 0x0300;
+pub const CVG_DST_MASK : u32 = 0x0300;
+pub const ZMODE_OPA : u32 = 0;
+pub const ZMODE_INTER : u32 = 0x0400;
+pub const ZMODE_XLU : u32 = 0x0800;
+pub const ZMODE_DEC : u32 = 0x0C00;
+pub const ZMODE_MASK : u32 = 0x0C00;
+pub const CVG_X_ALPHA : u32 = 0x1000;
+pub const ALPHA_CVG_SEL : u32 = 0x2000;
+pub const FORCE_BL : u32 = 0x4000;
+
+// --- blender inputs: the first cycle's P, A, M and B shifts are 30, 26, 22 and 18, the second's 28, 24, 20 and 16
+
+pub const G_BL_CLR_IN : u32 = 0;
+pub const G_BL_CLR_MEM : u32 = 1;
+pub const G_BL_CLR_BL : u32 = 2;
+pub const G_BL_CLR_FOG : u32 = 3;
+pub const G_BL_A_IN : u32 = 0;
+pub const G_BL_A_FOG : u32 = 1;
+pub const G_BL_A_SHADE : u32 = 2;
+pub const G_BL_1MA : u32 = 0;
+pub const G_BL_A_MEM : u32 = 1;
+pub const G_BL_1 : u32 = 2;
+pub const G_BL_0 : u32 = 3;
+
+//
+/// The blender settings for drawing an opaque surface in both cycles: input color, or memory at the edges
+//
+const BLEND_OPAQUE : u32 = (G_BL_CLR_IN << 30) | (G_BL_A_IN << 26) | (G_BL_CLR_MEM << 22) | (G_BL_A_MEM << 18)
+    | (G_BL_CLR_IN << 28) | (G_BL_A_IN << 24) | (G_BL_CLR_MEM << 20) | (G_BL_A_MEM << 16);
+
+//
+/// What the game sets in the lower word before drawing models (G_RM_AA_ZB_TEX_EDGE):
+/// opaque, Z buffered, and texels with no alpha dont cover the pixel
+//
+pub const DEFAULT_OTHER_MODE_L : u32 = AA_EN | Z_CMP | Z_UPD | IM_RD | CVG_DST_CLAMP | ZMODE_OPA
+    | CVG_X_ALPHA | ALPHA_CVG_SEL | BLEND_OPAQUE;
+
+//
+/// Replace length bits at shift in an other mode word
+//
+pub fn apply (mode : u32, shift : u32, length : u32, data : u32) -> u32
+{
+    let mask = p_mask(shift, length);
+    return (mode & !mask) | (data & mask);
+}
+
+//
+/// A field of an other mode word
+//
+pub fn field (mode : u32, shift : u32, length : u32) -> u32
+{
+    return (mode & p_mask(shift, length)) >> shift.min(31);
+}
+
+//
+/// Describe the settings of the higher word which fall between shift and shift+length
+//
+pub fn describe_high (shift : u32, length : u32, data : u32) -> String
+{
+    return p_describe(&HIGH_FIELDS, shift, length, data);
+}
+
+//
+/// Describe the settings of the lower word which fall between shift and shift+length
+//
+pub fn describe_low (shift : u32, length : u32, data : u32) -> String
+{
+    if shift > G_MDSFT_RENDERMODE || shift + length < 32 {
+        return p_describe(&LOW_FIELDS, shift, length, data);
+    }
+
+    // the render mode is a set of flags and the blender settings, rather than a number
+    let mut flags : Vec<&str> = RENDER_MODE_FLAGS.iter().filter(|(f, _)| data & f != 0).map(|(_, n)| *n).collect();
+    flags.push(match data & CVG_DST_MASK {
+        CVG_DST_WRAP => "CVG_DST_WRAP",
+        CVG_DST_FULL => "CVG_DST_FULL",
+        CVG_DST_SAVE => "CVG_DST_SAVE",
+        _ => "CVG_DST_CLAMP"
+    });
+    flags.push(match data & ZMODE_MASK {
+        ZMODE_OPA => "ZMODE_OPA",
+        ZMODE_INTER => "ZMODE_INTER",
+        ZMODE_XLU => "ZMODE_XLU",
+        _ => "ZMODE_DEC"
+    });
+    let render_mode = format!("renderMode:[{}] blend1:{} blend2:{}", flags.join("|"), p_describe_blend(data, 0), p_describe_blend(data, 1));
+
+    if shift == G_MDSFT_RENDERMODE {
+        return render_mode;
+    }
+    return format!("{} {}", p_describe(&LOW_FIELDS[.. 2], shift, G_MDSFT_RENDERMODE - shift, data), render_mode);
+}
+
+//
+/// The blender equation for a cycle, ex. (CLR_IN * A_IN + CLR_MEM * 1MA)
+//
+fn p_describe_blend (data : u32, cycle : u32) -> String
+{
+    let color = |v : u32| -> &str { ["CLR_IN", "CLR_MEM", "CLR_BL", "CLR_FOG"][v as usize] };
+    let p = field(data, 30 - cycle * 2, 2);
+    let a = field(data, 26 - cycle * 2, 2);
+    let m = field(data, 22 - cycle * 2, 2);
+    let b = field(data, 18 - cycle * 2, 2);
+    return format!("({} * {} + {} * {})",
+        color(p), ["A_IN", "A_FOG", "A_SHADE", "0"][a as usize],
+        color(m), ["1MA", "A_MEM", "1", "0"][b as usize]
+    );
+}
+
+fn p_describe (fields : &[(&str, u32, u32)], shift : u32, length : u32, data : u32) -> String
+{
+    let mut parts = Vec::<String>::new();
+    for (name, field_shift, field_length) in fields.iter() {
+        if *field_shift >= shift && field_shift + field_length <= shift + length {
+            parts.push(format!("{}:{}", name, field(data, *field_shift, *field_length)));
+        }
+    }
+
+    if parts.is_empty() {
+        return format!("shift:{} length:{} data:{:#X}", shift, length, data);
+    }
+    return parts.join(" ");
+}
+
+fn p_mask (shift : u32, length : u32) -> u32
+{
+    if shift >= 32 {
+        return 0;
+    }
+
+    let bits = if length >= 32 { u32::MAX } else { (1u32 << length) - 1 };
+    return bits.checked_shl(shift).unwrap_or(0);
+}
@@ -0,0 +1,297 @@
+use crate::errors;
+use crate::errors::Result;
+use crate::png;
+use crate::texture::rgba5551_to_rgba8;
+
+use snafu::ResultExt;
+use std::fmt::Write;
+
+//
+/// The size of each color in a swatch png, in pixels
+//
+const SWATCH_CELL_SIZE : usize = 16;
+
+//
+/// How many colors go on each row of a swatch png
+//
+const SWATCH_COLUMNS : usize = 16;
+
+//
+/// The palette (TLUT) of a CI texture: 16 (CI4) or 256 (CI8) RGBA5551 colors.
+///
+/// In the model the palette sits right before the color indices:
+/// [cccc] * count
+/// c: RGBA5551 color (rrrrr ggggg bbbbb a), big endian
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette
+{
+    m_colors: Vec<u16>
+}
+
+impl Palette
+{
+    //
+    /// Create a palette from RGBA5551 colors
+    //
+    pub fn new (colors : Vec<u16>) -> Palette
+    {
+        return Palette {
+            m_colors: colors
+        };
+    }
+
+    //
+    /// Read count colors from palette data in the model
+    //
+    pub fn from_bytes (data : &[u8], count : usize) -> Result<Palette>
+    {
+        if data.len() < count * 2 {
+            return errors::InvalidPalette { reason: format!("{} colors need {:#X} bytes, only have {:#X}", count, count * 2, data.len()) }.fail();
+        }
+
+        let colors = data.chunks_exact(2).take(count).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+        return Ok(Palette::new(colors));
+    }
+
+    //
+    /// Load a palette from a file - either a GIMP palette (.gpl) or raw RGBA5551 data (anything else)
+    //
+    pub fn load (filename : &str) -> Result<Palette>
+    {
+        if filename.to_lowercase().ends_with(".gpl") {
+            let text = std::fs::read_to_string(filename).context(errors::IOError)?;
+            return Palette::from_gpl(&text);
+        }
+
+        let data = std::fs::read(filename).context(errors::IOError)?;
+        return Palette::from_bytes(&data, data.len() / 2);
+    }
+
+    //
+    /// Parse a GIMP palette. Colors named with "transparent" get a clear alpha bit.
+    //
+    pub fn from_gpl (text : &str) -> Result<Palette>
+    {
+        let mut lines = text.lines();
+        if lines.next().map(|l| l.trim()) != Some("GIMP Palette") {
+            return errors::InvalidPalette { reason: "missing 'GIMP Palette' header".to_string() }.fail();
+        }
+
+        let mut colors = Vec::<u16>::new();
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+                continue; // comments, and the headers (color names can have colons too)
+            }
+
+            let mut parts = line.split_whitespace();
+            let mut channel = || -> Result<u8> {
+                return match parts.next().and_then(|p| p.parse::<u8>().ok()) {
+                    Some(v) => Ok(v),
+                    None => errors::InvalidPalette { reason: format!("bad color line '{}'", line) }.fail()
+                };
+            };
+            let (r, g, b) = (channel()?, channel()?, channel()?);
+            let transparent = parts.any(|p| p.eq_ignore_ascii_case("transparent"));
+
+            colors.push(rgba8_to_rgba5551([r, g, b, if transparent { 0 } else { 0xFF }]));
+        }
+
+        if colors.is_empty() {
+            return errors::InvalidPalette { reason: "no colors in the palette".to_string() }.fail();
+        }
+
+        return Ok(Palette::new(colors));
+    }
+
+    //
+    /// Build a palette holding every color in an 8 bit RGBA image (after reducing to RGBA5551).
+    /// Fails if the image needs more than max_colors.
+    //
+    pub fn from_image_exact (rgba : &[u8], max_colors : usize) -> Result<Palette>
+    {
+        let mut colors = Vec::<u16>::new();
+        for pixel in rgba.chunks_exact(4) {
+            let color = rgba8_to_rgba5551([pixel[0], pixel[1], pixel[2], pixel[3]]);
+            if !colors.contains(&color) {
+                colors.push(color);
+                if colors.len() > max_colors {
+                    return errors::TextureEncodeError {
+                        reason: format!("the image has more than {} colors, reduce the colors first", max_colors)
+                    }.fail();
+                }
+            }
+        }
+
+        return Ok(Palette::new(colors));
+    }
+
+    // --- public properties
+
+    //
+    /// The raw RGBA5551 colors
+    //
+    pub fn colors (&self) -> &Vec<u16>
+    { return &self.m_colors; }
+
+    // --- public helpers
+
+    //
+    /// A color expanded to 8 bit RGBA (None if the index is past the end of the palette)
+    //
+    pub fn rgba (&self, index : usize) -> Option<[u8; 4]>
+    {
+        return self.m_colors.get(index).map(|c| rgba5551_to_rgba8(*c));
+    }
+
+    //
+    /// The palette as stored in the model (big endian RGBA5551)
+    //
+    pub fn to_bytes (&self) -> Vec<u8>
+    {
+        return self.m_colors.iter().flat_map(|c| c.to_be_bytes()).collect();
+    }
+
+    //
+    /// Export as a GIMP palette. Transparent colors are marked in their name.
+    //
+    pub fn to_gpl (&self, name : &str) -> String
+    {
+        let mut out = String::new();
+        writeln!(out, "GIMP Palette").unwrap();
+        writeln!(out, "Name: {}", name).unwrap();
+        writeln!(out, "Columns: {}", SWATCH_COLUMNS).unwrap();
+        writeln!(out, "#").unwrap();
+
+        for (i, color) in self.m_colors.iter().enumerate() {
+            let rgba = rgba5551_to_rgba8(*color);
+            let transparent = if rgba[3] == 0 { " transparent" } else { "" };
+            writeln!(out, "{:3} {:3} {:3}\tindex {}{}", rgba[0], rgba[1], rgba[2], i, transparent).unwrap();
+        }
+
+        return out;
+    }
+
+    //
+    /// Export as a png swatch: a 16 pixel square per color, 16 colors per row
+    //
+    pub fn to_swatch_png (&self) -> Vec<u8>
+    {
+        let columns = SWATCH_COLUMNS.min(self.m_colors.len().max(1));
+        let rows = self.m_colors.len().div_ceil(SWATCH_COLUMNS).max(1);
+        let width = columns * SWATCH_CELL_SIZE;
+        let height = rows * SWATCH_CELL_SIZE;
+
+        let mut rgba = vec![0u8; width * height * 4];
+        for (i, color) in self.m_colors.iter().enumerate() {
+            let color = rgba5551_to_rgba8(*color);
+            let cell_x = (i % SWATCH_COLUMNS) * SWATCH_CELL_SIZE;
+            let cell_y = (i / SWATCH_COLUMNS) * SWATCH_CELL_SIZE;
+
+            for y in cell_y .. cell_y + SWATCH_CELL_SIZE {
+                for x in cell_x .. cell_x + SWATCH_CELL_SIZE {
+                    let p = (y * width + x) * 4;
+                    rgba[p .. p + 4].copy_from_slice(&color);
+                }
+            }
+        }
+
+        return png::encode_rgba(width as u32, height as u32, &rgba);
+    }
+}
+
+//
+/// Reduce an 8 bit RGBA color to RGBA5551 (alpha is on if it is at least half)
+//
+pub fn rgba8_to_rgba5551 (color : [u8; 4]) -> u16
+{
+    let r = (color[0] >> 3) as u16;
+    let g = (color[1] >> 3) as u16;
+    let b = (color[2] >> 3) as u16;
+    let a = if color[3] >= 0x80 { 1 } else { 0 };
+    return (r << 11) | (g << 6) | (b << 1) | a;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const RED : u16 = 0xF801;
+    const GREEN : u16 = 0x07C1;
+    const CLEAR : u16 = 0x0000;
+
+    #[test]
+    fn collects_the_exact_colors_of_an_image ()
+    {
+        // red, green, red again, and a transparent pixel whose color is dropped
+        let rgba = [
+            0xFF, 0x00, 0x00, 0xFF,   0x00, 0xFF, 0x00, 0xFF,
+            0xFF, 0x00, 0x00, 0xFF,   0x00, 0x00, 0x00, 0x00
+        ];
+        assert_eq!(Palette::from_image_exact(&rgba, 16).unwrap().colors(), &vec![RED, GREEN, CLEAR]);
+        assert_eq!(Palette::from_image_exact(&rgba, 3).unwrap().colors().len(), 3);
+
+        match Palette::from_image_exact(&rgba, 2) {
+            Err(errors::Error::TextureEncodeError { .. }) => {},
+            other => panic!("expected TextureEncodeError, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn exports_and_imports_gimp_palettes ()
+    {
+        let palette = Palette::new(vec![RED, GREEN, CLEAR]);
+        let gpl = palette.to_gpl("test");
+
+        let lines : Vec<&str> = gpl.lines().collect();
+        assert_eq!(&lines[.. 2], &["GIMP Palette", "Name: test"]);
+        assert_eq!(&lines[4 ..], &[
+            "255   0   0\tindex 0",
+            "  0 255   0\tindex 1",
+            "  0   0   0\tindex 2 transparent"
+        ]);
+
+        assert_eq!(Palette::from_gpl(&gpl).unwrap(), palette);
+
+        // only the headers are skipped, not colors with a colon in their name
+        let named = "GIMP Palette\nName: hot: cold\nColumns: 2\n255 0 0 red: hot\n0 255 0\tgreen\n0 0 0 note: transparent\n";
+        assert_eq!(Palette::from_gpl(named).unwrap(), palette);
+
+        for text in &["255 0 0\n", "GIMP Palette\n255 0\n", "GIMP Palette\n# no colors\n"] {
+            match Palette::from_gpl(text) {
+                Err(errors::Error::InvalidPalette { .. }) => {},
+                other => panic!("expected InvalidPalette for {:?}, got {:?}", text, other.map(|_| ()))
+            }
+        }
+    }
+
+    #[test]
+    fn reads_and_writes_model_bytes ()
+    {
+        let palette = Palette::new(vec![RED, GREEN]);
+        assert_eq!(palette.to_bytes(), vec![0xF8, 0x01, 0x07, 0xC1]);
+        assert_eq!(Palette::from_bytes(&palette.to_bytes(), 2).unwrap(), palette);
+        assert_eq!(palette.rgba(1), Some([0x00, 0xFF, 0x00, 0xFF]));
+        assert_eq!(palette.rgba(2), None);
+
+        match Palette::from_bytes(&palette.to_bytes(), 3) {
+            Err(errors::Error::InvalidPalette { .. }) => {},
+            other => panic!("expected InvalidPalette, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn lays_out_swatches_in_rows_of_16 ()
+    {
+        let palette = Palette::new((0 .. 20).map(|i| (i << 11) | 1).collect());
+        let swatch = png::decode_rgba(&palette.to_swatch_png()).unwrap();
+        assert_eq!((swatch.width, swatch.height), (16 * 16, 2 * 16));
+
+        // color 17 is the second square on the second row, and past color 19 is left empty
+        let pixel = |x : usize, y : usize| -> &[u8] { &swatch.rgba[(y * 256 + x) * 4 .. (y * 256 + x) * 4 + 4] };
+        assert_eq!(pixel(16 + 15, 16), &palette.rgba(17).unwrap()[..]);
+        assert_eq!(pixel(4 * 16, 16), &[0, 0, 0, 0]);
+    }
+}
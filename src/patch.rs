@@ -0,0 +1,578 @@
+use crate::crc32;
+use crate::errors;
+use crate::errors::Result;
+
+// IPS and BPS patches, so edited roms can be shared without the rom itself.
+//
+// IPS:
+// "PATCH" followed by records of [oooooo] [ssss] [data ...] and ending with "EOF"
+// o: offset to write to
+// s: size of the data, 0 for a run length record of [rrrr] [vv] (r copies of v)
+// optionally followed by [tttttt], the size to truncate the output to.
+//
+// BPS:
+// "BPS1" [source size] [target size] [metadata size] [metadata ...] [actions ...]
+// [source crc32] [target crc32] [patch crc32]
+// every number except the crcs is a variable length integer, and each action is
+// [(length - 1) << 2 | mode] with the mode picking where the bytes come from.
+
+const IPS_MAGIC : &[u8] = b"PATCH";
+const IPS_EOF : &[u8] = b"EOF";
+
+//
+/// IPS offsets are 24 bit
+//
+const IPS_MAX_OFFSET : usize = 0xFFFFFF;
+
+//
+/// The size field of an IPS record is 16 bit
+//
+const IPS_MAX_RECORD : usize = 0xFFFF;
+
+//
+/// Runs of the same byte at least this long are written as run length records
+//
+const IPS_MIN_RLE : usize = 9;
+
+const BPS_MAGIC : &[u8] = b"BPS1";
+
+const BPS_SOURCE_READ : u64 = 0;
+const BPS_TARGET_READ : u64 = 1;
+const BPS_SOURCE_COPY : u64 = 2;
+const BPS_TARGET_COPY : u64 = 3;
+
+//
+/// The supported patch formats
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PatchFormat
+{
+    Ips,
+    Bps
+}
+
+impl PatchFormat
+{
+    //
+    /// Work out the format of a patch from its magic
+    //
+    pub fn detect (patch : &[u8]) -> Option<PatchFormat>
+    {
+        if patch.starts_with(IPS_MAGIC) {
+            return Some(PatchFormat::Ips);
+        }
+        if patch.starts_with(BPS_MAGIC) {
+            return Some(PatchFormat::Bps);
+        }
+        return None;
+    }
+
+    //
+    /// Work out the format from a file name (.ips or .bps)
+    //
+    pub fn from_filename (filename : &str) -> Option<PatchFormat>
+    {
+        let lower = filename.to_lowercase();
+        if lower.ends_with(".ips") {
+            return Some(PatchFormat::Ips);
+        }
+        if lower.ends_with(".bps") {
+            return Some(PatchFormat::Bps);
+        }
+        return None;
+    }
+}
+
+//
+/// Create a patch which turns original into modified
+//
+pub fn create (format : PatchFormat, original : &[u8], modified : &[u8]) -> Result<Vec<u8>>
+{
+    return match format {
+        PatchFormat::Ips => create_ips(original, modified),
+        PatchFormat::Bps => Ok(create_bps(original, modified))
+    };
+}
+
+//
+/// Apply a patch (of either format) to original
+//
+pub fn apply (original : &[u8], patch : &[u8]) -> Result<Vec<u8>>
+{
+    return match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(original, patch),
+        Some(PatchFormat::Bps) => apply_bps(original, patch),
+        None => errors::PatchError { reason: "not an ips or bps patch".to_string() }.fail()
+    };
+}
+
+//
+/// Create an IPS patch. Fails if the data is too big for 24 bit offsets.
+//
+pub fn create_ips (original : &[u8], modified : &[u8]) -> Result<Vec<u8>>
+{
+    if modified.len() > IPS_MAX_OFFSET + 1 {
+        return errors::PatchError { reason: format!("{:#X} bytes is too big for an ips patch", modified.len()) }.fail();
+    }
+
+    let mut patch = IPS_MAGIC.to_vec();
+
+    let differs = |i : usize| i >= original.len() || original[i] != modified[i];
+
+    let mut i = 0;
+    while i < modified.len() {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+
+        // an offset of "EOF" would end the patch early, so start a byte sooner
+        let mut start = i;
+        if start == 0x454F46 {
+            start -= 1;
+        }
+
+        // run length records for long runs of the same byte
+        let run = modified[start ..].iter().take(IPS_MAX_RECORD).take_while(|b| **b == modified[start]).count();
+        if run >= IPS_MIN_RLE {
+            p_write_u24(&mut patch, start);
+            patch.extend_from_slice(&[0, 0]);
+            patch.extend_from_slice(&(run as u16).to_be_bytes());
+            patch.push(modified[start]);
+            i = start + run;
+            continue;
+        }
+
+        // otherwise take everything up to a few matching bytes in a row (cheaper than a new record)
+        let mut end = start;
+        let mut same = 0;
+        while end < modified.len() && end - start < IPS_MAX_RECORD {
+            if differs(end) {
+                same = 0;
+            } else {
+                same += 1;
+            }
+            end += 1;
+
+            if same > 5 {
+                break;
+            }
+        }
+        end -= same;
+
+        p_write_u24(&mut patch, start);
+        patch.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        patch.extend_from_slice(&modified[start .. end]);
+        i = end;
+    }
+
+    patch.extend_from_slice(IPS_EOF);
+
+    if modified.len() < original.len() {
+        p_write_u24(&mut patch, modified.len());
+    }
+
+    return Ok(patch);
+}
+
+//
+/// Apply an IPS patch
+//
+pub fn apply_ips (original : &[u8], patch : &[u8]) -> Result<Vec<u8>>
+{
+    if !patch.starts_with(IPS_MAGIC) {
+        return errors::PatchError { reason: "missing PATCH header".to_string() }.fail();
+    }
+
+    let mut out = original.to_vec();
+    let mut pos = IPS_MAGIC.len();
+
+    loop {
+        if patch[pos ..].starts_with(IPS_EOF) {
+            pos += IPS_EOF.len();
+            break;
+        }
+
+        let record = p_take(patch, pos, 5, "ips record")?;
+        let offset = p_read_u24(record);
+        let size = u16::from_be_bytes([record[3], record[4]]) as usize;
+        pos += 5;
+
+        let (length, fill) = if size == 0 {
+            let rle = p_take(patch, pos, 3, "ips run length record")?;
+            pos += 3;
+            (u16::from_be_bytes([rle[0], rle[1]]) as usize, Some(rle[2]))
+        } else {
+            (size, None)
+        };
+
+        if out.len() < offset + length {
+            out.resize(offset + length, 0);
+        }
+
+        match fill {
+            Some(value) => {
+                for b in out[offset .. offset + length].iter_mut() {
+                    *b = value;
+                }
+            },
+            None => {
+                out[offset .. offset + length].copy_from_slice(p_take(patch, pos, length, "ips record data")?);
+                pos += length;
+            }
+        }
+    }
+
+    // truncation extension
+    if patch.len() >= pos + 3 {
+        out.truncate(p_read_u24(&patch[pos .. pos + 3]));
+    }
+
+    return Ok(out);
+}
+
+//
+/// Create a BPS patch. This is a linear patch (source and target read only) which
+/// is all that's needed for edits that dont move data around much.
+//
+pub fn create_bps (original : &[u8], modified : &[u8]) -> Vec<u8>
+{
+    let mut patch = BPS_MAGIC.to_vec();
+    p_write_varint(&mut patch, original.len() as u64);
+    p_write_varint(&mut patch, modified.len() as u64);
+    p_write_varint(&mut patch, 0); // no metadata
+
+    let same = |i : usize| i < original.len() && original[i] == modified[i];
+
+    let mut i = 0;
+    while i < modified.len() {
+        let start = i;
+        let mode = if same(i) { BPS_SOURCE_READ } else { BPS_TARGET_READ };
+        while i < modified.len() && same(i) == (mode == BPS_SOURCE_READ) {
+            i += 1;
+        }
+
+        p_write_varint(&mut patch, ((i - start - 1) as u64) << 2 | mode);
+        if mode == BPS_TARGET_READ {
+            patch.extend_from_slice(&modified[start .. i]);
+        }
+    }
+
+    patch.extend_from_slice(&crc32::crc32(original).to_le_bytes());
+    patch.extend_from_slice(&crc32::crc32(modified).to_le_bytes());
+    let patch_crc = crc32::crc32(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+    return patch;
+}
+
+//
+/// Apply a BPS patch, checking every checksum
+//
+pub fn apply_bps (original : &[u8], patch : &[u8]) -> Result<Vec<u8>>
+{
+    if !patch.starts_with(BPS_MAGIC) || patch.len() < BPS_MAGIC.len() + 12 {
+        return errors::PatchError { reason: "missing BPS1 header".to_string() }.fail();
+    }
+
+    let footer = patch.len() - 12;
+    let source_crc = p_read_u32_le(&patch[footer ..]);
+    let target_crc = p_read_u32_le(&patch[footer + 4 ..]);
+    let patch_crc = p_read_u32_le(&patch[footer + 8 ..]);
+
+    if crc32::crc32(&patch[.. footer + 8]) != patch_crc {
+        return errors::PatchError { reason: "the patch is corrupt (checksum mismatch)".to_string() }.fail();
+    }
+    if crc32::crc32(original) != source_crc {
+        return errors::PatchError { reason: "the patch is for a different file (source checksum mismatch)".to_string() }.fail();
+    }
+
+    let mut pos = BPS_MAGIC.len();
+    let source_size = p_read_varint(patch, &mut pos, footer)? as usize;
+    let target_size = p_read_varint(patch, &mut pos, footer)? as usize;
+    let metadata_size = p_read_varint(patch, &mut pos, footer)? as usize;
+    pos += metadata_size;
+
+    if source_size != original.len() {
+        return errors::PatchError { reason: format!("the patch expects {:#X} bytes, got {:#X}", source_size, original.len()) }.fail();
+    }
+
+    let mut out = Vec::<u8>::with_capacity(target_size);
+    let mut source_relative = 0i64;
+    let mut target_relative = 0i64;
+
+    while pos < footer {
+        let action = p_read_varint(patch, &mut pos, footer)?;
+        let length = ((action >> 2) + 1) as usize;
+
+        match action & 3 {
+            BPS_SOURCE_READ => {
+                let start = out.len();
+                out.extend_from_slice(p_take(original, start, length, "bps source read")?);
+            },
+            BPS_TARGET_READ => {
+                out.extend_from_slice(p_take(patch, pos, length, "bps target read")?);
+                pos += length;
+            },
+            BPS_SOURCE_COPY => {
+                source_relative += p_read_signed_varint(patch, &mut pos, footer)?;
+                if source_relative < 0 {
+                    return errors::PatchError { reason: "bps source copy before the start".to_string() }.fail();
+                }
+                out.extend_from_slice(p_take(original, source_relative as usize, length, "bps source copy")?);
+                source_relative += length as i64;
+            },
+            BPS_TARGET_COPY => {
+                target_relative += p_read_signed_varint(patch, &mut pos, footer)?;
+                if target_relative < 0 || target_relative as usize >= out.len() {
+                    return errors::PatchError { reason: "bps target copy outside the output".to_string() }.fail();
+                }
+                // byte by byte since the copy can overlap itself
+                for _ in 0 .. length {
+                    let b = out[target_relative as usize];
+                    out.push(b);
+                    target_relative += 1;
+                }
+            },
+            _ => {
+                return errors::PatchError { reason: format!("unknown bps action {}", action & 3) }.fail();
+            }
+        }
+    }
+
+    if out.len() != target_size || crc32::crc32(&out) != target_crc {
+        return errors::PatchError { reason: "the patched output does not match (target checksum mismatch)".to_string() }.fail();
+    }
+
+    return Ok(out);
+}
+
+// --- private
+
+fn p_take<'a> (data : &'a [u8], offset : usize, length : usize, what : &str) -> Result<&'a [u8]>
+{
+    if offset + length > data.len() {
+        return errors::PatchError { reason: format!("{} at {:#X} runs past the end", what, offset) }.fail();
+    }
+    return Ok(&data[offset .. offset + length]);
+}
+
+fn p_write_u24 (out : &mut Vec<u8>, value : usize)
+{
+    out.extend_from_slice(&(value as u32).to_be_bytes()[1..]);
+}
+
+fn p_read_u24 (data : &[u8]) -> usize
+{
+    return (data[0] as usize) << 16 | (data[1] as usize) << 8 | data[2] as usize;
+}
+
+fn p_read_u32_le (data : &[u8]) -> u32
+{
+    return u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+}
+
+//
+/// BPS variable length integer: 7 bits at a time, the top bit marks the last byte.
+/// Each continuation also adds one so there is only one encoding for each number.
+//
+fn p_write_varint (out : &mut Vec<u8>, mut value : u64)
+{
+    loop {
+        let x = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | x);
+            return;
+        }
+        out.push(x);
+        value -= 1;
+    }
+}
+
+fn p_read_varint (data : &[u8], pos : &mut usize, end : usize) -> Result<u64>
+{
+    let mut value = 0u64;
+    let mut shift = 1u64;
+    loop {
+        if *pos >= end || shift > (1 << 56) {
+            return errors::PatchError { reason: "bad number in bps patch".to_string() }.fail();
+        }
+        let x = data[*pos];
+        *pos += 1;
+
+        value += (x & 0x7F) as u64 * shift;
+        if x & 0x80 != 0 {
+            return Ok(value);
+        }
+        shift <<= 7;
+        value += shift;
+    }
+}
+
+fn p_read_signed_varint (data : &[u8], pos : &mut usize, end : usize) -> Result<i64>
+{
+    let value = p_read_varint(data, pos, end)?;
+    let magnitude = (value >> 1) as i64;
+    return Ok(if value & 1 != 0 { -magnitude } else { magnitude });
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //
+    /// A buffer with a recognizable pattern
+    //
+    fn p_pattern (length : usize) -> Vec<u8>
+    {
+        return (0 .. length).map(|i| (i * 7 + i / 251) as u8).collect();
+    }
+
+    fn p_round_trip (original : &[u8], modified : &[u8])
+    {
+        for format in [PatchFormat::Ips, PatchFormat::Bps] {
+            let patch = create(format, original, modified).unwrap();
+            assert_eq!(PatchFormat::detect(&patch), Some(format));
+            assert_eq!(apply(original, &patch).unwrap(), modified, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn round_trips_small_edits ()
+    {
+        let original = p_pattern(0x1000);
+        let mut modified = original.clone();
+        modified[0] ^= 0xFF;
+        modified[0x123] = 0x55;
+        modified[0x800 .. 0x810].copy_from_slice(&[0xAA; 0x10]);
+        modified[0xFFF] ^= 1;
+        p_round_trip(&original, &modified);
+    }
+
+    #[test]
+    fn round_trips_identical_and_empty ()
+    {
+        let original = p_pattern(0x100);
+        p_round_trip(&original, &original);
+        p_round_trip(&[], &original);
+        p_round_trip(&original, &[]);
+    }
+
+    #[test]
+    fn round_trips_growing_and_shrinking ()
+    {
+        let original = p_pattern(0x400);
+
+        let mut grown = original.clone();
+        grown.extend_from_slice(&p_pattern(0x300)[0x10 ..]);
+        p_round_trip(&original, &grown);
+
+        let mut shrunk = original[.. 0x280].to_vec();
+        shrunk[0x10] ^= 0xFF;
+        p_round_trip(&original, &shrunk);
+
+        // the ips truncation extension is what makes the output shorter
+        let patch = create_ips(&original, &shrunk).unwrap();
+        assert_eq!(&patch[patch.len() - 3 ..], &[0x00, 0x02, 0x80]);
+    }
+
+    #[test]
+    fn ips_avoids_an_eof_offset ()
+    {
+        let original = vec![0u8; 0x454F50];
+        let mut modified = original.clone();
+        modified[0x454F46] = 1;
+        modified[0x454F47] = 2;
+
+        let patch = create_ips(&original, &modified).unwrap();
+        // the only "EOF" is the real end of the patch
+        let eof_count = patch.windows(3).filter(|w| *w == IPS_EOF).count();
+        assert_eq!(eof_count, 1);
+        assert!(patch.ends_with(IPS_EOF));
+        assert_eq!(apply_ips(&original, &patch).unwrap(), modified);
+    }
+
+    #[test]
+    fn ips_uses_run_length_records ()
+    {
+        let original = p_pattern(0x2000);
+        let mut modified = original.clone();
+        modified[0x100 .. 0x1100].copy_from_slice(&[0x42; 0x1000]);
+
+        let patch = create_ips(&original, &modified).unwrap();
+        assert!(patch.len() < 0x40, "patch is {:#X} bytes", patch.len());
+        // PATCH, then [offset] [0000] [run] [value]
+        assert_eq!(&patch[5 .. 13], &[0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x42]);
+        assert_eq!(apply_ips(&original, &patch).unwrap(), modified);
+    }
+
+    #[test]
+    fn rejects_truncated_patches ()
+    {
+        let original = p_pattern(0x200);
+        let mut modified = original.clone();
+        modified[0x20 .. 0x40].copy_from_slice(&[1; 0x20]);
+        modified[0x100] = 0;
+
+        let ips = create_ips(&original, &modified).unwrap();
+        assert!(apply_ips(&original, &ips[.. ips.len() - 8]).is_err());
+        assert!(apply_ips(&original, &ips[.. 7]).is_err());
+
+        let bps = create_bps(&original, &modified);
+        assert!(apply_bps(&original, &bps[.. bps.len() - 4]).is_err());
+        assert!(apply_bps(&original, &bps[.. 6]).is_err());
+    }
+
+    #[test]
+    fn bps_checks_every_crc ()
+    {
+        let original = p_pattern(0x200);
+        let mut modified = original.clone();
+        modified[0x80] ^= 0x80;
+        let patch = create_bps(&original, &modified);
+        let footer = patch.len() - 12;
+
+        // a different source
+        let mut other = original.clone();
+        other[0] ^= 1;
+        assert!(apply_bps(&other, &patch).is_err());
+
+        // a wrong target crc (with the patch crc fixed up so only the target check fails)
+        let mut bad_target = patch.clone();
+        bad_target[footer + 4] ^= 1;
+        let patch_crc = crc32::crc32(&bad_target[.. footer + 8]);
+        bad_target[footer + 8 ..].copy_from_slice(&patch_crc.to_le_bytes());
+        assert!(apply_bps(&original, &bad_target).is_err());
+
+        // a corrupt patch
+        let mut corrupt = patch.clone();
+        corrupt[BPS_MAGIC.len() + 4] ^= 1;
+        assert!(apply_bps(&original, &corrupt).is_err());
+
+        assert_eq!(apply_bps(&original, &patch).unwrap(), modified);
+    }
+
+    #[test]
+    fn bps_target_copy_repeats_output ()
+    {
+        // hand made: write "ab" then copy 4 bytes from the start of the output -> "ababab"
+        let original = Vec::<u8>::new();
+        let target = b"ababab".to_vec();
+
+        let mut patch = BPS_MAGIC.to_vec();
+        p_write_varint(&mut patch, 0);
+        p_write_varint(&mut patch, target.len() as u64);
+        p_write_varint(&mut patch, 0);
+        p_write_varint(&mut patch, 1 << 2 | BPS_TARGET_READ);
+        patch.extend_from_slice(b"ab");
+        p_write_varint(&mut patch, 3 << 2 | BPS_TARGET_COPY);
+        p_write_varint(&mut patch, 0); // relative offset +0
+        patch.extend_from_slice(&crc32::crc32(&original).to_le_bytes());
+        patch.extend_from_slice(&crc32::crc32(&target).to_le_bytes());
+        let patch_crc = crc32::crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+
+        assert_eq!(apply_bps(&original, &patch).unwrap(), target);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::crc32;
+use crate::deflate;
+use crate::errors;
+use crate::errors::Result;
+use crate::inflate;
+
+// Minimal png support. Encoding is 8 bit RGBA only, which is all we need for textures and renders.
+// Decoding handles every non interlaced color type, converting to 8 bit RGBA.
+
+/// The png file signature
+pub const PNG_SIGNATURE : [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+//
+/// Encode an 8 bit RGBA image (width * height * 4 bytes, row major) as a png
+//
+pub fn encode_rgba (width: u32, height: u32, rgba: &[u8]) -> Vec<u8>
+{
+    let mut out = Vec::<u8>::new();
+    out.extend_from_slice(&PNG_SIGNATURE);
+
+    // IHDR: width, height, bit depth 8, color type 6 (RGBA), default compression/filter/interlace
+    let mut ihdr = Vec::<u8>::new();
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+    p_write_chunk(&mut out, b"IHDR", &ihdr);
+
+    // every row gets filter type 0 (none)
+    let row_size = width as usize * 4;
+    let mut raw = Vec::<u8>::with_capacity((row_size + 1) * height as usize);
+    for row in rgba.chunks(row_size).take(height as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    p_write_chunk(&mut out, b"IDAT", &p_zlib_wrap(&raw));
+    p_write_chunk(&mut out, b"IEND", &[]);
+
+    return out;
+}
+
+//
+/// A decoded png: 8 bit RGBA (width * height * 4 bytes, row major)
+//
+pub struct DecodedPng
+{
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>
+}
+
+//
+/// Decode a png to 8 bit RGBA
+//
+pub fn decode_rgba (data: &[u8]) -> Result<DecodedPng>
+{
+    if data.len() < PNG_SIGNATURE.len() || data[0..8] != PNG_SIGNATURE {
+        return errors::InvalidPng { reason: "missing png signature".to_string() }.fail();
+    }
+
+    let mut header : Option<[u8; 13]> = None;
+    let mut palette = Vec::<[u8; 4]>::new();
+    let mut transparency = Vec::<u8>::new();
+    let mut compressed = Vec::<u8>::new();
+
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes([data[pos], data[pos+1], data[pos+2], data[pos+3]]) as usize;
+        let chunk_type = &data[pos+4 .. pos+8];
+        if pos + 12 + length > data.len() {
+            return errors::InvalidPng { reason: "chunk runs past the end of the file".to_string() }.fail();
+        }
+        let chunk = &data[pos+8 .. pos+8+length];
+        pos += 12 + length;
+
+        match chunk_type {
+            b"IHDR" if length == 13 => {
+                let mut h = [0u8; 13];
+                h.copy_from_slice(chunk);
+                header = Some(h);
+            },
+            b"PLTE" => palette = chunk.chunks_exact(3).map(|c| [c[0], c[1], c[2], 0xFF]).collect(),
+            b"tRNS" => transparency = chunk.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = match header {
+        Some(h) => h,
+        None => return errors::InvalidPng { reason: "missing IHDR".to_string() }.fail()
+    };
+
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let bit_depth = header[8] as usize;
+    let color_type = header[9];
+    if header[12] != 0 {
+        return errors::InvalidPng { reason: "interlaced pngs are not supported".to_string() }.fail();
+    }
+
+    let channels = match color_type {
+        0 => 1, // gray
+        2 => 3, // rgb
+        3 => 1, // palette
+        4 => 2, // gray + alpha
+        6 => 4, // rgba
+        _ => return errors::InvalidPng { reason: format!("unknown color type {}", color_type) }.fail()
+    };
+    if ![1, 2, 4, 8, 16].contains(&bit_depth) {
+        return errors::InvalidPng { reason: format!("unsupported bit depth {}", bit_depth) }.fail();
+    }
+
+    // zlib: skip the 2 byte header, the adler32 at the end isnt checked
+    if compressed.len() < 2 {
+        return errors::InvalidPng { reason: "missing image data".to_string() }.fail();
+    }
+    let (raw, _) = inflate::inflate(&compressed[2..])?;
+
+    let bits_per_pixel = channels * bit_depth;
+    let row_size = (width as usize * bits_per_pixel).div_ceil(8);
+    let filter_step = bits_per_pixel.div_ceil(8);
+    let pixels = p_unfilter(&raw, row_size, height as usize, filter_step)?;
+
+    // read a sample (channel value) as 8 bits
+    let sample = |row : &[u8], index : usize| -> u8 {
+        return match bit_depth {
+            16 => row[index * 2],
+            8 => row[index],
+            _ => {
+                let bit = index * bit_depth;
+                let v = (row[bit / 8] >> (8 - bit_depth - bit % 8)) & ((1 << bit_depth) - 1) as u8;
+                (v as u32 * 255 / ((1u32 << bit_depth) - 1)) as u8
+            }
+        };
+    };
+    // the raw sample, for palette indices and tRNS matching
+    let raw_sample = |row : &[u8], index : usize| -> u16 {
+        return match bit_depth {
+            16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+            8 => row[index] as u16,
+            _ => {
+                let bit = index * bit_depth;
+                ((row[bit / 8] >> (8 - bit_depth - bit % 8)) & ((1 << bit_depth) - 1) as u8) as u16
+            }
+        };
+    };
+    let transparent_key = |channel : usize| -> Option<u16> {
+        return transparency.get(channel * 2 .. channel * 2 + 2).map(|t| u16::from_be_bytes([t[0], t[1]]));
+    };
+
+    let mut rgba = Vec::<u8>::with_capacity(width as usize * height as usize * 4);
+    for row in pixels.chunks(row_size) {
+        for x in 0 .. width as usize {
+            let color = match color_type {
+                0 => {
+                    let v = sample(row, x);
+                    let alpha = if transparent_key(0) == Some(raw_sample(row, x)) { 0 } else { 0xFF };
+                    [v, v, v, alpha]
+                },
+                2 => {
+                    let keyed = (0 .. 3).all(|c| transparent_key(c) == Some(raw_sample(row, x * 3 + c)));
+                    [sample(row, x * 3), sample(row, x * 3 + 1), sample(row, x * 3 + 2), if keyed { 0 } else { 0xFF }]
+                },
+                3 => {
+                    let index = raw_sample(row, x) as usize;
+                    let mut color = match palette.get(index) {
+                        Some(c) => *c,
+                        None => return errors::InvalidPng { reason: format!("palette index {} out of range", index) }.fail()
+                    };
+                    if let Some(alpha) = transparency.get(index) {
+                        color[3] = *alpha;
+                    }
+                    color
+                },
+                4 => {
+                    let v = sample(row, x * 2);
+                    [v, v, v, sample(row, x * 2 + 1)]
+                },
+                _ => [sample(row, x * 4), sample(row, x * 4 + 1), sample(row, x * 4 + 2), sample(row, x * 4 + 3)]
+            };
+            rgba.extend_from_slice(&color);
+        }
+    }
+
+    return Ok(DecodedPng { width, height, rgba });
+}
+
+//
+/// Undo the per row filters, returning the rows without their filter bytes
+//
+fn p_unfilter (raw: &[u8], row_size: usize, height: usize, step: usize) -> Result<Vec<u8>>
+{
+    if raw.len() < (row_size + 1) * height {
+        return errors::InvalidPng { reason: "not enough image data".to_string() }.fail();
+    }
+
+    let mut out = vec![0u8; row_size * height];
+    for y in 0 .. height {
+        let filter = raw[y * (row_size + 1)];
+        let source = &raw[y * (row_size + 1) + 1 .. (y + 1) * (row_size + 1)];
+
+        for x in 0 .. row_size {
+            let left = if x >= step { out[y * row_size + x - step] } else { 0 };
+            let up = if y > 0 { out[(y - 1) * row_size + x] } else { 0 };
+            let up_left = if x >= step && y > 0 { out[(y - 1) * row_size + x - step] } else { 0 };
+
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => p_paeth(left, up, up_left),
+                _ => return errors::InvalidPng { reason: format!("unknown filter {}", filter) }.fail()
+            };
+            out[y * row_size + x] = source[x].wrapping_add(predicted);
+        }
+    }
+
+    return Ok(out);
+}
+
+fn p_paeth (a: u8, b: u8, c: u8) -> u8
+{
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        return a;
+    }
+    if pb <= pc {
+        return b;
+    }
+    return c;
+}
+
+//
+/// Wrap data in a zlib stream (header + deflate + adler32).
+/// The header is deflate with a 32K window at the default level, which 6 is.
+//
+fn p_zlib_wrap (data: &[u8]) -> Vec<u8>
+{
+    let mut out = vec![0x78, 0x9C];
+    out.extend_from_slice(&deflate::deflate(data, 6));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    return out;
+}
+
+//
+/// The adler32 checksum zlib streams end with
+//
+pub fn adler32 (data: &[u8]) -> u32
+{
+    let mut a : u32 = 1;
+    let mut b : u32 = 0;
+    for chunk in data.chunks(5552) {
+        for v in chunk {
+            a += *v as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    return (b << 16) | a;
+}
+
+fn p_write_chunk (out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8])
+{
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = out.len();
+    out.extend_from_slice(chunk_type);
+    out.extend_from_slice(data);
+    let crc = crc32::crc32(&out[start..]);
+
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn writes_a_truthful_zlib_header ()
+    {
+        let wrapped = p_zlib_wrap(b"banjo kazooie banjo kazooie");
+
+        // deflate with a 32K window, the check bits, no dictionary and FLEVEL 2 (default) for level 6
+        let header = u16::from_be_bytes([wrapped[0], wrapped[1]]);
+        assert_eq!(wrapped[0], 0x78);
+        assert_eq!(header % 31, 0);
+        assert_eq!(wrapped[1] & 0x20, 0);
+        assert_eq!(wrapped[1] >> 6, 2);
+
+        let (inflated, used) = inflate::inflate(&wrapped[2..]).unwrap();
+        assert_eq!(inflated, b"banjo kazooie banjo kazooie".to_vec());
+        assert_eq!(wrapped[2 + used ..], adler32(b"banjo kazooie banjo kazooie").to_be_bytes());
+    }
+
+    // The pngs in testdata/png were made outside the crate (zlib and the filters from the png spec),
+    // so they dont depend on encode_rgba. Each test lists the pixels it was made from.
+
+    fn p_fixture (name : &str) -> DecodedPng
+    {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata").join("png").join(name);
+        return decode_rgba(&std::fs::read(&path).unwrap()).unwrap();
+    }
+
+    fn p_assert_pixels (name : &str, width : u32, height : u32, pixels : &[[u8; 4]])
+    {
+        let png = p_fixture(name);
+        assert_eq!((png.width, png.height), (width, height), "{}", name);
+        let got : Vec<[u8; 4]> = png.rgba.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]).collect();
+        assert_eq!(got, pixels.to_vec(), "{}", name);
+    }
+
+    #[test]
+    fn round_trips_its_own_output ()
+    {
+        let rgba : Vec<u8> = (0 .. 3 * 2 * 4).map(|i| (i * 11) as u8).collect();
+        let png = decode_rgba(&encode_rgba(3, 2, &rgba)).unwrap();
+        assert_eq!((png.width, png.height, png.rgba), (3, 2, rgba));
+    }
+
+    #[test]
+    fn undoes_every_filter ()
+    {
+        // rgb, one row per filter: none, sub, up, average, paeth. The green of the last row's second
+        // pixel is a paeth tie between up (2) and up left (80), which goes to up
+        p_assert_pixels("filters.png", 3, 5, &[
+            [10, 20, 30, 0xFF], [200, 100, 50, 0xFF], [255, 0, 128, 0xFF],
+            [0, 0, 0, 0xFF], [255, 255, 255, 0xFF], [30, 60, 90, 0xFF],
+            [40, 50, 60, 0xFF], [250, 5, 100, 0xFF], [128, 128, 128, 0xFF],
+            [90, 80, 70, 0xFF], [1, 2, 3, 0xFF], [240, 230, 220, 0xFF],
+            [200, 119, 90, 0xFF], [15, 160, 35, 0xFF], [100, 101, 102, 0xFF]
+        ]);
+    }
+
+    #[test]
+    fn reads_palettes_with_transparency ()
+    {
+        // 2 bit indices 0 1 2 3 1, with tRNS for the first two colors only
+        p_assert_pixels("palette_2bit.png", 5, 1, &[
+            [0xFF, 0x00, 0x00, 0x00], [0x00, 0xFF, 0x00, 0x80], [0x00, 0x00, 0xFF, 0xFF],
+            [0xFF, 0xFF, 0x00, 0xFF], [0x00, 0xFF, 0x00, 0x80]
+        ]);
+    }
+
+    #[test]
+    fn reads_grayscale ()
+    {
+        // 4 bit, odd width so the rows end half way through a byte, second row up filtered. 5 is transparent
+        p_assert_pixels("gray_4bit.png", 3, 2, &[
+            [0x00, 0x00, 0x00, 0xFF], [0x55, 0x55, 0x55, 0x00], [0xFF, 0xFF, 0xFF, 0xFF],
+            [0xAA, 0xAA, 0xAA, 0xFF], [0x11, 0x11, 0x11, 0xFF], [0x55, 0x55, 0x55, 0x00]
+        ]);
+
+        // 1 bit: 1011000110
+        let bits = [1, 0, 1, 1, 0, 0, 0, 1, 1, 0];
+        let pixels : Vec<[u8; 4]> = bits.iter().map(|b| { let v = if *b == 1 { 0xFF } else { 0x00 }; [v, v, v, 0xFF] }).collect();
+        p_assert_pixels("gray_1bit.png", 10, 1, &pixels);
+    }
+
+    #[test]
+    fn reads_16_bit_samples ()
+    {
+        // gray + alpha, sub then paeth filtered (the filters step over whole 4 byte pixels)
+        p_assert_pixels("gray_alpha_16bit.png", 2, 2, &[
+            [0x12, 0x12, 0x12, 0xFF], [0xAB, 0xAB, 0xAB, 0x80],
+            [0x00, 0x00, 0x00, 0x00], [0xFF, 0xFF, 0xFF, 0x7F]
+        ]);
+
+        // rgb, average filtered, with tRNS matching all 16 bits of the second pixel
+        p_assert_pixels("rgb_16bit.png", 2, 1, &[[0x10, 0x20, 0x30, 0xFF], [0xFF, 0x00, 0x80, 0x00]]);
+    }
+
+    #[test]
+    fn joins_image_data_chunks ()
+    {
+        // rgba split over 3 IDAT chunks, the first holding only the zlib header
+        let mut pixels = Vec::<[u8; 4]>::new();
+        for y in 0 .. 4u32 {
+            for x in 0 .. 4u32 {
+                pixels.push([(x * 60) as u8, (y * 70) as u8, (x * y * 20) as u8, (255 - x * 10 - y * 40) as u8]);
+            }
+        }
+        p_assert_pixels("multi_idat.png", 4, 4, &pixels);
+    }
+
+    #[test]
+    fn rejects_what_it_cant_read ()
+    {
+        let valid = encode_rgba(1, 1, &[0, 0, 0, 0xFF]);
+
+        let mut signature = valid.clone();
+        signature[1] = b'Q';
+
+        // IHDR starts at 8, so the data is at 16 and the interlace method is its last byte
+        let mut interlaced = valid.clone();
+        interlaced[16 + 12] = 1;
+
+        let mut color_type = valid.clone();
+        color_type[16 + 9] = 5;
+
+        let truncated = valid[.. valid.len() - 20].to_vec();
+
+        for (name, data) in &[("signature", signature), ("interlaced", interlaced), ("color type", color_type), ("truncated", truncated)] {
+            match decode_rgba(data) {
+                Err(errors::Error::InvalidPng { .. }) => {},
+                other => panic!("expected InvalidPng for {}, got {:?}", name, other.map(|_| ()))
+            }
+        }
+    }
+
+}
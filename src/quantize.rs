@@ -0,0 +1,462 @@
+use crate::palette;
+use crate::palette::Palette;
+use crate::texture::rgba5551_to_rgba8;
+
+// Color quantization for CI textures: pick a palette of up to 16 (CI4) or 256 (CI8)
+// RGBA5551 colors for a true color image, and map every pixel onto it.
+//
+// Palette entries only have 1 bit of alpha, so transparent pixels (alpha < 0x80) all share
+// a single transparent entry and the rest of the palette is picked from the opaque pixels
+// with median cut.
+
+//
+/// 4x4 bayer matrix for ordered dithering (values 0-15)
+//
+const BAYER_4X4 : [[i32; 4]; 4] = [
+    [ 0,  8,  2, 10],
+    [12,  4, 14,  6],
+    [ 3, 11,  1,  9],
+    [15,  7, 13,  5]
+];
+
+//
+/// How to spread the error when a pixel doesnt exactly match a palette color
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dither
+{
+    //
+    /// Use the nearest color
+    //
+    None,
+
+    //
+    /// Offset each pixel by a 4x4 bayer pattern before picking the nearest color
+    //
+    Ordered,
+
+    //
+    /// Push the error onto the neighbouring pixels (Floyd-Steinberg)
+    //
+    FloydSteinberg
+}
+
+impl Dither
+{
+    //
+    /// Parse a dither name from the command line (none, ordered, floyd-steinberg)
+    //
+    pub fn from_name (name : &str) -> Option<Dither>
+    {
+        return match name.to_lowercase().as_str() {
+            "none" => Some(Dither::None),
+            "ordered" | "bayer" => Some(Dither::Ordered),
+            "floyd-steinberg" | "fs" => Some(Dither::FloydSteinberg),
+            _ => None
+        };
+    }
+}
+
+//
+/// How far the quantized image is from the original (8 bit RGB, opaque pixels only)
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct QuantizationError
+{
+    //
+    /// Mean squared error per channel
+    //
+    pub mean_squared: f64,
+
+    //
+    /// Peak signal to noise ratio in dB (infinite if the image is exact)
+    //
+    pub psnr: f64,
+
+    //
+    /// The largest squared distance of any pixel
+    //
+    pub max_squared: u32,
+
+    //
+    /// Pixels whose alpha changed (their alpha wasnt 0 or 0xFF)
+    //
+    pub alpha_changed: usize
+}
+
+//
+/// The result of quantizing an image
+//
+pub struct Quantized
+{
+    pub palette: Palette,
+
+    //
+    /// One palette index per pixel
+    //
+    pub indices: Vec<u8>,
+
+    pub error: QuantizationError
+}
+
+//
+/// Quantize an 8 bit RGBA image (width * height * 4 bytes) down to max_colors palette entries
+//
+pub fn quantize (rgba : &[u8], width : usize, height : usize, max_colors : usize, dither : Dither) -> Quantized
+{
+    let palette = generate_palette(rgba, max_colors);
+    let indices = map_to_palette(rgba, width, height, &palette, dither);
+    let error = measure_error(rgba, &palette, &indices);
+
+    return Quantized { palette, indices, error };
+}
+
+//
+/// Pick a palette of up to max_colors for the image with median cut.
+/// If the image already fits, every color is kept exactly.
+//
+pub fn generate_palette (rgba : &[u8], max_colors : usize) -> Palette
+{
+    if let Ok(exact) = Palette::from_image_exact(rgba, max_colors) {
+        return exact;
+    }
+
+    let has_transparent = rgba.chunks_exact(4).any(|p| p[3] < 0x80);
+    let opaque_slots = if has_transparent { max_colors - 1 } else { max_colors };
+
+    // histogram of the opaque colors, reduced to 5 bits per channel
+    let mut histogram = std::collections::HashMap::<[u8; 3], u32>::new();
+    for p in rgba.chunks_exact(4).filter(|p| p[3] >= 0x80) {
+        *histogram.entry([p[0] >> 3, p[1] >> 3, p[2] >> 3]).or_insert(0) += 1;
+    }
+    let mut colors : Vec<([u8; 3], u32)> = histogram.into_iter().collect();
+    colors.sort(); // keep the output stable
+
+    let mut boxes = vec![ColorBox::new(colors)];
+    while boxes.len() < opaque_slots {
+        // split the box with the most spread (weighted by how many pixels it covers)
+        let candidate = boxes.iter().enumerate()
+            .filter(|(_, b)| b.m_colors.len() > 1)
+            .max_by_key(|(_, b)| b.range().1 as u64 * b.m_count as u64)
+            .map(|(i, _)| i);
+
+        let index = match candidate {
+            Some(i) => i,
+            None => break // every box is a single color
+        };
+
+        let (low, high) = boxes.swap_remove(index).split();
+        boxes.push(low);
+        boxes.push(high);
+    }
+
+    let mut colors : Vec<u16> = boxes.iter().map(|b| b.average()).collect();
+    if has_transparent {
+        colors.insert(0, 0x0000);
+    }
+
+    return Palette::new(colors);
+}
+
+//
+/// Map every pixel onto the palette, returning one index per pixel
+//
+pub fn map_to_palette (rgba : &[u8], width : usize, height : usize, palette : &Palette, dither : Dither) -> Vec<u8>
+{
+    let entries : Vec<[u8; 4]> = palette.colors().iter().map(|c| rgba5551_to_rgba8(*c)).collect();
+    let transparent = entries.iter().position(|c| c[3] == 0).unwrap_or(0);
+
+    // ordered dithering spreads by about the distance between palette colors
+    let spread = 255.0 / (entries.len().max(2) as f64).cbrt();
+
+    // floyd-steinberg error for this row and the next
+    let mut error_current = vec![[0i32; 3]; width + 2];
+    let mut error_next = vec![[0i32; 3]; width + 2];
+
+    let mut indices = Vec::<u8>::with_capacity(width * height);
+    for y in 0 .. height {
+        for x in 0 .. width {
+            let p = &rgba[(y * width + x) * 4 .. (y * width + x) * 4 + 4];
+            if p[3] < 0x80 {
+                indices.push(transparent as u8);
+                continue;
+            }
+
+            let mut target = [p[0] as i32, p[1] as i32, p[2] as i32];
+            match dither {
+                Dither::None => {},
+                Dither::Ordered => {
+                    let offset = ((BAYER_4X4[y % 4][x % 4] as f64 + 0.5) / 16.0 - 0.5) * spread;
+                    for c in target.iter_mut() {
+                        *c += offset as i32;
+                    }
+                },
+                Dither::FloydSteinberg => {
+                    for (c, e) in target.iter_mut().zip(error_current[x + 1].iter()) {
+                        *c += e / 16;
+                    }
+                }
+            }
+            for c in target.iter_mut() {
+                *c = (*c).clamp(0, 255);
+            }
+
+            let index = p_nearest(&entries, target);
+            indices.push(index as u8);
+
+            if dither == Dither::FloydSteinberg {
+                let chosen = entries[index];
+                for c in 0 .. 3 {
+                    let e = target[c] - chosen[c] as i32;
+                    error_current[x + 2][c] += e * 7;
+                    error_next[x][c] += e * 3;
+                    error_next[x + 1][c] += e * 5;
+                    error_next[x + 2][c] += e;
+                }
+            }
+        }
+
+        std::mem::swap(&mut error_current, &mut error_next);
+        for e in error_next.iter_mut() {
+            *e = [0; 3];
+        }
+    }
+
+    return indices;
+}
+
+//
+/// Measure how far the quantized image is from the original
+//
+pub fn measure_error (rgba : &[u8], palette : &Palette, indices : &[u8]) -> QuantizationError
+{
+    let mut total : u64 = 0;
+    let mut max_squared : u32 = 0;
+    let mut opaque : u64 = 0;
+    let mut alpha_changed = 0;
+
+    for (p, index) in rgba.chunks_exact(4).zip(indices.iter()) {
+        let color = palette.rgba(*index as usize).unwrap_or([0; 4]);
+        if color[3] != p[3] {
+            alpha_changed += 1;
+        }
+        if p[3] < 0x80 {
+            continue;
+        }
+
+        let squared : u32 = (0 .. 3).map(|c| {
+            let d = p[c] as i32 - color[c] as i32;
+            (d * d) as u32
+        }).sum();
+
+        total += squared as u64;
+        max_squared = max_squared.max(squared);
+        opaque += 1;
+    }
+
+    let mean_squared = if opaque == 0 { 0.0 } else { total as f64 / (opaque as f64 * 3.0) };
+    let psnr = if mean_squared == 0.0 { f64::INFINITY } else { 10.0 * (255.0 * 255.0 / mean_squared).log10() };
+
+    return QuantizationError { mean_squared, psnr, max_squared, alpha_changed };
+}
+
+//
+/// The closest opaque palette entry to a color
+//
+fn p_nearest (entries : &[[u8; 4]], target : [i32; 3]) -> usize
+{
+    let mut best = 0;
+    let mut best_distance = i32::MAX;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry[3] == 0 {
+            continue;
+        }
+
+        let distance : i32 = (0 .. 3).map(|c| (entry[c] as i32 - target[c]).pow(2)).sum();
+        if distance < best_distance {
+            best = i;
+            best_distance = distance;
+        }
+    }
+    return best;
+}
+
+//
+/// A box of colors (5 bits per channel) for median cut
+//
+struct ColorBox
+{
+    m_colors: Vec<([u8; 3], u32)>,
+    m_count: u32
+}
+
+impl ColorBox
+{
+    fn new (colors : Vec<([u8; 3], u32)>) -> ColorBox
+    {
+        let count = colors.iter().map(|(_, n)| *n).sum();
+        return ColorBox { m_colors: colors, m_count: count };
+    }
+
+    //
+    /// The channel with the largest spread, and how big the spread is
+    //
+    fn range (&self) -> (usize, u8)
+    {
+        let mut widest = (0, 0);
+        for c in 0 .. 3 {
+            let min = self.m_colors.iter().map(|(color, _)| color[c]).min().unwrap_or(0);
+            let max = self.m_colors.iter().map(|(color, _)| color[c]).max().unwrap_or(0);
+            if max - min > widest.1 {
+                widest = (c, max - min);
+            }
+        }
+        return widest;
+    }
+
+    //
+    /// Split along the widest channel at the median pixel
+    //
+    fn split (mut self) -> (ColorBox, ColorBox)
+    {
+        let (channel, _) = self.range();
+        self.m_colors.sort_by_key(|(color, _)| color[channel]);
+
+        let mut seen = 0;
+        let mut split = 1;
+        for (i, (_, n)) in self.m_colors.iter().enumerate() {
+            seen += n;
+            if seen * 2 >= self.m_count {
+                split = (i + 1).clamp(1, self.m_colors.len() - 1);
+                break;
+            }
+        }
+
+        let high = self.m_colors.split_off(split);
+        return (ColorBox::new(self.m_colors), ColorBox::new(high));
+    }
+
+    //
+    /// The pixel weighted average color, as an opaque RGBA5551 color
+    //
+    fn average (&self) -> u16
+    {
+        let mut sum = [0u64; 3];
+        for (color, n) in &self.m_colors {
+            for c in 0 .. 3 {
+                sum[c] += color[c] as u64 * *n as u64;
+            }
+        }
+
+        let count = self.m_count.max(1) as u64;
+        let average = |c : usize| -> u8 { (((sum[c] + count / 2) / count) as u8) << 3 };
+        return palette::rgba8_to_rgba5551([average(0), average(1), average(2), 0xFF]);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //
+    /// 8x8 image with 64 different colors, all of which RGBA5551 holds exactly
+    //
+    fn p_gradient () -> Vec<u8>
+    {
+        let mut rgba = Vec::<u8>::new();
+        for y in 0 .. 8u16 {
+            for x in 0 .. 8u16 {
+                rgba.extend_from_slice(&rgba5551_to_rgba8((x * 4) << 11 | (y * 4) << 6 | 16 << 1 | 1));
+            }
+        }
+        return rgba;
+    }
+
+    #[test]
+    fn parses_dither_names ()
+    {
+        assert_eq!(Dither::from_name("none"), Some(Dither::None));
+        assert_eq!(Dither::from_name("Ordered"), Some(Dither::Ordered));
+        assert_eq!(Dither::from_name("bayer"), Some(Dither::Ordered));
+        assert_eq!(Dither::from_name("floyd-steinberg"), Some(Dither::FloydSteinberg));
+        assert_eq!(Dither::from_name("FS"), Some(Dither::FloydSteinberg));
+        assert_eq!(Dither::from_name("random"), None);
+    }
+
+    #[test]
+    fn keeps_images_that_fit_exactly ()
+    {
+        let rgba = p_gradient();
+        let quantized = quantize(&rgba, 8, 8, 256, Dither::FloydSteinberg);
+        assert_eq!(quantized.palette.colors().len(), 64);
+        assert_eq!(quantized.error.mean_squared, 0.0);
+        assert_eq!(quantized.error.psnr, f64::INFINITY);
+    }
+
+    #[test]
+    fn reduces_to_16_colors ()
+    {
+        let rgba = p_gradient();
+        let quantized = quantize(&rgba, 8, 8, 16, Dither::None);
+        assert_eq!(quantized.palette.colors().len(), 16);
+        assert_eq!(quantized.indices.len(), 64);
+        assert!(quantized.indices.iter().all(|i| *i < 16));
+
+        // every pixel is within one of the 16 boxes
+        assert!(quantized.error.mean_squared > 0.0);
+        assert!(quantized.error.psnr > 25.0, "psnr {}", quantized.error.psnr);
+        assert_eq!(quantized.error.alpha_changed, 0);
+    }
+
+    #[test]
+    fn median_cut_is_deterministic ()
+    {
+        let rgba = p_gradient();
+        let palette = generate_palette(&rgba, 16);
+        assert_eq!(generate_palette(&rgba, 16), palette);
+
+        // the order of the pixels doesnt matter either
+        let reversed : Vec<u8> = rgba.chunks_exact(4).rev().flatten().copied().collect();
+        assert_eq!(generate_palette(&reversed, 16), palette);
+    }
+
+    #[test]
+    fn transparent_pixels_share_one_slot ()
+    {
+        // knock out a diagonal, with a different color behind each transparent pixel
+        let mut rgba = p_gradient();
+        for i in 0 .. 8 {
+            rgba[(i * 9) * 4 + 3] = 0;
+        }
+
+        let quantized = quantize(&rgba, 8, 8, 16, Dither::FloydSteinberg);
+        let transparent : Vec<usize> = (0 .. quantized.palette.colors().len())
+            .filter(|i| quantized.palette.rgba(*i).unwrap()[3] == 0)
+            .collect();
+        assert_eq!(transparent, vec![0]);
+        assert_eq!(quantized.palette.colors().len(), 16);
+
+        for (i, index) in quantized.indices.iter().enumerate() {
+            assert_eq!(*index == 0, i % 9 == 0, "pixel {}", i);
+        }
+        assert_eq!(quantized.error.alpha_changed, 0);
+    }
+
+    #[test]
+    fn dithering_mixes_the_nearest_colors ()
+    {
+        // mid grey between black and white: either all one color, or a mix of both
+        let rgba : Vec<u8> = [0x80, 0x80, 0x80, 0xFF].repeat(64);
+        let palette = Palette::new(vec![0x0001, 0xFFFF]);
+
+        let count_white = |dither : Dither| -> usize {
+            map_to_palette(&rgba, 8, 8, &palette, dither).iter().filter(|i| **i == 1).count()
+        };
+        assert!(count_white(Dither::None) % 64 == 0);
+        for dither in &[Dither::Ordered, Dither::FloydSteinberg] {
+            let white = count_white(*dither);
+            assert!((24 ..= 40).contains(&white), "{:?} gave {} white pixels", dither, white);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::deflate;
+use crate::errors;
+use crate::errors::Result;
+use crate::inflate;
+
+// Rarezip - the compressed asset container BK uses in the ROM.
+// Format (big endian):
+// [1172] [ssss ssss] [deflate stream ...]
+// 1172: magic
+// s: size of the decompressed data
+// Followed by a raw deflate stream (no zlib header/adler checksum).
+
+/// The magic at the start of every compressed asset
+pub const RAREZIP_MAGIC : [u8; 2] = [0x11, 0x72];
+
+/// Size of the header before the deflate stream
+pub const RAREZIP_HEADER_SIZE : usize = 6;
+
+//
+/// If the data looks like a rarezip container
+//
+pub fn is_compressed (data: &[u8]) -> bool
+{
+    return data.len() >= RAREZIP_HEADER_SIZE && data[0..2] == RAREZIP_MAGIC;
+}
+
+//
+/// The decompressed size stored in the header (None if it isnt a rarezip container)
+//
+pub fn decompressed_size (data: &[u8]) -> Option<u32>
+{
+    if !is_compressed(data) {
+        return None;
+    }
+
+    return Some(u32::from_be_bytes([data[2], data[3], data[4], data[5]]));
+}
+
+//
+/// Decompress a rarezip container, returning the decompressed data
+//
+pub fn decompress (data: &[u8]) -> Result<Vec<u8>>
+{
+    let expected_size = match decompressed_size(data) {
+        Some(s) => s,
+        None => {
+            return errors::DecompressionError { reason: "missing rarezip header".to_string() }.fail();
+        }
+    };
+
+    let (decompressed, _used) = inflate::inflate(&data[RAREZIP_HEADER_SIZE..])?;
+    if decompressed.len() != expected_size as usize {
+        return errors::DecompressionError {
+            reason: format!("header says {:#X} bytes but the stream held {:#X}", expected_size, decompressed.len())
+        }.fail();
+    }
+
+    return Ok(decompressed);
+}
+
+//
+/// Compress data into a rarezip container.
+/// level is the deflate level (1-9), deflate::RETAIL_LEVEL is the one the game assets were built with
+/// (whether the result matches an original asset byte for byte can be checked with compare).
+//
+pub fn compress (data: &[u8], level: u32) -> Vec<u8>
+{
+    let mut out = Vec::<u8>::with_capacity(data.len() / 2 + RAREZIP_HEADER_SIZE);
+    out.extend_from_slice(&RAREZIP_MAGIC);
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&deflate::deflate(data, level));
+    return out;
+}
+
+//
+/// Result of comparing a freshly compressed container against an original one
+//
+#[derive(Debug, PartialEq)]
+pub enum CompressionMatch
+{
+    //
+    /// Byte for byte identical
+    //
+    Identical,
+
+    //
+    /// Identical, but the original has extra bytes after the stream (ROM padding)
+    //
+    IdenticalWithPadding { padding: usize },
+
+    //
+    /// The streams differ, starting at the given offset
+    //
+    Differs { offset: usize }
+}
+
+//
+/// Compare freshly compressed data against an original container (ex. from the ROM)
+//
+pub fn compare (compressed: &[u8], original: &[u8]) -> CompressionMatch
+{
+    let common = compressed.len().min(original.len());
+    for i in 0 .. common {
+        if compressed[i] != original[i] {
+            return CompressionMatch::Differs { offset: i };
+        }
+    }
+
+    if compressed.len() == original.len() {
+        return CompressionMatch::Identical;
+    }
+
+    if compressed.len() < original.len() && original[common..].iter().all(|b| *b == 0) {
+        return CompressionMatch::IdenticalWithPadding { padding: original.len() - common };
+    }
+
+    return CompressionMatch::Differs { offset: common };
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn round_trips_at_every_level ()
+    {
+        let inputs = [Vec::new(), b"banjo".to_vec(), b"kazooie ".repeat(5000), (0 .. 0x11000).map(|i| (i * 7 % 253) as u8).collect()];
+        for level in 1 ..= 9 {
+            for input in inputs.iter() {
+                let compressed = compress(input, level);
+                assert!(is_compressed(&compressed));
+                assert_eq!(decompressed_size(&compressed), Some(input.len() as u32));
+                assert!(decompress(&compressed).unwrap() == *input, "level {} with {:#X} bytes", level, input.len());
+            }
+        }
+    }
+
+    #[test]
+    fn compares_against_the_original ()
+    {
+        let original = compress(&b"kazooie ".repeat(50), deflate::RETAIL_LEVEL);
+        assert_eq!(compare(&original, &original), CompressionMatch::Identical);
+
+        let mut padded = original.clone();
+        padded.extend_from_slice(&[0; 6]);
+        assert_eq!(compare(&original, &padded), CompressionMatch::IdenticalWithPadding { padding: 6 });
+
+        let mut changed = original.clone();
+        changed[8] ^= 0x01;
+        assert_eq!(compare(&original, &changed), CompressionMatch::Differs { offset: 8 });
+        assert_eq!(compare(&padded, &original), CompressionMatch::Differs { offset: original.len() });
+    }
+
+    fn p_assert_fails (data : &[u8], reason : &str)
+    {
+        match decompress(data) {
+            Err(errors::Error::DecompressionError { reason: r }) => assert!(r.contains(reason), "{}", r),
+            other => panic!("expected DecompressionError, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn rejects_the_wrong_magic ()
+    {
+        let mut data = compress(b"banjo", deflate::RETAIL_LEVEL);
+        data[0] = 0x12;
+        assert!(!is_compressed(&data));
+        assert_eq!(decompressed_size(&data), None);
+        p_assert_fails(&data, "missing rarezip header");
+        p_assert_fails(&RAREZIP_MAGIC, "missing rarezip header");
+    }
+
+    #[test]
+    fn rejects_the_wrong_size ()
+    {
+        for size in [4u32, 6, 0xFFFFFFFF] {
+            let mut data = compress(b"banjo", deflate::RETAIL_LEVEL);
+            data[2 .. 6].copy_from_slice(&size.to_be_bytes());
+            p_assert_fails(&data, "header says");
+        }
+
+        // a header with no stream after it
+        p_assert_fails(&[0x11, 0x72, 0x00, 0x00, 0x00, 0x05], "unexpected end");
+    }
+}
@@ -0,0 +1,1224 @@
+use crate::blender::{BlenderInputs, RenderMode};
+use crate::combiner::CombinerInputs;
+use crate::display_list_interpreter::{InterpreterResult, RenderState, Triangle};
+use crate::geo_type::GeoType;
+use crate::geometry_mode;
+use crate::model::Model;
+use crate::other_mode;
+use crate::png;
+use crate::tile_descriptor::{TileDescriptor, TILE_COUNT};
+use crate::vertex_store::Vertex;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// A software rasterizer for previewing models without an emulator (or a GPU).
+//
+// The triangles from the display list interpreter are transformed by a Camera,
+// clipped against the near plane and filled with a Z buffer. Each pixel goes through
+// the color combiner (G_SETCOMBINE) and the blender (the render mode from
+// G_SetOtherMode_L) like the RDP would, with the shade color being the vertex color -
+// or a fixed light when G_LIGHTING is on.
+//
+// Textures are sampled like the RDP does: through the tile descriptor (shift, clamp,
+// wrap and mirror), with the other mode's filter (point, three point bilinear or
+// average), and picking between mipmap levels by level of detail.
+
+//
+/// How much of the lit color comes from the ambient light
+//
+const AMBIENT_LIGHT : f64 = 0.4;
+
+//
+/// The direction the light comes from (in model space) when G_LIGHTING is on: above and in front
+//
+const LIGHT_DIRECTION : [f64; 3] = [0.4, 0.7, 0.6];
+
+//
+/// How close (relative to its depth) a decal has to be to the surface under it to be drawn
+//
+const DECAL_DEPTH_TOLERANCE : f64 = 0.001;
+
+//
+/// The gap between images on a contact sheet, in pixels
+//
+const CONTACT_SHEET_SPACING : usize = 4;
+
+//
+/// An 8 bit RGBA image
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image
+{
+    pub width: usize,
+    pub height: usize,
+
+    //
+    /// width * height pixels, 4 bytes each
+    //
+    pub rgba: Vec<u8>
+}
+
+impl Image
+{
+    //
+    /// Create an image filled with one color
+    //
+    pub fn new (width : usize, height : usize, color : [u8; 4]) -> Image
+    {
+        let rgba = color.iter().copied().cycle().take(width * height * 4).collect();
+        return Image { width, height, rgba };
+    }
+
+    //
+    /// The color of a pixel
+    //
+    pub fn pixel (&self, x : usize, y : usize) -> [u8; 4]
+    {
+        let p = (y * self.width + x) * 4;
+        return [self.rgba[p], self.rgba[p + 1], self.rgba[p + 2], self.rgba[p + 3]];
+    }
+
+    //
+    /// Change the color of a pixel
+    //
+    pub fn set_pixel (&mut self, x : usize, y : usize, color : [u8; 4])
+    {
+        let p = (y * self.width + x) * 4;
+        self.rgba[p .. p + 4].copy_from_slice(&color);
+    }
+
+    //
+    /// Copy another image on top of this one, with its top left corner at x, y (anything outside is cut off)
+    //
+    pub fn paste (&mut self, image : &Image, x : usize, y : usize)
+    {
+        for row in 0 .. image.height.min(self.height.saturating_sub(y)) {
+            let columns = image.width.min(self.width.saturating_sub(x));
+            let from = row * image.width * 4;
+            let to = ((y + row) * self.width + x) * 4;
+            self.rgba[to .. to + columns * 4].copy_from_slice(&image.rgba[from .. from + columns * 4]);
+        }
+    }
+
+    //
+    /// Encode as a png
+    //
+    pub fn to_png (&self) -> Vec<u8>
+    {
+        return png::encode_rgba(self.width as u32, self.height as u32, &self.rgba);
+    }
+}
+
+//
+/// Settings for a render
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderOptions
+{
+    pub width: usize,
+    pub height: usize,
+
+    //
+    /// The color of pixels nothing was drawn to
+    //
+    pub background: [u8; 4]
+}
+
+impl Default for RenderOptions
+{
+    fn default () -> RenderOptions
+    {
+        return RenderOptions {
+            width: 320,
+            height: 240,
+            background: [0, 0, 0, 0]
+        };
+    }
+}
+
+//
+/// Where the model is viewed from. Model space is Y up, and the camera looks along m_forward.
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera
+{
+    m_eye: [f64; 3],
+    m_forward: [f64; 3],
+    m_right: [f64; 3],
+    m_up: [f64; 3],
+
+    //
+    /// Vertical field of view in degrees
+    //
+    m_fov: f64,
+
+    //
+    /// Anything closer to the camera than this is clipped
+    //
+    m_near: f64,
+
+    //
+    /// For an orthographic camera, half the height of the view in model units (None for perspective)
+    //
+    m_orthographic: Option<f64>
+}
+
+//
+/// The orthographic views of a model
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum View
+{
+    //
+    /// Looking at the front (down -Z)
+    //
+    Front,
+
+    //
+    /// Looking at the right side (down -X)
+    //
+    Side,
+
+    //
+    /// Looking down from above, with the front at the bottom
+    //
+    Top
+}
+
+impl View
+{
+    //
+    /// Parse a view name from the command line (front, side, top)
+    //
+    pub fn from_name (name : &str) -> Option<View>
+    {
+        return match name.trim().to_lowercase().as_str() {
+            "front" => Some(View::Front),
+            "side" | "right" => Some(View::Side),
+            "top" => Some(View::Top),
+            _ => None
+        };
+    }
+
+    //
+    /// The yaw and pitch (in degrees) the view is seen from
+    //
+    pub fn angles (&self) -> (f64, f64)
+    {
+        return match self {
+            View::Front => (0.0, 0.0),
+            View::Side => (90.0, 0.0),
+            View::Top => (0.0, 90.0)
+        };
+    }
+}
+
+impl Camera
+{
+    //
+    /// A camera at eye looking at target, with a vertical field of view in degrees
+    //
+    pub fn look_at (eye : [f64; 3], target : [f64; 3], fov : f64) -> Camera
+    {
+        let forward = p_normalize(p_sub(target, eye));
+
+        // looking straight up or down, so pick another up direction
+        // (the one that keeps the front of the model at the bottom, like a slightly tilted view would)
+        let world_up = if forward[1].abs() > 0.999 { [0.0, 0.0, forward[1].signum()] } else { [0.0, 1.0, 0.0] };
+        let right = p_normalize(p_cross(forward, world_up));
+        let up = p_cross(right, forward);
+
+        let distance = p_length(p_sub(target, eye));
+        return Camera {
+            m_eye: eye,
+            m_forward: forward,
+            m_right: right,
+            m_up: up,
+            m_fov: fov,
+            m_near: (distance * 0.01).max(0.01),
+            m_orthographic: None
+        };
+    }
+
+    //
+    /// An orthographic camera at eye looking at target, showing half_height model units above and below the target
+    //
+    pub fn orthographic (eye : [f64; 3], target : [f64; 3], half_height : f64) -> Camera
+    {
+        let mut camera = Camera::look_at(eye, target, 0.0);
+        camera.m_orthographic = Some(half_height);
+        return camera;
+    }
+
+    //
+    /// A camera orbiting the model's verticies, far enough away that all of them are in view.
+    /// yaw turns around the model (0 looks at the front, down -Z) and pitch looks down from above, both in degrees.
+    //
+    pub fn for_model (model : &Model, yaw : f64, pitch : f64, fov : f64) -> Camera
+    {
+        return Camera::for_models(&[model], yaw, pitch, fov);
+    }
+
+    //
+    /// Like for_model, but framing the verticies of several models at once (ex. to compare them with the same camera)
+    //
+    pub fn for_models (models : &[&Model], yaw : f64, pitch : f64, fov : f64) -> Camera
+    {
+        let mut bounds = models.iter().map(|m| m.vertex_store().verticies()).filter(|v| !v.is_empty()).map(|v| p_vertex_bounds(v));
+        let (mut min, mut max) = bounds.next().unwrap_or(([0.0; 3], [0.0; 3]));
+        for (other_min, other_max) in bounds {
+            for axis in 0 .. 3 {
+                min[axis] = min[axis].min(other_min[axis]);
+                max[axis] = max[axis].max(other_max[axis]);
+            }
+        }
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, (min[2] + max[2]) / 2.0];
+        let radius = (p_length(p_sub(max, min)) / 2.0).max(1.0);
+
+        // far enough for the bounding sphere to fit in the view, with a little margin
+        let distance = radius / (fov.to_radians() / 2.0).sin() * 1.05;
+
+        let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+        let direction = [pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos()];
+        let eye = p_add(center, p_scale(direction, distance));
+
+        return Camera::look_at(eye, center, fov);
+    }
+
+    //
+    /// An orthographic camera looking at the model's verticies from one of the views, with all of them in view
+    //
+    pub fn for_model_orthographic (model : &Model, view : View) -> Camera
+    {
+        let (min, max) = p_vertex_bounds(model.vertex_store().verticies());
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, (min[2] + max[2]) / 2.0];
+        let radius = (p_length(p_sub(max, min)) / 2.0).max(1.0);
+
+        // back far enough that nothing is behind the camera, the distance doesnt change the size
+        let (yaw, pitch) = view.angles();
+        let (yaw, pitch) = (yaw.to_radians(), pitch.to_radians());
+        let direction = [pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos()];
+        let eye = p_add(center, p_scale(direction, radius * 2.0));
+
+        return Camera::orthographic(eye, center, radius * 1.05);
+    }
+
+    //
+    /// Where a point in model space ends up in an image of width x height pixels, and how far it is
+    /// in front of the camera. None if it is behind the near plane.
+    //
+    pub fn project (&self, point : [f64; 3], width : usize, height : usize) -> Option<([f64; 2], f64)>
+    {
+        let camera_space = self.p_to_camera_space(point);
+        if camera_space[2] < self.m_near {
+            return None;
+        }
+        return Some((self.p_project(camera_space, width, height).0, camera_space[2]));
+    }
+
+    //
+    /// Transform a point in model space to camera space (x right, y up, z away from the camera)
+    //
+    fn p_to_camera_space (&self, point : [f64; 3]) -> [f64; 3]
+    {
+        let relative = p_sub(point, self.m_eye);
+        return [p_dot(relative, self.m_right), p_dot(relative, self.m_up), p_dot(relative, self.m_forward)];
+    }
+
+    //
+    /// Project a camera space point to pixels, returning the pixel position and 1/w for perspective correction
+    //
+    fn p_project (&self, point : [f64; 3], width : usize, height : usize) -> ([f64; 2], f64)
+    {
+        let aspect = width as f64 / height as f64;
+        if let Some(half_height) = self.m_orthographic {
+            let ndc = [point[0] / (half_height * aspect), point[1] / half_height];
+            let pixel = [(ndc[0] + 1.0) / 2.0 * width as f64, (1.0 - ndc[1]) / 2.0 * height as f64];
+            return (pixel, 1.0);
+        }
+
+        let f = 1.0 / (self.m_fov.to_radians() / 2.0).tan();
+
+        let ndc = [point[0] * f / (point[2] * aspect), point[1] * f / point[2]];
+        let pixel = [(ndc[0] + 1.0) / 2.0 * width as f64, (1.0 - ndc[1]) / 2.0 * height as f64];
+        return (pixel, 1.0 / point[2]);
+    }
+}
+
+//
+/// Render the triangles from running a model's display list
+//
+pub fn render (model : &Model, result : &InterpreterResult, camera : &Camera, options : &RenderOptions) -> Image
+{
+    let mut renderer = Renderer {
+        m_model: model,
+        m_camera: camera,
+        m_textures: HashMap::new(),
+        m_image: Image::new(options.width, options.height, options.background),
+        m_depth: vec![f64::INFINITY; options.width * options.height]
+    };
+
+    for triangle in &result.triangles {
+        renderer.p_draw_triangle(triangle);
+    }
+
+    return renderer.m_image;
+}
+
+//
+/// Lay images out in a grid, left to right then top to bottom, with a small gap between them.
+/// Every cell is the size of the largest image.
+//
+pub fn contact_sheet (images : &[Image], columns : usize, background : [u8; 4]) -> Image
+{
+    let spacing = CONTACT_SHEET_SPACING;
+    let columns = columns.clamp(1, images.len().max(1));
+    let rows = images.len().div_ceil(columns).max(1);
+    let cell_width = images.iter().map(|i| i.width).max().unwrap_or(0);
+    let cell_height = images.iter().map(|i| i.height).max().unwrap_or(0);
+
+    let mut sheet = Image::new(
+        columns * cell_width + (columns - 1) * spacing,
+        rows * cell_height + (rows - 1) * spacing,
+        background
+    );
+    for (i, image) in images.iter().enumerate() {
+        let x = (i % columns) * (cell_width + spacing);
+        let y = (i / columns) * (cell_height + spacing);
+        sheet.paste(image, x, y);
+    }
+    return sheet;
+}
+
+//
+/// Parse a color from the command line: RRGGBB or RRGGBBAA in hex (an optional # in front)
+//
+pub fn parse_color (text : &str) -> Option<[u8; 4]>
+{
+    let text = text.trim_start_matches('#');
+    if (text.len() != 6 && text.len() != 8) || !text.is_ascii() {
+        return None;
+    }
+
+    let mut color = [0xFF; 4];
+    for (i, c) in color.iter_mut().enumerate().take(text.len() / 2) {
+        *c = u8::from_str_radix(&text[i * 2 .. i * 2 + 2], 16).ok()?;
+    }
+    return Some(color);
+}
+
+//
+/// A corner of a triangle being drawn
+//
+#[derive(Copy, Clone, Debug)]
+struct RasterVertex
+{
+    //
+    /// Camera space position
+    //
+    position: [f64; 3],
+
+    //
+    /// Shade color (0-1)
+    //
+    color: [f64; 4],
+
+    //
+    /// Texture coordinates in texels
+    //
+    uv: [f64; 2]
+}
+
+//
+/// One mipmap level of a texture, and the tile it is read through
+//
+struct TextureLevel
+{
+    image: Rc<Image>,
+    tile: TileDescriptor,
+
+    //
+    /// What to divide coordinates by when the display list didnt set up the tile (and its shift)
+    //
+    scale: f64
+}
+
+//
+/// State for a single render
+//
+struct Renderer<'a>
+{
+    m_model: &'a Model,
+    m_camera: &'a Camera,
+
+    //
+    /// Decoded texture images by (texture, offset, row size, height). None if it couldnt be decoded.
+    //
+    m_textures: HashMap<(usize, u32, u32, u32), Option<Rc<Image>>>,
+
+    m_image: Image,
+    m_depth: Vec<f64>
+}
+
+impl<'a> Renderer<'a>
+{
+    fn p_draw_triangle (&mut self, triangle : &Triangle)
+    {
+        let verticies = self.m_model.vertex_store().verticies();
+        let state = &triangle.state;
+
+        let mut corners = [RasterVertex { position: [0.0; 3], color: [1.0; 4], uv: [0.0; 2] }; 3];
+        for (corner, index) in corners.iter_mut().zip(triangle.verticies.iter()) {
+            let vertex = &verticies[*index];
+            let position = [vertex.pos[0] as f64, vertex.pos[1] as f64, vertex.pos[2] as f64];
+
+            corner.position = self.m_camera.p_to_camera_space(position);
+            corner.color = p_shade_color(vertex, state.geometry_mode);
+
+            // S10.5 coordinates, scaled by G_TEXTURE
+            corner.uv = [
+                vertex.uv[0] as f64 * state.texture_scale[0] as f64 / 65536.0 / 32.0,
+                vertex.uv[1] as f64 * state.texture_scale[1] as f64 / 65536.0 / 32.0
+            ];
+        }
+
+        // flat shading uses the first vertex's color for the whole triangle
+        if state.geometry_mode & geometry_mode::G_SHADING_SMOOTH == 0 {
+            corners[1].color = corners[0].color;
+            corners[2].color = corners[0].color;
+        }
+
+        // TEXEL0 is the render tile, TEXEL1 the next one - which is the next mipmap level when mipmapping
+        let two_cycle = p_two_cycle(state);
+        let mipmapping = state.other_mode_high & other_mode::G_TL_LOD != 0
+            || matches!(self.m_model.internal_geo_type(), GeoType::TrilinearMipMapping);
+        let next_tile_set = state.tiles[(state.texture_tile as usize + 1) % TILE_COUNT].is_some();
+        let level_count = if mipmapping {
+            state.mipmap_levels as usize + 1
+        } else if state.combine.uses_texel1(two_cycle) && next_tile_set {
+            2
+        } else {
+            1
+        };
+
+        let levels = match triangle.texture {
+            Some(index) if state.texture_enabled => self.p_texture_levels(index, state, level_count),
+            _ => Vec::new()
+        };
+
+        let polygon = p_clip_near(&corners, self.m_camera.m_near);
+        for i in 1 .. polygon.len().saturating_sub(1) {
+            self.p_rasterize(triangle, [polygon[0], polygon[i], polygon[i + 1]], &levels, mipmapping);
+        }
+    }
+
+    fn p_rasterize (&mut self, triangle : &Triangle, corners : [RasterVertex; 3], levels : &[TextureLevel], mipmapping : bool)
+    {
+        let (width, height) = (self.m_image.width, self.m_image.height);
+        let projected : Vec<([f64; 2], f64)> = corners.iter().map(|c| self.m_camera.p_project(c.position, width, height)).collect();
+        let (a, b, c) = (projected[0].0, projected[1].0, projected[2].0);
+        let state = &triangle.state;
+
+        // counter clockwise (in Y up model space) is the front, which is clockwise once Y points down
+        let area = p_edge(a, b, c);
+        if area == 0.0 {
+            return;
+        }
+        let front_facing = area < 0.0;
+        let mode = state.geometry_mode;
+        if (front_facing && mode & geometry_mode::G_CULL_FRONT != 0) || (!front_facing && mode & geometry_mode::G_CULL_BACK != 0) {
+            return;
+        }
+
+        // the RSP only passes depth along with G_ZBUFFER, then the render mode decides what to do with it
+        let render_mode = RenderMode::new(state.other_mode_low);
+        let z_buffer = mode & geometry_mode::G_ZBUFFER != 0;
+        let z_compare = z_buffer && render_mode.z_compare();
+        let z_update = z_buffer && render_mode.z_update();
+        let decal = render_mode.z_mode() == other_mode::ZMODE_DEC;
+
+        let two_cycle = p_two_cycle(state);
+        let perspective = state.other_mode_high & other_mode::G_TP_PERSP != 0;
+        let filter = state.other_mode_high & (0x03 << other_mode::G_MDSFT_TEXTFILT);
+
+        let primitive = p_unit_color(state.primitive_color);
+        let environment = p_unit_color(state.environment_color);
+        let blend_color = p_unit_color(state.blend_color);
+        let fog_color = p_unit_color(state.fog_color);
+
+        let barycentric = |p : [f64; 2]| -> [f64; 3] {
+            return [p_edge(b, c, p) / area, p_edge(c, a, p) / area, p_edge(a, b, p) / area];
+        };
+
+        // perspective correct interpolation: interpolate attribute/w and 1/w
+        let interpolate = |weights : &[f64; 3], correct : bool, value : &dyn Fn(&RasterVertex) -> f64| -> f64 {
+            if !correct {
+                return (0 .. 3).map(|i| weights[i] * value(&corners[i])).sum();
+            }
+            let inverse_w : f64 = (0 .. 3).map(|i| weights[i] * projected[i].1).sum();
+            return (0 .. 3).map(|i| weights[i] * projected[i].1 * value(&corners[i])).sum::<f64>() / inverse_w;
+        };
+        let uv_at = |weights : &[f64; 3]| -> [f64; 2] {
+            return [interpolate(weights, perspective, &|v| v.uv[0]), interpolate(weights, perspective, &|v| v.uv[1])];
+        };
+
+        let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as usize;
+        let max_x = (a[0].max(b[0]).max(c[0]).ceil().max(0.0) as usize).min(width);
+        let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as usize;
+        let max_y = (a[1].max(b[1]).max(c[1]).ceil().max(0.0) as usize).min(height);
+
+        for y in min_y .. max_y {
+            for x in min_x .. max_x {
+                let p = [x as f64 + 0.5, y as f64 + 0.5];
+                let weights = barycentric(p);
+                if weights.iter().any(|w| *w < 0.0) {
+                    continue;
+                }
+
+                let depth = interpolate(&weights, true, &|v : &RasterVertex| v.position[2]);
+                let pixel = y * width + x;
+                if z_compare {
+                    let stored = self.m_depth[pixel];
+                    let visible = if decal { (depth - stored).abs() <= stored * DECAL_DEPTH_TOLERANCE } else { depth < stored };
+                    if !visible {
+                        continue;
+                    }
+                }
+
+                let mut shade = [0.0; 4];
+                for (channel, value) in shade.iter_mut().enumerate() {
+                    *value = interpolate(&weights, true, &|v : &RasterVertex| v.color[channel]);
+                }
+
+                let mut inputs = CombinerInputs {
+                    texel0: [1.0; 4],
+                    texel1: [1.0; 4],
+                    shade,
+                    primitive,
+                    environment,
+                    lod_fraction: 0.0,
+                    primitive_lod_fraction: state.primitive_lod_fraction as f64 / 255.0
+                };
+
+                if !levels.is_empty() {
+                    let uv = uv_at(&weights);
+
+                    // level of detail: how many texels the pixel covers, from the neighbouring pixels
+                    let (level, fraction) = if mipmapping && levels.len() > 1 {
+                        let right = uv_at(&barycentric([p[0] + 1.0, p[1]]));
+                        let below = uv_at(&barycentric([p[0], p[1] + 1.0]));
+                        let lod = (right[0] - uv[0]).abs().max((right[1] - uv[1]).abs())
+                            .max((below[0] - uv[0]).abs()).max((below[1] - uv[1]).abs());
+                        p_level_of_detail(lod, levels.len() - 1)
+                    } else {
+                        (0, 0.0)
+                    };
+
+                    inputs.texel0 = p_sample(&levels[level], uv, filter);
+                    inputs.texel1 = match levels.get(level + 1) {
+                        Some(next) => p_sample(next, uv, filter),
+                        None => inputs.texel0
+                    };
+                    inputs.lod_fraction = fraction;
+                }
+
+                let mut combined = state.combine.evaluate(0, &inputs, [0.0; 4]);
+                if two_cycle {
+                    combined = state.combine.evaluate(1, &inputs, combined);
+                }
+
+                let covered = match render_mode.alpha_compare() {
+                    other_mode::G_AC_THRESHOLD => combined[3] > blend_color[3],
+                    other_mode::G_AC_DITHER => combined[3] > p_dither_threshold(x, y),
+                    _ => true
+                };
+                // cutouts: with the coverage multiplied by alpha, too little alpha covers nothing
+                if !covered || (render_mode.coverage_times_alpha() && combined[3] < 1.0 / 8.0) {
+                    continue;
+                }
+
+                let mut blender_inputs = BlenderInputs {
+                    input: combined,
+                    memory: p_unit_color(self.m_image.pixel(x, y)),
+                    blend_color,
+                    fog_color,
+                    shade_alpha: shade[3]
+                };
+                let out = if two_cycle {
+                    let first = render_mode.blend(0, false, &blender_inputs);
+                    blender_inputs.input = [first[0], first[1], first[2], combined[3]];
+                    render_mode.blend(1, true, &blender_inputs)
+                } else {
+                    render_mode.blend(0, true, &blender_inputs)
+                };
+
+                self.m_image.set_pixel(x, y, [
+                    (out[0] * 255.0).round() as u8, (out[1] * 255.0).round() as u8,
+                    (out[2] * 255.0).round() as u8, (out[3] * 255.0).round() as u8
+                ]);
+
+                if z_update {
+                    self.m_depth[pixel] = depth;
+                }
+            }
+        }
+    }
+
+    //
+    /// The images (and tiles) for the first level_count tiles from the render tile: the texture itself,
+    /// then the mipmap levels (G_TL_LOD, or a TrilinearMipMapping model) or whatever TEXEL1 reads.
+    ///
+    /// The levels follow each other in the texture data. When the display list sets up a tile for
+    /// each level, where the tile is in TMEM gives where the level is in the data (and its row size).
+    //
+    fn p_texture_levels (&mut self, index : usize, state : &RenderState, level_count : usize) -> Vec<TextureLevel>
+    {
+        let model = self.m_model;
+        let texture = match model.textures().get(index) {
+            Some(t) => t,
+            None => return Vec::new()
+        };
+
+        let bits = texture.texture_type().bits_per_pixel().max(1);
+        let first_tile = state.tiles[state.texture_tile as usize % TILE_COUNT];
+
+        let mut levels = Vec::<TextureLevel>::new();
+        let mut next_offset = 0u32;
+        for level in 0 .. level_count {
+            let tile = state.tiles[(state.texture_tile as usize + level) % TILE_COUNT];
+            let (width, height) = match tile.and_then(|t| t.size()) {
+                Some(size) if level > 0 => (size[0], size[1]),
+                _ => (((texture.width() as u32) >> level).max(1), ((texture.height() as u32) >> level).max(1))
+            };
+
+            let offset = match (tile, first_tile) {
+                (Some(t), Some(first)) if level > 0 && t.tmem_address >= first.tmem_address => (t.tmem_address - first.tmem_address) as u32 * 8,
+                _ => next_offset
+            };
+
+            // rows are as long as the tile's line (at least the width)
+            let row_size = tile.map(|t| t.line as u32 * 64 / bits).filter(|r| *r >= width && *r <= 0xFF).unwrap_or(width);
+
+            let image = match self.p_decode_level(index, offset, row_size, width, height) {
+                Some(i) => i,
+                None => break
+            };
+
+            next_offset = offset + (row_size * height * bits).div_ceil(8);
+            levels.push(TextureLevel {
+                image,
+                tile: tile.unwrap_or_default(),
+                scale: if tile.is_some() { 1.0 } else { (1u32 << level) as f64 }
+            });
+        }
+
+        return levels;
+    }
+
+    //
+    /// Decode (or reuse) an image from a texture's data
+    //
+    fn p_decode_level (&mut self, index : usize, offset : u32, row_size : u32, width : u32, height : u32) -> Option<Rc<Image>>
+    {
+        let model = self.m_model;
+        return self.m_textures.entry((index, offset, row_size, height)).or_insert_with(|| {
+            let texture = model.textures().get(index)?;
+            if row_size > 0xFF || height > 0xFF {
+                return None;
+            }
+
+            let rgba = model.texture_region_rgba(texture, offset, row_size as u8, height as u8).ok()?;
+            let mut image = Image::new(width as usize, height as usize, [0; 4]);
+            for y in 0 .. height as usize {
+                let row = y * row_size as usize * 4;
+                let out = y * width as usize * 4;
+                image.rgba[out .. out + width as usize * 4].copy_from_slice(&rgba[row .. row + width as usize * 4]);
+            }
+            return Some(Rc::new(image));
+        }).clone();
+    }
+}
+
+//
+/// If the RDP is in two cycle mode (the combiner and blender run twice)
+//
+fn p_two_cycle (state : &RenderState) -> bool
+{
+    return other_mode::field(state.other_mode_high, other_mode::G_MDSFT_CYCLETYPE, 2) << other_mode::G_MDSFT_CYCLETYPE == other_mode::G_CYC_2CYCLE;
+}
+
+//
+/// An RGBA color as 0-1
+//
+fn p_unit_color (color : [u8; 4]) -> [f64; 4]
+{
+    return [color[0] as f64 / 255.0, color[1] as f64 / 255.0, color[2] as f64 / 255.0, color[3] as f64 / 255.0];
+}
+
+//
+/// The alpha a pixel needs to be drawn with G_AC_DITHER (a 4x4 ordered pattern instead of random noise)
+//
+fn p_dither_threshold (x : usize, y : usize) -> f64
+{
+    const PATTERN : [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+    return (PATTERN[y % 4][x % 4] as f64 + 0.5) / 16.0;
+}
+
+//
+/// Pick the mipmap level for how many texels a pixel covers, returning the level and how far it is
+/// towards the next one (for blending them, trilinear)
+//
+fn p_level_of_detail (texels_per_pixel : f64, max_level : usize) -> (usize, f64)
+{
+    if texels_per_pixel <= 1.0 {
+        return (0, 0.0);
+    }
+
+    let level = texels_per_pixel.log2().floor() as usize;
+    if level >= max_level {
+        return (max_level, 0.0);
+    }
+
+    let fraction = texels_per_pixel / (1u32 << level) as f64 - 1.0;
+    return (level, fraction.clamp(0.0, 1.0));
+}
+
+//
+/// Sample a texture level through its tile, returning the color as 0-1.
+/// The bilinear filter is the RDP's three point version: it uses the triangle of texels nearest the sample.
+//
+fn p_sample (level : &TextureLevel, uv : [f64; 2], filter : u32) -> [f64; 4]
+{
+    let image = &level.image;
+    let s = level.tile.tile_coordinate(uv[0] / level.scale, 0);
+    let t = level.tile.tile_coordinate(uv[1] / level.scale, 1);
+
+    let fetch = |x : i64, y : i64| -> [f64; 4] {
+        let x = level.tile.wrap_texel(x, 0).rem_euclid(image.width as i64) as usize;
+        let y = level.tile.wrap_texel(y, 1).rem_euclid(image.height as i64) as usize;
+        let texel = image.pixel(x, y);
+        return [texel[0] as f64 / 255.0, texel[1] as f64 / 255.0, texel[2] as f64 / 255.0, texel[3] as f64 / 255.0];
+    };
+
+    let (x, y) = (s.floor() as i64, t.floor() as i64);
+    if filter == other_mode::G_TF_POINT {
+        return fetch(x, y);
+    }
+
+    let corners = [fetch(x, y), fetch(x + 1, y), fetch(x, y + 1), fetch(x + 1, y + 1)];
+    let mut out = [0.0; 4];
+    if filter == other_mode::G_TF_AVERAGE {
+        for (channel, value) in out.iter_mut().enumerate() {
+            *value = corners.iter().map(|c| c[channel]).sum::<f64>() / 4.0;
+        }
+        return out;
+    }
+
+    let (fx, fy) = (s - s.floor(), t - t.floor());
+    for (channel, value) in out.iter_mut().enumerate() {
+        let [c00, c10, c01, c11] = [corners[0][channel], corners[1][channel], corners[2][channel], corners[3][channel]];
+        *value = if fx + fy <= 1.0 {
+            c00 + fx * (c10 - c00) + fy * (c01 - c00)
+        } else {
+            c11 + (1.0 - fx) * (c01 - c11) + (1.0 - fy) * (c10 - c11)
+        };
+    }
+    return out;
+}
+
+//
+/// The shade color of a vertex: its color, or a fixed light on its normal when G_LIGHTING is on
+//
+fn p_shade_color (vertex : &Vertex, mode : u32) -> [f64; 4]
+{
+    let alpha = vertex.alpha as f64 / 255.0;
+    if mode & geometry_mode::G_SHADE == 0 {
+        return [1.0, 1.0, 1.0, 1.0];
+    }
+
+    if mode & geometry_mode::G_LIGHTING != 0 {
+        let light = AMBIENT_LIGHT + (1.0 - AMBIENT_LIGHT) * p_dot(vertex.normal(), p_normalize(LIGHT_DIRECTION)).max(0.0);
+        return [light, light, light, alpha];
+    }
+
+    return p_unit_color(vertex.color());
+}
+
+//
+/// Clip a triangle against the near plane, returning the polygon left (0, 3 or 4 corners)
+//
+fn p_clip_near (corners : &[RasterVertex; 3], near : f64) -> Vec<RasterVertex>
+{
+    let mut out = Vec::<RasterVertex>::with_capacity(4);
+    for i in 0 .. 3 {
+        let current = corners[i];
+        let next = corners[(i + 1) % 3];
+        let current_inside = current.position[2] >= near;
+        let next_inside = next.position[2] >= near;
+
+        if current_inside {
+            out.push(current);
+        }
+        if current_inside != next_inside {
+            let t = (near - current.position[2]) / (next.position[2] - current.position[2]);
+            out.push(p_lerp_vertex(&current, &next, t));
+        }
+    }
+    return out;
+}
+
+fn p_lerp_vertex (a : &RasterVertex, b : &RasterVertex, t : f64) -> RasterVertex
+{
+    let lerp = |x : f64, y : f64| -> f64 { x + (y - x) * t };
+    return RasterVertex {
+        position: [lerp(a.position[0], b.position[0]), lerp(a.position[1], b.position[1]), lerp(a.position[2], b.position[2])],
+        color: [lerp(a.color[0], b.color[0]), lerp(a.color[1], b.color[1]), lerp(a.color[2], b.color[2]), lerp(a.color[3], b.color[3])],
+        uv: [lerp(a.uv[0], b.uv[0]), lerp(a.uv[1], b.uv[1])]
+    };
+}
+
+//
+/// The smallest and largest corner of the box around some verticies
+//
+fn p_vertex_bounds (verticies : &[Vertex]) -> ([f64; 3], [f64; 3])
+{
+    if verticies.is_empty() {
+        return ([0.0; 3], [0.0; 3]);
+    }
+
+    let mut min = [f64::MAX; 3];
+    let mut max = [f64::MIN; 3];
+    for v in verticies {
+        for axis in 0 .. 3 {
+            min[axis] = min[axis].min(v.pos[axis] as f64);
+            max[axis] = max[axis].max(v.pos[axis] as f64);
+        }
+    }
+    return (min, max);
+}
+
+//
+/// Twice the signed area of the triangle a, b, p
+//
+fn p_edge (a : [f64; 2], b : [f64; 2], p : [f64; 2]) -> f64
+{
+    return (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0]);
+}
+
+// --- vector helpers
+
+fn p_add (a : [f64; 3], b : [f64; 3]) -> [f64; 3]
+{ return [a[0] + b[0], a[1] + b[1], a[2] + b[2]]; }
+
+fn p_sub (a : [f64; 3], b : [f64; 3]) -> [f64; 3]
+{ return [a[0] - b[0], a[1] - b[1], a[2] - b[2]]; }
+
+fn p_scale (a : [f64; 3], s : f64) -> [f64; 3]
+{ return [a[0] * s, a[1] * s, a[2] * s]; }
+
+fn p_dot (a : [f64; 3], b : [f64; 3]) -> f64
+{ return a[0] * b[0] + a[1] * b[1] + a[2] * b[2]; }
+
+fn p_cross (a : [f64; 3], b : [f64; 3]) -> [f64; 3]
+{ return [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]; }
+
+fn p_length (a : [f64; 3]) -> f64
+{ return p_dot(a, a).sqrt(); }
+
+fn p_normalize (a : [f64; 3]) -> [f64; 3]
+{
+    let length = p_length(a);
+    if length == 0.0 {
+        return a;
+    }
+    return p_scale(a, 1.0 / length);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::display_list_interpreter::DisplayListInterpreter;
+    use crate::image_diff;
+    use crate::test_models::*;
+    use crate::texture_type::TextureType;
+    use crate::tile_descriptor::{G_TX_CLAMP, G_TX_MIRROR};
+
+    use std::path::PathBuf;
+
+    // Golden renders: small synthetic models (built by the tests, and checked in next to
+    // their render in testdata/render) compared against reference pngs.
+    // Run with BKMODEL_UPDATE_GOLDEN=1 to write new ones after an intended change.
+
+    //
+    /// An RGBA16 texel
+    //
+    fn p_rgba16 (color : [u8; 3]) -> [u8; 2]
+    {
+        let value = ((color[0] as u16 >> 3) << 11) | ((color[1] as u16 >> 3) << 6) | ((color[2] as u16 >> 3) << 1) | 1;
+        return value.to_be_bytes();
+    }
+
+    //
+    /// A 4x4 RGBA16 texture where every texel is different: red across, green down
+    //
+    fn p_gradient_texture () -> Vec<u8>
+    {
+        return (0 .. 16).flat_map(|i| p_rgba16([(i % 4) as u8 * 80, (i / 4) as u8 * 80, 0x40])).collect();
+    }
+
+    //
+    /// A 32x32 quad centered on x facing the front, with S and T (S10.5) going from uv[0] to uv[1]
+    //
+    fn p_quad (builder : &mut ModelBuilder, x : i16, uv : [i16; 2])
+    {
+        let first = builder.vertex([x - 16, -16, 0], [uv[0], uv[1]], [0xFF; 3], 0xFF);
+        builder.vertex([x + 16, -16, 0], [uv[1], uv[1]], [0xFF; 3], 0xFF);
+        builder.vertex([x + 16, 16, 0], [uv[1], uv[0]], [0xFF; 3], 0xFF);
+        builder.vertex([x - 16, 16, 0], [uv[0], uv[0]], [0xFF; 3], 0xFF);
+
+        builder.command(g_vtx(0, 4, vertex_address(first)));
+        builder.command(g_tri2([0, 1, 2], [0, 2, 3]));
+    }
+
+    //
+    /// The gradient texture on a quad, read through tile 0 with the clamp/mirror flags and filter
+    //
+    fn p_sampler_model (clamp_mirror : u8, filter : u32) -> ModelBuilder
+    {
+        let mut builder = ModelBuilder::new();
+        let texture = builder.texture(TextureType::RGBA16, 4, 4, p_gradient_texture());
+        builder.command(g_texture([0xFFFF, 0xFFFF], 0, 0));
+        builder.command(g_settimg(texture));
+        builder.command(g_settile(0, 1, 0, [clamp_mirror; 2], [2, 2], [0, 0]));
+        builder.command(g_settilesize(0, [0, 0], [3, 3]));
+        builder.command(g_setothermode_h(other_mode::G_MDSFT_TEXTFILT as u8, 2, filter));
+        // -4 to 8 texels, past both edges of the texture
+        p_quad(&mut builder, 0, [-4 * 32, 8 * 32]);
+        return builder;
+    }
+
+    //
+    /// Render a golden model and compare it with its reference png
+    //
+    fn p_check_golden (name : &str, builder : &ModelBuilder, width : usize, height : usize)
+    {
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata").join("render");
+        let model_path = directory.join(format!("{}.bin", name));
+        let png_path = directory.join(format!("{}.png", name));
+
+        let render = |data : Vec<u8>| -> Image {
+            let model = Model::from_bytes(data).unwrap();
+            let result = DisplayListInterpreter::run(&model);
+            assert!(result.warnings.is_empty(), "{}: {:?}", name, result.warnings);
+
+            let camera = Camera::for_model_orthographic(&model, View::Front);
+            return super::render(&model, &result, &camera, &RenderOptions { width, height, background: [0, 0, 0, 0xFF] });
+        };
+
+        if std::env::var_os("BKMODEL_UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(&directory).unwrap();
+            std::fs::write(&model_path, builder.build()).unwrap();
+            std::fs::write(&png_path, render(builder.build()).to_png()).unwrap();
+        }
+
+        let model = std::fs::read(&model_path).unwrap();
+        assert!(model == builder.build(), "{} is out of date, run with BKMODEL_UPDATE_GOLDEN=1", model_path.display());
+
+        let expected = png::decode_rgba(&std::fs::read(&png_path).unwrap()).unwrap();
+        let expected = Image { width: expected.width as usize, height: expected.height as usize, rgba: expected.rgba };
+        let got = render(model);
+        assert_eq!((got.width, got.height), (expected.width, expected.height), "{}", name);
+
+        let (difference, _) = image_diff::compare(&expected, &got, 0);
+        assert_eq!(difference.changed_pixels, 0, "{}: {:?}", name, difference);
+    }
+
+    #[test]
+    fn golden_wrap ()
+    {
+        p_check_golden("wrap", &p_sampler_model(0, other_mode::G_TF_POINT), 32, 32);
+    }
+
+    #[test]
+    fn golden_mirror ()
+    {
+        p_check_golden("mirror", &p_sampler_model(G_TX_MIRROR, other_mode::G_TF_POINT), 32, 32);
+    }
+
+    #[test]
+    fn golden_clamp ()
+    {
+        p_check_golden("clamp", &p_sampler_model(G_TX_CLAMP, other_mode::G_TF_POINT), 32, 32);
+    }
+
+    #[test]
+    fn golden_three_point_filter ()
+    {
+        p_check_golden("three_point", &p_sampler_model(0, other_mode::G_TF_BILERP), 32, 32);
+    }
+
+    #[test]
+    fn golden_mipmap_levels ()
+    {
+        // an 8x8 red texture with its mipmaps after it: 4x4 green, 2x2 blue, 1x1 white
+        let mut data = Vec::<u8>::new();
+        for (size, color) in [(8, [0xFF, 0, 0]), (4, [0, 0xFF, 0]), (2, [0, 0, 0xFF]), (1, [0xFF, 0xFF, 0xFF])].iter() {
+            for _ in 0 .. size * size {
+                data.extend_from_slice(&p_rgba16(*color));
+            }
+        }
+
+        let mut builder = ModelBuilder::new();
+        let texture = builder.texture(TextureType::RGBA16, 8, 8, data);
+        builder.command(g_texture([0xFFFF, 0xFFFF], 3, 0));
+        builder.command(g_settimg(texture));
+        builder.command(g_setothermode_h(other_mode::G_MDSFT_TEXTFILT as u8, 2, other_mode::G_TF_POINT));
+        builder.command(g_setothermode_h(other_mode::G_MDSFT_TEXTLOD as u8, 1, other_mode::G_TL_LOD));
+
+        // about a texel per pixel on the left (the first level), several on the right (a smaller one)
+        p_quad(&mut builder, -24, [0, 8 * 32]);
+        p_quad(&mut builder, 24, [0, 64 * 32]);
+        p_check_golden("mipmap", &builder, 64, 32);
+    }
+
+    //
+    /// A flat colored square around center, facing the front (or the back, wound the other way)
+    //
+    fn p_colored_quad (builder : &mut ModelBuilder, center : [i16; 3], half_size : i16, color : [u8; 3], facing_front : bool)
+    {
+        let [x, y, z] = center;
+        let first = builder.vertex([x - half_size, y - half_size, z], [0, 0], color, 0xFF);
+        builder.vertex([x + half_size, y - half_size, z], [0, 0], color, 0xFF);
+        builder.vertex([x + half_size, y + half_size, z], [0, 0], color, 0xFF);
+        builder.vertex([x - half_size, y + half_size, z], [0, 0], color, 0xFF);
+
+        builder.command(g_vtx(0, 4, vertex_address(first)));
+        if facing_front {
+            builder.command(g_tri2([0, 1, 2], [0, 2, 3]));
+        } else {
+            builder.command(g_tri2([0, 2, 1], [0, 3, 2]));
+        }
+    }
+
+    //
+    /// Render from the front, returning the color at each of the points (in model space)
+    //
+    fn p_colors_at (builder : &ModelBuilder, points : &[[f64; 3]]) -> Vec<[u8; 4]>
+    {
+        let model = Model::from_bytes(builder.build()).unwrap();
+        let result = DisplayListInterpreter::run(&model);
+        let camera = Camera::for_model_orthographic(&model, View::Front);
+        let (width, height) = (64, 64);
+        let image = super::render(&model, &result, &camera, &RenderOptions { width, height, background: [0, 0, 0, 0xFF] });
+
+        return points.iter().map(|point| {
+            let (pixel, _) = camera.project(*point, width, height).unwrap();
+            return image.pixel(pixel[0] as usize, pixel[1] as usize);
+        }).collect();
+    }
+
+    const RED : [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+    const BLUE : [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+    const BACKGROUND : [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+    #[test]
+    fn nearer_triangles_win_the_depth_test ()
+    {
+        // red in front of blue, overlapping around x = 4. The camera is looking down -Z, so red is nearer.
+        let points = [[-12.0, 0.0, 10.0], [4.0, 0.0, 10.0], [20.0, 0.0, 0.0]];
+        for red_first in [true, false] {
+            let mut builder = ModelBuilder::new();
+            for red in [red_first, !red_first] {
+                if red {
+                    p_colored_quad(&mut builder, [0, 0, 10], 16, [0xFF, 0, 0], true);
+                } else {
+                    p_colored_quad(&mut builder, [8, 0, 0], 16, [0, 0, 0xFF], true);
+                }
+            }
+            assert_eq!(p_colors_at(&builder, &points), [RED, RED, BLUE], "red drawn first: {}", red_first);
+        }
+
+        // without G_ZBUFFER whatever is drawn last covers the rest
+        let mut builder = ModelBuilder::new();
+        builder.command(g_cleargeometrymode(geometry_mode::G_ZBUFFER));
+        p_colored_quad(&mut builder, [0, 0, 10], 16, [0xFF, 0, 0], true);
+        p_colored_quad(&mut builder, [8, 0, 0], 16, [0, 0, 0xFF], true);
+        assert_eq!(p_colors_at(&builder, &points), [RED, BLUE, BLUE]);
+    }
+
+    #[test]
+    fn culls_faces_by_winding ()
+    {
+        // red faces the camera and blue faces away
+        let points = [[-20.0, 0.0, 0.0], [20.0, 0.0, 0.0]];
+        let expected = [
+            (0, [RED, BLUE]),
+            (geometry_mode::G_CULL_BACK, [RED, BACKGROUND]),
+            (geometry_mode::G_CULL_FRONT, [BACKGROUND, BLUE]),
+            (geometry_mode::G_CULL_BACK | geometry_mode::G_CULL_FRONT, [BACKGROUND, BACKGROUND])
+        ];
+
+        for (cull, colors) in expected.iter() {
+            let mut builder = ModelBuilder::new();
+            builder.command(g_cleargeometrymode(geometry_mode::G_CULL_BACK | geometry_mode::G_CULL_FRONT));
+            builder.command(g_setgeometrymode(*cull));
+            p_colored_quad(&mut builder, [-20, 0, 0], 8, [0xFF, 0, 0], true);
+            p_colored_quad(&mut builder, [20, 0, 0], 8, [0, 0, 0xFF], false);
+            assert_eq!(p_colors_at(&builder, &points), *colors, "cull {:#X}", cull);
+        }
+    }
+
+    #[test]
+    fn lays_out_contact_sheets ()
+    {
+        // five 3x2 images in 2 columns, with the spacing and the empty last cell left as the background
+        let colors : Vec<[u8; 4]> = (1 ..= 5).map(|i| [i * 40, 0, 0, 0xFF]).collect();
+        let images : Vec<Image> = colors.iter().map(|c| Image::new(3, 2, *c)).collect();
+        let sheet = contact_sheet(&images, 2, BACKGROUND);
+        let spacing = CONTACT_SHEET_SPACING;
+        assert_eq!((sheet.width, sheet.height), (2 * 3 + spacing, 3 * 2 + 2 * spacing));
+
+        for (i, color) in colors.iter().enumerate() {
+            let (x, y) = ((i % 2) * (3 + spacing), (i / 2) * (2 + spacing));
+            assert_eq!(sheet.pixel(x, y), *color, "image {}", i);
+            assert_eq!(sheet.pixel(x + 2, y + 1), *color, "image {}", i);
+        }
+        assert_eq!(sheet.pixel(3, 0), BACKGROUND);
+        assert_eq!(sheet.pixel(0, 2), BACKGROUND);
+        assert_eq!(sheet.pixel(3 + spacing, 2 * (2 + spacing)), BACKGROUND);
+
+        // every cell is the size of the largest image, and the columns are kept between 1 and the image count
+        let mixed = [Image::new(1, 1, RED), Image::new(4, 3, BLUE)];
+        let sheet = contact_sheet(&mixed, 0, BACKGROUND);
+        assert_eq!((sheet.width, sheet.height), (4, 2 * 3 + spacing));
+        assert_eq!(sheet.pixel(1, 0), BACKGROUND);
+        let sheet = contact_sheet(&mixed, 10, BACKGROUND);
+        assert_eq!((sheet.width, sheet.height), (2 * 4 + spacing, 3));
+    }
+
+    #[test]
+    fn turns_the_camera_around_the_model ()
+    {
+        assert_eq!(View::from_name(" Side "), Some(View::Side));
+        assert_eq!(View::from_name("right"), Some(View::Side));
+        assert_eq!(View::from_name("back"), None);
+        assert_eq!(View::Top.angles(), (0.0, 90.0));
+
+        // a quad facing the front, with back faces culled: seen from the front but not from behind
+        let mut builder = ModelBuilder::new();
+        p_colored_quad(&mut builder, [0, 0, 0], 16, [0xFF, 0, 0], true);
+        let model = Model::from_bytes(builder.build()).unwrap();
+        let result = DisplayListInterpreter::run(&model);
+        let options = RenderOptions { width: 32, height: 32, background: BACKGROUND };
+
+        let center = |yaw : f64| -> [u8; 4] {
+            return super::render(&model, &result, &Camera::for_model(&model, yaw, 0.0, 45.0), &options).pixel(16, 16);
+        };
+        assert_eq!(center(0.0), RED);
+        assert_eq!(center(30.0), RED);
+        assert_eq!(center(180.0), BACKGROUND);
+        assert_eq!(center(-150.0), BACKGROUND);
+    }
+}
@@ -0,0 +1,815 @@
+use crate::deflate;
+use crate::errors;
+use crate::errors::Result;
+use crate::rarezip;
+use crate::rom_checksum;
+use crate::rom_checksum::CicChip;
+
+use snafu::ResultExt;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+
+//
+/// The first word of a big endian rom (.z64)
+//
+const Z64_MAGIC : [u8; 4] = [0x80, 0x37, 0x12, 0x40];
+
+//
+/// The first word of a byte swapped rom (.v64)
+//
+const V64_MAGIC : [u8; 4] = [0x37, 0x80, 0x40, 0x12];
+
+//
+/// The first word of a little endian rom (.n64)
+//
+const N64_MAGIC : [u8; 4] = [0x40, 0x12, 0x37, 0x80];
+
+//
+/// Size of the asset table header ([count] [unknown])
+//
+const ASSET_TABLE_HEADER_SIZE : usize = 8;
+
+//
+/// Size of each asset table entry ([offset] [compressed] [flags])
+//
+const ASSET_TABLE_ENTRY_SIZE : usize = 8;
+
+//
+/// Assets are padded to this alignment in the rom
+//
+const ASSET_ALIGNMENT : usize = 8;
+
+//
+/// Where the asset table lives in the known retail roms: (game code, version, offset)
+//
+const KNOWN_ASSET_TABLES : [([u8; 4], u8, usize); 1] = [
+    (*b"NBKE", 0x00, 0x5E90)
+];
+
+//
+/// How the rom file was stored on disk
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ByteOrder
+{
+    //
+    /// Native big endian (.z64)
+    //
+    BigEndian,
+
+    //
+    /// Every pair of bytes swapped (.v64)
+    //
+    ByteSwapped,
+
+    //
+    /// Every 32bit word reversed (.n64)
+    //
+    LittleEndian
+}
+
+impl ByteOrder
+{
+    //
+    /// Convert data between this byte order and big endian (the conversion is its own inverse)
+    //
+    pub fn convert (&self, data: &mut [u8])
+    {
+        match self {
+            ByteOrder::BigEndian => {},
+            ByteOrder::ByteSwapped => {
+                for pair in data.chunks_exact_mut(2) {
+                    pair.swap(0, 1);
+                }
+            },
+            ByteOrder::LittleEndian => {
+                for word in data.chunks_exact_mut(4) {
+                    word.reverse();
+                }
+            }
+        }
+    }
+}
+
+//
+/// What an asset appears to be, going by its (decompressed) contents
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AssetType
+{
+    Unknown,
+    Model,
+    Animation,
+    Sprite,
+    LevelSetup
+}
+
+impl AssetType
+{
+    //
+    /// Guess the type of an asset from its decompressed data.
+    /// These are heuristics on the headers - only models have a real magic.
+    //
+    pub fn sniff (data: &[u8]) -> AssetType
+    {
+        if data.len() < 8 {
+            return AssetType::Unknown;
+        }
+
+        let word = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let h0 = u16::from_be_bytes([data[0], data[1]]);
+        let h1 = u16::from_be_bytes([data[2], data[3]]);
+        let h2 = u16::from_be_bytes([data[4], data[5]]);
+        let h3 = u16::from_be_bytes([data[6], data[7]]);
+
+        // models: 0000000B
+        if word == 0x0000000B {
+            return AssetType::Model;
+        }
+
+        // level setups: 0101 ...
+        if h0 == 0x0101 {
+            return AssetType::LevelSetup;
+        }
+
+        // sprites: [frame count] [format] with a known format
+        let sprite_formats = [0x0001, 0x0004, 0x0020, 0x0040, 0x0100, 0x0400, 0x0800];
+        if h0 > 0 && h0 <= 0x100 && sprite_formats.contains(&h1) {
+            return AssetType::Sprite;
+        }
+
+        // animations: [start frame] [end frame] [element count] [0000]
+        if h0 <= h1 && h1 > 0 && h2 > 0 && h2 < 0x100 && h3 == 0 {
+            return AssetType::Animation;
+        }
+
+        return AssetType::Unknown;
+    }
+}
+
+//
+/// A single entry in the asset table
+//
+#[derive(Copy, Clone, Debug)]
+pub struct AssetEntry
+{
+    //
+    /// The index of the asset in the table
+    //
+    m_index : usize,
+
+    //
+    /// The absolute offset in the rom
+    //
+    m_offset : usize,
+
+    //
+    /// The size of the data in the rom (compressed size, including padding)
+    //
+    m_size : usize,
+
+    //
+    /// If the asset is rarezip compressed
+    //
+    m_compressed : bool,
+
+    //
+    /// The flags from the table (unknown use)
+    //
+    m_flags : u16
+}
+
+impl AssetEntry
+{
+    pub fn index (&self) -> usize {
+        return self.m_index;
+    }
+
+    pub fn offset (&self) -> usize {
+        return self.m_offset;
+    }
+
+    pub fn size (&self) -> usize {
+        return self.m_size;
+    }
+
+    pub fn compressed (&self) -> bool {
+        return self.m_compressed;
+    }
+
+    pub fn flags (&self) -> u16 {
+        return self.m_flags;
+    }
+}
+
+//
+/// A Banjo-Kazooie rom image.
+///
+/// The data is always stored big endian internally, no matter how the file was stored.
+///
+/// The asset table is:
+/// [cccc cccc] [uuuu uuuu]
+/// c: number of entries
+/// u: unknown
+/// followed by c entries of:
+/// [oooo oooo] [pppp] [ffff]
+/// o: offset of the asset from the end of the table
+/// p: 1 if the asset is compressed
+/// f: flags
+/// The size of an asset is the distance to the next entry, so the last entry only marks the end.
+//
+pub struct Rom
+{
+    m_byte_order: ByteOrder,
+    m_asset_table_offset: usize,
+    m_assets: Vec<AssetEntry>,
+    m_data: Vec<u8>
+}
+
+impl Rom
+{
+    // --- public static
+
+    //
+    /// Load a rom from the given file, in any byte order
+    //
+    pub fn load (filename : String) -> Result<Rom>
+    {
+        let mut file = File::open(filename).context(errors::IOError)?;
+
+        let mut data = Vec::<u8>::new();
+        file.read_to_end(&mut data).context(errors::IOError)?;
+
+        return Rom::from_bytes(data);
+    }
+
+    //
+    /// Load a rom from a buffer, in any byte order
+    //
+    pub fn from_bytes (mut data : Vec<u8>) -> Result<Rom>
+    {
+        if data.len() < 0x1000 {
+            return errors::InvalidRom { reason: format!("only {:#X} bytes, too small for a rom", data.len()) }.fail();
+        }
+
+        let magic = [data[0], data[1], data[2], data[3]];
+        let byte_order = match magic {
+            Z64_MAGIC => ByteOrder::BigEndian,
+            V64_MAGIC => ByteOrder::ByteSwapped,
+            N64_MAGIC => ByteOrder::LittleEndian,
+            _ => {
+                return errors::InvalidRom { reason: format!("unknown header {:02X?}", &data[0..4]) }.fail();
+            }
+        };
+        byte_order.convert(&mut data);
+
+        let asset_table_offset = match Rom::p_find_asset_table(&data) {
+            Some(o) => o,
+            None => {
+                return errors::InvalidRom { reason: "could not find the asset table".to_string() }.fail();
+            }
+        };
+
+        let assets = Rom::p_parse_asset_table(&data, asset_table_offset);
+
+        return Ok(Rom {
+            m_byte_order: byte_order,
+            m_asset_table_offset: asset_table_offset,
+            m_assets: assets,
+            m_data: data
+        });
+    }
+
+    // --- public properties
+
+    //
+    /// How the rom was stored on disk
+    //
+    pub fn byte_order (&self) -> ByteOrder
+    { return self.m_byte_order; }
+
+    //
+    /// The offset of the asset table in the rom
+    //
+    pub fn asset_table_offset (&self) -> usize
+    { return self.m_asset_table_offset; }
+
+    //
+    /// Every asset in the table
+    //
+    pub fn assets (&self) -> &Vec<AssetEntry>
+    { return &self.m_assets; }
+
+    //
+    /// The internal name from the rom header
+    //
+    pub fn internal_name (&self) -> String
+    {
+        return String::from_utf8_lossy(&self.m_data[0x20..0x34]).trim_end_matches([' ', '\0']).to_string();
+    }
+
+    //
+    /// The game code from the rom header (ex. NBKE)
+    //
+    pub fn game_code (&self) -> String
+    {
+        return String::from_utf8_lossy(&self.m_data[0x3B..0x3F]).to_string();
+    }
+
+    //
+    /// The version from the rom header
+    //
+    pub fn version (&self) -> u8
+    { return self.m_data[0x3F]; }
+
+    //
+    /// The lockout chip, going by the boot code (None if the boot code is unknown)
+    //
+    pub fn cic (&self) -> Option<CicChip>
+    { return CicChip::detect(&self.m_data); }
+
+    //
+    /// The checksum stored in the header
+    //
+    pub fn checksum (&self) -> (u32, u32)
+    {
+        let offset = rom_checksum::CHECKSUM_OFFSET;
+        return (p_read_u32(&self.m_data, offset), p_read_u32(&self.m_data, offset + 4));
+    }
+
+    // --- public helpers
+
+    //
+    /// The raw data for an asset, as stored in the rom
+    //
+    pub fn asset_raw (&self, index : usize) -> Result<&[u8]>
+    {
+        let entry = self.p_entry(index)?;
+        return Ok(&self.m_data[entry.m_offset .. entry.m_offset + entry.m_size]);
+    }
+
+    //
+    /// The data for an asset, decompressed if needed
+    //
+    pub fn asset_data (&self, index : usize) -> Result<Vec<u8>>
+    {
+        let entry = self.p_entry(index)?;
+        let raw = self.asset_raw(index)?;
+
+        if entry.m_compressed {
+            return rarezip::decompress(raw);
+        }
+
+        return Ok(raw.to_vec());
+    }
+
+    //
+    /// Guess what an asset is. Assets which fail to decompress are unknown.
+    //
+    pub fn asset_type (&self, index : usize) -> AssetType
+    {
+        return match self.asset_data(index) {
+            Ok(data) => AssetType::sniff(&data),
+            Err(_) => AssetType::Unknown
+        };
+    }
+
+    //
+    /// Replace an asset, shifting every later asset and updating the asset table.
+    ///
+    /// Compressed assets are recompressed the way the retail rom was (data which is already
+    /// a rarezip container is stored as is).
+    ///
+    /// The game reads whatever comes after the asset data from fixed offsets, so neither it nor
+    /// the asset data can be moved, and the rom isnt made any bigger. An asset can only grow
+    /// into the padding (0x00 or 0xFF bytes) after the last asset, and fails with AssetDoesNotFit
+    /// if there isnt enough. A smaller asset moves the later ones back and zeroes the freed space.
+    //
+    pub fn replace_asset (&mut self, index : usize, data : &[u8]) -> Result<()>
+    {
+        let entry = self.p_entry(index)?;
+
+        let mut raw = if entry.m_compressed && !rarezip::is_compressed(data) {
+            rarezip::compress(data, deflate::RETAIL_LEVEL)
+        } else {
+            data.to_vec()
+        };
+        raw.resize(raw.len().div_ceil(ASSET_ALIGNMENT) * ASSET_ALIGNMENT, 0);
+
+        let region_end = match self.m_assets.last() {
+            Some(last) => last.m_offset + last.m_size,
+            None => entry.m_offset + entry.m_size
+        };
+        let old_end = entry.m_offset + entry.m_size;
+
+        if raw.len() > entry.m_size {
+            let growth = raw.len() - entry.m_size;
+            let padding = self.m_data[region_end ..].iter().take_while(|b| **b == 0x00 || **b == 0xFF).count();
+            if growth > padding {
+                return errors::AssetDoesNotFit { index, size: raw.len(), available: entry.m_size + padding }.fail();
+            }
+
+            self.m_data.copy_within(old_end .. region_end, old_end + growth);
+        } else {
+            let shrink = entry.m_size - raw.len();
+            self.m_data.copy_within(old_end .. region_end, old_end - shrink);
+            for b in self.m_data[region_end - shrink .. region_end].iter_mut() {
+                *b = 0;
+            }
+        }
+        self.m_data[entry.m_offset .. entry.m_offset + raw.len()].copy_from_slice(&raw);
+
+        // every later entry (and the end marker) moves by the change in size
+        let count = p_read_u32(&self.m_data, self.m_asset_table_offset) as usize;
+        for i in index + 1 .. count {
+            let table_entry = self.m_asset_table_offset + ASSET_TABLE_HEADER_SIZE + i * ASSET_TABLE_ENTRY_SIZE;
+            let asset_offset = p_read_u32(&self.m_data, table_entry) as usize;
+            let moved = (asset_offset + raw.len() - entry.m_size) as u32;
+            self.m_data[table_entry .. table_entry + 4].copy_from_slice(&moved.to_be_bytes());
+        }
+
+        self.m_assets = Rom::p_parse_asset_table(&self.m_data, self.m_asset_table_offset);
+        return Ok(());
+    }
+
+    //
+    /// Recalculate the header checksum so the rom boots.
+    /// Roms with unknown boot code are assumed to use the 6103 like Banjo-Kazooie.
+    //
+    pub fn fix_checksum (&mut self) -> Result<(u32, u32)>
+    {
+        let cic = self.cic().unwrap_or(CicChip::Cic6103);
+        let (crc1, crc2) = rom_checksum::calculate(&self.m_data, cic)?;
+
+        let offset = rom_checksum::CHECKSUM_OFFSET;
+        self.m_data[offset .. offset + 4].copy_from_slice(&crc1.to_be_bytes());
+        self.m_data[offset + 4 .. offset + 8].copy_from_slice(&crc2.to_be_bytes());
+        return Ok((crc1, crc2));
+    }
+
+    //
+    /// Save the rom to the given file, in the byte order it was loaded in
+    //
+    pub fn save (&self, filename : String) -> Result<()>
+    {
+        let mut data = self.m_data.clone();
+        self.m_byte_order.convert(&mut data);
+
+        let mut file = File::create(filename).context(errors::IOError)?;
+        file.write_all(&data).context(errors::IOError)?;
+        return Ok(());
+    }
+
+    // --- private
+
+    fn p_entry (&self, index : usize) -> Result<AssetEntry>
+    {
+        return match self.m_assets.get(index) {
+            Some(e) => Ok(*e),
+            None => errors::AssetIndexOutOfRange { index, count: self.m_assets.len() }.fail()
+        };
+    }
+
+    //
+    /// Find the asset table - use the known location for the rom if there is one,
+    /// otherwise scan for something shaped like the table
+    //
+    fn p_find_asset_table (data : &[u8]) -> Option<usize>
+    {
+        let game_code = &data[0x3B..0x3F];
+        let version = data[0x3F];
+        for (known_code, known_version, offset) in KNOWN_ASSET_TABLES.iter() {
+            if game_code == known_code && version == *known_version && Rom::p_is_asset_table(data, *offset) {
+                return Some(*offset);
+            }
+        }
+
+        // the table is after the boot code
+        let mut offset = 0x1000;
+        while offset + ASSET_TABLE_HEADER_SIZE < data.len() {
+            if Rom::p_is_asset_table(data, offset) {
+                return Some(offset);
+            }
+            offset += 8;
+        }
+
+        return None;
+    }
+
+    //
+    /// If there looks to be a valid asset table at the given offset
+    //
+    fn p_is_asset_table (data : &[u8], offset : usize) -> bool
+    {
+        if offset + ASSET_TABLE_HEADER_SIZE > data.len() {
+            return false;
+        }
+
+        let count = p_read_u32(data, offset) as usize;
+        if !(2..=0x10000).contains(&count) {
+            return false;
+        }
+
+        let data_start = offset + ASSET_TABLE_HEADER_SIZE + count * ASSET_TABLE_ENTRY_SIZE;
+        if data_start > data.len() {
+            return false;
+        }
+
+        let mut last_offset = 0;
+        let mut compressed_seen = 0;
+        for i in 0 .. count {
+            let entry = offset + ASSET_TABLE_HEADER_SIZE + i * ASSET_TABLE_ENTRY_SIZE;
+            let asset_offset = p_read_u32(data, entry) as usize;
+            let compressed = p_read_u16(data, entry + 4);
+
+            if (i == 0 && asset_offset != 0) || asset_offset < last_offset || compressed > 1 {
+                return false;
+            }
+            if data_start + asset_offset > data.len() {
+                return false;
+            }
+
+            // spot check the first few compressed assets actually are compressed
+            if compressed == 1 && i + 1 < count && compressed_seen < 4 {
+                if !rarezip::is_compressed(&data[data_start + asset_offset ..]) {
+                    return false;
+                }
+                compressed_seen += 1;
+            }
+
+            last_offset = asset_offset;
+        }
+
+        return compressed_seen > 0;
+    }
+
+    fn p_parse_asset_table (data : &[u8], offset : usize) -> Vec<AssetEntry>
+    {
+        let count = p_read_u32(data, offset) as usize;
+        let data_start = offset + ASSET_TABLE_HEADER_SIZE + count * ASSET_TABLE_ENTRY_SIZE;
+
+        let mut assets = Vec::<AssetEntry>::new();
+        for i in 0 .. count - 1 {
+            let entry = offset + ASSET_TABLE_HEADER_SIZE + i * ASSET_TABLE_ENTRY_SIZE;
+            let asset_offset = p_read_u32(data, entry) as usize;
+            let next_offset = p_read_u32(data, entry + ASSET_TABLE_ENTRY_SIZE) as usize;
+
+            assets.push(AssetEntry {
+                m_index: i,
+                m_offset: data_start + asset_offset,
+                m_size: next_offset - asset_offset,
+                m_compressed: p_read_u16(data, entry + 4) == 1,
+                m_flags: p_read_u16(data, entry + 6)
+            });
+        }
+
+        return assets;
+    }
+}
+
+fn p_read_u32 (data : &[u8], offset : usize) -> u32
+{
+    return u32::from_be_bytes([data[offset], data[offset+1], data[offset+2], data[offset+3]]);
+}
+
+fn p_read_u16 (data : &[u8], offset : usize) -> u16
+{
+    return u16::from_be_bytes([data[offset], data[offset+1]]);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_models::ModelBuilder;
+
+    //
+    /// A rom with the asset table at table_offset, holding the assets (data, compressed)
+    //
+    fn p_rom (table_offset : usize, game_code : &[u8; 4], assets : &[(Vec<u8>, bool)]) -> Vec<u8>
+    {
+        let mut rom = vec![0u8; 0x20000];
+        rom[0 .. 4].copy_from_slice(&Z64_MAGIC);
+        rom[0x20 .. 0x34].copy_from_slice(b"BANJO-KAZOOIE       ");
+        rom[0x3B .. 0x3F].copy_from_slice(game_code);
+
+        let mut table = Vec::<u8>::new();
+        let mut data = Vec::<u8>::new();
+        table.extend_from_slice(&(assets.len() as u32 + 1).to_be_bytes());
+        table.extend_from_slice(&[0; 4]);
+        for (asset, compressed) in assets {
+            table.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            table.extend_from_slice(&(*compressed as u16).to_be_bytes());
+            table.extend_from_slice(&[0, 0]);
+
+            let mut stored = if *compressed { rarezip::compress(asset, deflate::RETAIL_LEVEL) } else { asset.clone() };
+            stored.resize(stored.len().div_ceil(ASSET_ALIGNMENT) * ASSET_ALIGNMENT, 0);
+            data.extend_from_slice(&stored);
+        }
+        table.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        table.extend_from_slice(&[0; 4]);
+
+        rom[table_offset .. table_offset + table.len()].copy_from_slice(&table);
+        rom[table_offset + table.len() .. table_offset + table.len() + data.len()].copy_from_slice(&data);
+        return rom;
+    }
+
+    fn p_assets () -> Vec<(Vec<u8>, bool)>
+    {
+        return vec![
+            (ModelBuilder::new().build(), true),
+            (b"RAWDATA!RAWDATA!".to_vec(), false),
+            ([0x01, 0x01].iter().copied().chain(std::iter::repeat(0x22).take(0x40)).collect(), true)
+        ];
+    }
+
+    #[test]
+    fn normalizes_every_byte_order ()
+    {
+        let z64 = p_rom(0x5E90, b"NBKE", &p_assets());
+        let v64 : Vec<u8> = z64.chunks(2).flat_map(|pair| vec![pair[1], pair[0]]).collect();
+        let n64 : Vec<u8> = z64.chunks(4).flat_map(|word| vec![word[3], word[2], word[1], word[0]]).collect();
+
+        for (data, byte_order) in [(z64, ByteOrder::BigEndian), (v64, ByteOrder::ByteSwapped), (n64, ByteOrder::LittleEndian)] {
+            let rom = Rom::from_bytes(data).unwrap();
+            assert_eq!(rom.byte_order(), byte_order);
+            assert_eq!(rom.internal_name(), "BANJO-KAZOOIE");
+            assert_eq!(rom.game_code(), "NBKE");
+            assert_eq!(rom.assets().len(), 3);
+            assert_eq!(rom.asset_data(1).unwrap(), b"RAWDATA!RAWDATA!");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_headers ()
+    {
+        let mut data = p_rom(0x5E90, b"NBKE", &p_assets());
+        data[0] = 0x12;
+        assert!(Rom::from_bytes(data).is_err());
+        assert!(Rom::from_bytes(Z64_MAGIC.to_vec()).is_err());
+    }
+
+    #[test]
+    fn finds_the_asset_table ()
+    {
+        // the known location for the game, then a scan for other games or versions
+        assert_eq!(Rom::from_bytes(p_rom(0x5E90, b"NBKE", &p_assets())).unwrap().asset_table_offset(), 0x5E90);
+        assert_eq!(Rom::from_bytes(p_rom(0x7000, b"NBKP", &p_assets())).unwrap().asset_table_offset(), 0x7000);
+        assert_eq!(Rom::from_bytes(p_rom(0x7000, b"NBKE", &p_assets())).unwrap().asset_table_offset(), 0x7000);
+
+        // without a compressed asset it doesnt look like the table
+        let plain = vec![(b"RAWDATA!".to_vec(), false), (b"RAWDATA!".to_vec(), false)];
+        assert!(Rom::from_bytes(p_rom(0x7000, b"NBKP", &plain)).is_err());
+    }
+
+    #[test]
+    fn reads_assets ()
+    {
+        let assets = p_assets();
+        let rom = Rom::from_bytes(p_rom(0x5E90, b"NBKE", &assets)).unwrap();
+
+        for (i, (data, compressed)) in assets.iter().enumerate() {
+            assert_eq!(rom.assets()[i].compressed(), *compressed);
+            assert_eq!(&rom.asset_data(i).unwrap(), data);
+            assert_eq!(rarezip::is_compressed(rom.asset_raw(i).unwrap()), *compressed);
+        }
+        assert_eq!(rom.assets()[1].offset(), rom.assets()[0].offset() + rom.assets()[0].size());
+    }
+
+    #[test]
+    fn sniffs_asset_types ()
+    {
+        let rom = Rom::from_bytes(p_rom(0x5E90, b"NBKE", &p_assets())).unwrap();
+        assert_eq!(rom.asset_type(0), AssetType::Model);
+        assert_eq!(rom.asset_type(1), AssetType::Unknown);
+        assert_eq!(rom.asset_type(2), AssetType::LevelSetup);
+
+        // the type comes from the decompressed data, a compressed asset is just data
+        assert_eq!(AssetType::sniff(&ModelBuilder::new().build()), AssetType::Model);
+        assert_eq!(AssetType::sniff(rom.asset_raw(0).unwrap()), AssetType::Unknown);
+        assert_eq!(AssetType::sniff(&[0x00, 0x00]), AssetType::Unknown);
+    }
+
+    #[test]
+    fn rejects_assets_past_the_table ()
+    {
+        let mut rom = Rom::from_bytes(p_rom(0x5E90, b"NBKE", &p_assets())).unwrap();
+        for result in [rom.asset_raw(3).map(|_| ()), rom.asset_data(3).map(|_| ()), rom.replace_asset(3, &[0; 8])] {
+            match result {
+                Err(errors::Error::AssetIndexOutOfRange { index, count }) => assert_eq!((index, count), (3, 3)),
+                other => panic!("expected AssetIndexOutOfRange, got {:?}", other)
+            }
+        }
+    }
+
+    //
+    /// A rom whose last asset is raw, so the end of the asset data is easy to find
+    //
+    fn p_patch_rom () -> Rom
+    {
+        let assets = vec![
+            (ModelBuilder::new().build(), true),
+            (b"RAWDATA!RAWDATA!".to_vec(), false),
+            (b"LASTDATALASTDATA".to_vec(), false)
+        ];
+        return Rom::from_bytes(p_rom(0x5E90, b"NBKE", &assets)).unwrap();
+    }
+
+    fn p_region_end (rom : &Rom) -> usize
+    {
+        let last = rom.assets().last().unwrap();
+        return last.offset() + last.size();
+    }
+
+    #[test]
+    fn replaces_an_asset_of_the_same_size ()
+    {
+        let mut rom = p_patch_rom();
+        let before : Vec<usize> = rom.assets().iter().map(|a| a.offset()).collect();
+
+        rom.replace_asset(1, b"NEWDATA!NEWDATA!").unwrap();
+        assert_eq!(rom.assets().iter().map(|a| a.offset()).collect::<Vec<usize>>(), before);
+        assert_eq!(rom.asset_data(0).unwrap(), ModelBuilder::new().build());
+        assert_eq!(rom.asset_data(1).unwrap(), b"NEWDATA!NEWDATA!");
+        assert_eq!(rom.asset_data(2).unwrap(), b"LASTDATALASTDATA");
+    }
+
+    #[test]
+    fn shrinking_an_asset_moves_the_later_ones_back ()
+    {
+        let mut rom = p_patch_rom();
+        let (offset, end) = (rom.assets()[2].offset(), p_region_end(&rom));
+
+        rom.replace_asset(1, b"SMALLER!").unwrap();
+        assert_eq!(rom.assets()[1].size(), 8);
+        assert_eq!(rom.assets()[2].offset(), offset - 8);
+        assert_eq!(rom.asset_data(1).unwrap(), b"SMALLER!");
+        assert_eq!(rom.asset_data(2).unwrap(), b"LASTDATALASTDATA");
+
+        // the end of the last asset was moved back, leaving zeroes behind
+        assert_eq!(p_region_end(&rom), end - 8);
+        assert_eq!(&rom.m_data[end - 16 .. end], b"LASTDATA\0\0\0\0\0\0\0\0");
+    }
+
+    #[test]
+    fn growing_an_asset_uses_the_padding ()
+    {
+        let mut rom = p_patch_rom();
+        let (offset, end) = (rom.assets()[2].offset(), p_region_end(&rom));
+
+        // compressed assets are recompressed
+        let mut builder = ModelBuilder::new();
+        builder.vertex([1, 2, 3], [0, 0], [0xFF; 3], 0xFF);
+        let model = builder.build();
+        rom.replace_asset(0, &model).unwrap();
+        assert_eq!(rom.asset_data(0).unwrap(), model);
+        assert!(rarezip::is_compressed(rom.asset_raw(0).unwrap()));
+
+        let moved = rom.assets()[2].offset() - offset;
+        rom.replace_asset(1, b"RAWDATA!RAWDATA!GROWING!").unwrap();
+        assert_eq!(rom.assets()[2].offset(), offset + moved + 8);
+        assert_eq!(p_region_end(&rom), end + moved + 8);
+        assert_eq!(rom.asset_data(1).unwrap(), b"RAWDATA!RAWDATA!GROWING!");
+        assert_eq!(rom.asset_data(2).unwrap(), b"LASTDATALASTDATA");
+    }
+
+    #[test]
+    fn rejects_growth_past_the_padding ()
+    {
+        // only 8 bytes of padding before data that cant be moved
+        let mut rom = p_patch_rom();
+        let end = p_region_end(&rom);
+        for b in rom.m_data[end + 8 ..].iter_mut() {
+            *b = 0x5A;
+        }
+        let original = rom.m_data.clone();
+
+        match rom.replace_asset(1, &[0x11; 32]) {
+            Err(errors::Error::AssetDoesNotFit { index, size, available }) => assert_eq!((index, size, available), (1, 32, 24)),
+            other => panic!("expected AssetDoesNotFit, got {:?}", other)
+        }
+        assert!(rom.m_data == original);
+
+        // exactly filling it is fine
+        rom.replace_asset(1, &[0x11; 24]).unwrap();
+        assert_eq!(p_region_end(&rom), end + 8);
+        assert_eq!(rom.asset_data(2).unwrap(), b"LASTDATALASTDATA");
+        assert_eq!(rom.m_data[end + 8], 0x5A);
+    }
+
+    #[test]
+    fn repairs_the_checksum ()
+    {
+        // unknown boot code is treated as the 6103
+        let mut data = p_rom(0x5E90, b"NBKE", &p_assets());
+        data.resize(0x101000, 0);
+        let mut rom = Rom::from_bytes(data).unwrap();
+        assert_eq!(rom.cic(), None);
+
+        let expected = rom_checksum::calculate(&rom.m_data, CicChip::Cic6103).unwrap();
+        assert_eq!(rom.fix_checksum().unwrap(), expected);
+        assert_eq!(p_read_u32(&rom.m_data, rom_checksum::CHECKSUM_OFFSET), expected.0);
+        assert_eq!(p_read_u32(&rom.m_data, rom_checksum::CHECKSUM_OFFSET + 4), expected.1);
+    }
+}
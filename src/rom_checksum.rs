@@ -0,0 +1,150 @@
+use crate::crc32;
+use crate::errors;
+use crate::errors::Result;
+
+// The N64 header checksum (the two words at 0x10 and 0x14).
+// The boot code checks these against the first megabyte after the boot code,
+// so any change to the rom needs them recalculated or it wont boot.
+
+//
+/// Where the checksummed data starts
+//
+const CHECKSUM_START : usize = 0x1000;
+
+//
+/// How much data is checksummed
+//
+const CHECKSUM_LENGTH : usize = 0x100000;
+
+//
+/// Where the checksum is stored in the header
+//
+pub const CHECKSUM_OFFSET : usize = 0x10;
+
+//
+/// The lockout chip the rom was made for. Each one seeds the checksum differently.
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CicChip
+{
+    Cic6101,
+    Cic6102,
+    Cic6103,
+    Cic6105,
+    Cic6106
+}
+
+impl CicChip
+{
+    //
+    /// Work out the chip from the CRC-32 of the boot code (0x40 - 0x1000).
+    /// Returns None if the boot code isnt a known one.
+    //
+    pub fn detect (data : &[u8]) -> Option<CicChip>
+    {
+        if data.len() < CHECKSUM_START {
+            return None;
+        }
+
+        return match crc32::crc32(&data[0x40 .. CHECKSUM_START]) {
+            0x6170A4A1 => Some(CicChip::Cic6101),
+            0x90BB6CB5 => Some(CicChip::Cic6102),
+            0x0B050EE0 => Some(CicChip::Cic6103), // Banjo-Kazooie
+            0x98BC2C86 => Some(CicChip::Cic6105),
+            0xACC8580A => Some(CicChip::Cic6106),
+            _ => None
+        };
+    }
+
+    fn seed (&self) -> u32
+    {
+        return match self {
+            CicChip::Cic6101 | CicChip::Cic6102 => 0xF8CA4DDC,
+            CicChip::Cic6103 => 0xA3886759,
+            CicChip::Cic6105 => 0xDF26F436,
+            CicChip::Cic6106 => 0x1FEA617A
+        };
+    }
+}
+
+//
+/// Calculate the header checksum for a (big endian) rom
+//
+pub fn calculate (data : &[u8], cic : CicChip) -> Result<(u32, u32)>
+{
+    if data.len() < CHECKSUM_START + CHECKSUM_LENGTH {
+        return errors::InvalidRom { reason: format!("only {:#X} bytes, too small to checksum", data.len()) }.fail();
+    }
+
+    let seed = cic.seed();
+    let (mut t1, mut t2, mut t3, mut t4, mut t5, mut t6) = (seed, seed, seed, seed, seed, seed);
+
+    for i in (CHECKSUM_START .. CHECKSUM_START + CHECKSUM_LENGTH).step_by(4) {
+        let d = p_read_u32(data, i);
+
+        let (sum, overflow) = t6.overflowing_add(d);
+        if overflow {
+            t4 = t4.wrapping_add(1);
+        }
+        t6 = sum;
+        t3 ^= d;
+
+        let r = d.rotate_left(d & 0x1F);
+        t5 = t5.wrapping_add(r);
+
+        if t2 > d {
+            t2 ^= r;
+        } else {
+            t2 ^= t6 ^ d;
+        }
+
+        if cic == CicChip::Cic6105 {
+            t1 = t1.wrapping_add(p_read_u32(data, 0x0750 + (i & 0xFF)) ^ d);
+        } else {
+            t1 = t1.wrapping_add(t5 ^ d);
+        }
+    }
+
+    return Ok(match cic {
+        CicChip::Cic6103 => ((t6 ^ t4).wrapping_add(t3), (t5 ^ t2).wrapping_add(t1)),
+        CicChip::Cic6106 => (t6.wrapping_mul(t4).wrapping_add(t3), t5.wrapping_mul(t2).wrapping_add(t1)),
+        _ => (t6 ^ t4 ^ t3, t5 ^ t2 ^ t1)
+    });
+}
+
+fn p_read_u32 (data : &[u8], offset : usize) -> u32
+{
+    return u32::from_be_bytes([data[offset], data[offset+1], data[offset+2], data[offset+3]]);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //
+    /// A rom just big enough to checksum, with a repeating pattern (every byte is its offset mod 251)
+    //
+    fn p_rom () -> Vec<u8>
+    {
+        return (0 .. CHECKSUM_START + CHECKSUM_LENGTH).map(|i| (i % 251) as u8).collect();
+    }
+
+    #[test]
+    fn calculates_known_checksums ()
+    {
+        // worked out separately with the algorithm from n64crc
+        let rom = p_rom();
+        assert_eq!(calculate(&rom, CicChip::Cic6102).unwrap(), (0xC91EE9E4, 0xDDF56886));
+        assert_eq!(calculate(&rom, CicChip::Cic6101).unwrap(), (0xC91EE9E4, 0xDDF56886));
+        assert_eq!(calculate(&rom, CicChip::Cic6103).unwrap(), (0x9BE3C391, 0x46F35086));
+    }
+
+    #[test]
+    fn rejects_roms_too_small_to_checksum ()
+    {
+        let rom = p_rom();
+        assert!(calculate(&rom[.. rom.len() - 4], CicChip::Cic6102).is_err());
+        assert_eq!(CicChip::detect(&rom[.. 0x100]), None);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::segment_table;
+use crate::texture::TextureAddress;
+use crate::segment_table::{SegmentTable, SEGMENT_COUNT};
+
+//
+/// The segment the game points at the verticies in the vertex store
+//
+pub const VERTEX_SEGMENT : u8 = 0x01;
+
+//
+/// The segment the game points at the texture data (after the texture headers)
+//
+pub const TEXTURE_SEGMENT : u8 = 0x02;
+
+//
+/// The segment the game points at the display list commands
+//
+pub const DISPLAY_LIST_SEGMENT : u8 = 0x03;
+
+//
+/// Maps each segment to where it starts in the model data, so segmented
+/// addresses ([ss oooooo]) can be turned into offsets in the model.
+///
+/// Offsets are relative to the start of the model and can be negative, since
+/// a segment from an RDRAM dump can start before the model does.
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SegmentMap
+{
+    m_segments: [Option<i64>; SEGMENT_COUNT],
+
+    //
+    /// Where the model is in RDRAM, for resolving plain virtual addresses (models from memory only)
+    //
+    m_base_address: Option<u32>
+}
+
+impl SegmentMap
+{
+    //
+    /// Create an empty segment map
+    //
+    pub fn new () -> SegmentMap
+    {
+        return SegmentMap::default();
+    }
+
+    //
+    /// The map the game sets up when drawing a model, given where its sections are
+    //
+    pub fn for_model_layout (vertex_data_offset : usize, texture_data_offset : usize, display_list_commands_offset : usize) -> SegmentMap
+    {
+        let mut map = SegmentMap::new();
+        map.set(VERTEX_SEGMENT, vertex_data_offset as i64);
+        map.set(TEXTURE_SEGMENT, texture_data_offset as i64);
+        map.set(DISPLAY_LIST_SEGMENT, display_list_commands_offset as i64);
+        return map;
+    }
+
+    //
+    /// Override the map with an RDRAM segment table, for a model which was at base_address in RDRAM
+    //
+    pub fn apply_segment_table (&mut self, table : &SegmentTable, base_address : u32)
+    {
+        let base = segment_table::physical_address(base_address) as i64;
+        for segment in 0 .. SEGMENT_COUNT as u8 {
+            if let Some(address) = table.get(segment) {
+                self.set(segment, segment_table::physical_address(address) as i64 - base);
+            }
+        }
+        self.m_base_address = Some(base_address);
+    }
+
+    //
+    /// Set where a segment starts, relative to the start of the model
+    //
+    pub fn set (&mut self, segment : u8, offset : i64)
+    {
+        self.m_segments[segment as usize & 0x0F] = Some(offset);
+    }
+
+    //
+    /// Where a segment starts relative to the start of the model (None if it isnt mapped)
+    //
+    pub fn get (&self, segment : u8) -> Option<i64>
+    {
+        return self.m_segments[segment as usize & 0x0F];
+    }
+
+    //
+    /// Resolve a segmented address to an offset relative to the start of the model.
+    /// The result can be outside the model, so check it against the model size.
+    //
+    pub fn resolve (&self, segmented_address : u32) -> Option<i64>
+    {
+        if segmented_address & 0x80000000 != 0 {
+            let base = self.m_base_address?;
+            return Some(segment_table::physical_address(segmented_address) as i64 - segment_table::physical_address(base) as i64);
+        }
+
+        let segment = ((segmented_address >> 24) & 0x0F) as u8;
+        return Some(self.get(segment)? + (segmented_address & 0x00FFFFFF) as i64);
+    }
+}
+
+//
+/// What a segmented address in a display list command points at
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResolvedAddress
+{
+    //
+    /// A range of verticies in the vertex store
+    //
+    Verticies { first: usize, count: usize },
+
+    //
+    /// Where it lands in the model's textures
+    //
+    Texture(TextureAddress),
+
+    //
+    /// A command in the display list
+    //
+    DisplayList { command_index: usize },
+
+    //
+    /// Somewhere in the model which isnt the start of anything we know about
+    //
+    Offset { offset: usize },
+
+    //
+    /// The segment isnt mapped, or the address is outside the model
+    //
+    Unresolved { segmented_address: u32 }
+}
+
+impl ResolvedAddress
+{
+    //
+    /// A short description for listings (ex. texture_3)
+    //
+    pub fn describe (&self) -> String
+    {
+        return match self {
+            ResolvedAddress::Verticies { first, count } => format!("verticies {}-{}", first, first + (*count).max(1) - 1),
+            ResolvedAddress::Texture(texture) => texture.describe(),
+            ResolvedAddress::DisplayList { command_index } => format!("command {}", command_index),
+            ResolvedAddress::Offset { offset } => format!("model offset {:#X}", offset),
+            ResolvedAddress::Unresolved { segmented_address } => format!("{:#X} is outside the model", segmented_address)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn resolves_the_model_segments ()
+    {
+        let map = SegmentMap::for_model_layout(0x200, 0x50, 0x100);
+        assert_eq!(map.resolve(0x0100_0010), Some(0x210));
+        assert_eq!(map.resolve(0x0200_0000), Some(0x50));
+        assert_eq!(map.resolve(0x0300_0018), Some(0x118));
+
+        // only the low 4 bits of the segment count, like the RSP
+        assert_eq!(map.resolve(0x1100_0010), Some(0x210));
+
+        // there is nowhere for plain or unmapped addresses to go without a base address
+        assert_eq!(map.resolve(0x0500_0000), None);
+        assert_eq!(map.resolve(0x0000_0010), None);
+        assert_eq!(map.resolve(0x8020_0000), None);
+    }
+
+    #[test]
+    fn offsets_run_past_the_segment ()
+    {
+        // the map doesnt know how big the sections are, the model checks the offset against its size
+        let map = SegmentMap::for_model_layout(0x200, 0x50, 0x100);
+        assert_eq!(map.resolve(0x01FF_FFFF), Some(0x200 + 0xFF_FFFF));
+    }
+
+    #[test]
+    fn applies_a_segment_table ()
+    {
+        // KSEG0, KSEG1 and physical addresses are all the same place in RDRAM
+        let mut table = SegmentTable::new();
+        table.set(VERTEX_SEGMENT, 0x8020_1200);
+        table.set(TEXTURE_SEGMENT, 0xA020_0100);
+        table.set(0x06, 0x0010_0000);
+
+        let mut map = SegmentMap::for_model_layout(0x200, 0x50, 0x100);
+        map.apply_segment_table(&table, 0x8020_0000);
+        assert_eq!(map.get(VERTEX_SEGMENT), Some(0x1200));
+        assert_eq!(map.get(TEXTURE_SEGMENT), Some(0x100));
+        assert_eq!(map.get(DISPLAY_LIST_SEGMENT), Some(0x100));
+
+        // a segment before the model is a negative offset
+        assert_eq!(map.get(0x06), Some(0x0010_0000 - 0x0020_0000));
+        assert_eq!(map.resolve(0x0610_0000), Some(0));
+
+        // virtual addresses are relative to the base address, whichever way they are mapped
+        assert_eq!(map.resolve(0x8020_0040), Some(0x40));
+        assert_eq!(map.resolve(0xA020_0040), Some(0x40));
+        assert_eq!(map.resolve(0x801F_FFF0), Some(-0x10));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::errors;
+use crate::errors::Result;
+
+//
+/// The RSP has 16 segment registers
+//
+pub const SEGMENT_COUNT : usize = 16;
+
+//
+/// Mask to turn a KSEG0/KSEG1 virtual address (0x80xxxxxx / 0xA0xxxxxx) into a physical one
+//
+const PHYSICAL_ADDRESS_MASK : u32 = 0x1FFFFFFF;
+
+//
+/// The segment table the game had set up when the display list ran:
+/// where each segment starts in RDRAM.
+///
+/// A segmented address is [ss oooooo]
+/// s: segment number
+/// o: offset from the start of the segment
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SegmentTable
+{
+    m_segments: [Option<u32>; SEGMENT_COUNT]
+}
+
+impl SegmentTable
+{
+    //
+    /// Create an empty segment table
+    //
+    pub fn new () -> SegmentTable
+    {
+        return SegmentTable::default();
+    }
+
+    //
+    /// Parse a list of segment=address entries (ex. ["1=0x80201230", "2=0x80204000"])
+    //
+    pub fn parse (entries : &[String]) -> Result<SegmentTable>
+    {
+        let mut table = SegmentTable::new();
+        for entry in entries {
+            let (segment, address) = match entry.split_once('=') {
+                Some(parts) => parts,
+                None => {
+                    return errors::InvalidSegmentTable { reason: format!("'{}' should be segment=address", entry) }.fail();
+                }
+            };
+
+            let segment = match parse_number(segment) {
+                Some(s) if (s as usize) < SEGMENT_COUNT => s as u8,
+                _ => {
+                    return errors::InvalidSegmentTable { reason: format!("'{}' is not a segment (0-15)", segment) }.fail();
+                }
+            };
+
+            let address = match parse_number(address) {
+                Some(a) => a,
+                None => {
+                    return errors::InvalidSegmentTable { reason: format!("'{}' is not an address", address) }.fail();
+                }
+            };
+
+            table.set(segment, address);
+        }
+
+        return Ok(table);
+    }
+
+    //
+    /// Set where a segment starts in RDRAM
+    //
+    pub fn set (&mut self, segment : u8, address : u32)
+    {
+        self.m_segments[segment as usize & 0x0F] = Some(address);
+    }
+
+    //
+    /// Where a segment starts in RDRAM (None if it was never set)
+    //
+    pub fn get (&self, segment : u8) -> Option<u32>
+    {
+        return self.m_segments[segment as usize & 0x0F];
+    }
+}
+
+//
+/// Turn a virtual address into a physical RDRAM address (the offset in an RDRAM dump)
+//
+pub fn physical_address (address : u32) -> u32
+{
+    return address & PHYSICAL_ADDRESS_MASK;
+}
+
+//
+/// Parse a number given on the command line, either hex (0x80201230) or decimal
+//
+pub fn parse_number (text : &str) -> Option<u32>
+{
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+    return text.parse::<u32>().ok();
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn p_parse (entries : &[&str]) -> Result<SegmentTable>
+    {
+        return SegmentTable::parse(&entries.iter().map(|e| e.to_string()).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn parses_segment_entries ()
+    {
+        let table = p_parse(&["1=0x80201230", " 2 = 2048 ", "0xF=0X00400000"]).unwrap();
+        assert_eq!(table.get(1), Some(0x80201230));
+        assert_eq!(table.get(2), Some(2048));
+        assert_eq!(table.get(15), Some(0x00400000));
+        assert_eq!(table.get(3), None);
+
+        // a later entry for the same segment wins
+        assert_eq!(p_parse(&["1=0x100", "1=0x200"]).unwrap().get(1), Some(0x200));
+        assert_eq!(p_parse(&[]).unwrap(), SegmentTable::new());
+    }
+
+    #[test]
+    fn rejects_malformed_entries ()
+    {
+        for entries in &[&["1"][..], &["1:0x100"], &["16=0x100"], &["x=0x100"], &["1=0x"], &["1=0x1FFFFFFFF"], &["2=0x100", "1=ram"]] {
+            match p_parse(entries) {
+                Err(errors::Error::InvalidSegmentTable { .. }) => {},
+                other => panic!("expected InvalidSegmentTable for {:?}, got {:?}", entries, other.map(|_| ()))
+            }
+        }
+    }
+
+    #[test]
+    fn masks_virtual_addresses ()
+    {
+        assert_eq!(physical_address(0x80201230), 0x00201230);
+        assert_eq!(physical_address(0xA0201230), 0x00201230);
+        assert_eq!(physical_address(0x00201230), 0x00201230);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::display_list_interpreter::InterpreterResult;
+use crate::model::Model;
+use crate::render::Camera;
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+//
+/// The fill for triangles drawn without a texture
+//
+const UNTEXTURED_COLOR : &str = "#C0C0C0";
+
+//
+/// Settings for an svg export
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SvgOptions
+{
+    pub width: usize,
+    pub height: usize,
+
+    //
+    /// Write each vertex's index next to it
+    //
+    pub label_verticies: bool
+}
+
+//
+/// Export the triangles the display list draws as an svg, seen from camera.
+///
+/// Each triangle is a path (farthest first, so nearer ones cover them) with the
+/// class of the texture it was drawn with (texture_<index>, or none), and each
+/// texture gets its own fill color. Triangles with a corner behind the camera are left out.
+//
+pub fn export_svg (model : &Model, result : &InterpreterResult, camera : &Camera, options : &SvgOptions) -> String
+{
+    let verticies = model.vertex_store().verticies();
+    let project = |index : usize| -> Option<([f64; 2], f64)> {
+        let v = verticies.get(index)?;
+        return camera.project([v.pos[0] as f64, v.pos[1] as f64, v.pos[2] as f64], options.width, options.height);
+    };
+
+    // (triangle index, corners, depth of the farthest corner)
+    let mut visible = Vec::<(usize, [[f64; 2]; 3], f64)>::new();
+    for (i, triangle) in result.triangles.iter().enumerate() {
+        let corners : Option<Vec<([f64; 2], f64)>> = triangle.verticies.iter().map(|v| project(*v)).collect();
+        if let Some(corners) = corners {
+            let depth = corners.iter().map(|c| c.1).fold(f64::MIN, f64::max);
+            visible.push((i, [corners[0].0, corners[1].0, corners[2].0], depth));
+        }
+    }
+    visible.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+
+    let mut out = String::new();
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>").unwrap();
+    writeln!(out, "<!-- exported by bkmodel -->").unwrap();
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+        options.width, options.height
+    ).unwrap();
+
+    writeln!(out, "<style>").unwrap();
+    writeln!(out, "path {{ stroke: #000000; stroke-width: 0.5; stroke-linejoin: round; }}").unwrap();
+    writeln!(out, ".none {{ fill: {}; }}", UNTEXTURED_COLOR).unwrap();
+    for texture in model.textures() {
+        writeln!(out, ".texture_{} {{ fill: {}; }}", texture.index(), p_texture_color(texture.index() as usize)).unwrap();
+    }
+    writeln!(out, "text {{ font-family: monospace; font-size: 8px; text-anchor: middle; }}").unwrap();
+    writeln!(out, "</style>").unwrap();
+
+    writeln!(out, "<g id=\"triangles\">").unwrap();
+    for (i, corners, _) in &visible {
+        let triangle = &result.triangles[*i];
+        let class = match triangle.texture {
+            Some(index) => format!("texture_{}", index),
+            None => "none".to_string()
+        };
+        writeln!(out, "<path id=\"triangle_{}\" class=\"{}\" data-verticies=\"{} {} {}\" d=\"M {} L {} L {} Z\"/>",
+            i, class, triangle.verticies[0], triangle.verticies[1], triangle.verticies[2],
+            p_point(corners[0]), p_point(corners[1]), p_point(corners[2])
+        ).unwrap();
+    }
+    writeln!(out, "</g>").unwrap();
+
+    if options.label_verticies {
+        let used : BTreeSet<usize> = visible.iter().flat_map(|(i, _, _)| result.triangles[*i].verticies.iter().copied()).collect();
+
+        writeln!(out, "<g id=\"labels\">").unwrap();
+        for index in used {
+            if let Some((position, _)) = project(index) {
+                writeln!(out, "<text x=\"{:.2}\" y=\"{:.2}\">{}</text>", position[0], position[1] - 2.0, index).unwrap();
+            }
+        }
+        writeln!(out, "</g>").unwrap();
+    }
+
+    writeln!(out, "</svg>").unwrap();
+    return out;
+}
+
+fn p_point (point : [f64; 2]) -> String
+{
+    return format!("{:.2} {:.2}", point[0], point[1]);
+}
+
+//
+/// A color for a texture that stands out from its neighbours: the hue steps by the golden angle
+//
+fn p_texture_color (index : usize) -> String
+{
+    let hue = (index as f64 * 137.508) % 360.0;
+    let (saturation, lightness) = (0.55, 0.65);
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0_f64).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x)
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |c : f64| -> u8 { ((c + m) * 255.0).round() as u8 };
+
+    return format!("#{:02X}{:02X}{:02X}", channel(r), channel(g), channel(b));
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::display_list_interpreter::DisplayListInterpreter;
+    use crate::test_models::*;
+
+    //
+    /// Triangles (drawn in this order) seen from the front, 20 units either side of the middle of an 80x80 image
+    //
+    fn p_export (triangles : &[[[i16; 3]; 3]], label_verticies : bool) -> String
+    {
+        let mut builder = ModelBuilder::new();
+        for corners in triangles {
+            let first = corners.iter().map(|c| builder.vertex(*c, [0, 0], [0xFF; 3], 0xFF)).min().unwrap();
+            builder.command(g_vtx(0, 3, vertex_address(first)));
+            builder.command(g_tri2([0, 1, 2], [0, 0, 0]));
+        }
+        let model = Model::from_bytes(builder.build()).unwrap();
+        let result = DisplayListInterpreter::run(&model);
+
+        let camera = Camera::orthographic([0.0, 0.0, 100.0], [0.0; 3], 20.0);
+        return export_svg(&model, &result, &camera, &SvgOptions { width: 80, height: 80, label_verticies });
+    }
+
+    fn p_paths (svg : &str) -> Vec<&str>
+    {
+        return svg.lines().filter(|line| line.starts_with("<path")).collect();
+    }
+
+    #[test]
+    fn writes_a_path_for_a_triangle ()
+    {
+        let svg = p_export(&[[[-10, -10, 0], [10, -10, 0], [0, 10, 0]]], false);
+        assert!(svg.contains("viewBox=\"0 0 80 80\""));
+        assert_eq!(p_paths(&svg), ["<path id=\"triangle_0\" class=\"none\" data-verticies=\"0 1 2\" d=\"M 20.00 60.00 L 60.00 60.00 L 40.00 20.00 Z\"/>"]);
+        assert!(!svg.contains("<text"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn draws_farther_triangles_first ()
+    {
+        let svg = p_export(&[[[-10, -10, 10], [10, -10, 10], [0, 10, 10]], [[-10, -10, -10], [10, -10, -10], [0, 10, -10]]], true);
+        let paths = p_paths(&svg);
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].starts_with("<path id=\"triangle_1\"") && paths[0].contains("data-verticies=\"3 4 5\""), "{:?}", paths);
+        assert!(paths[1].starts_with("<path id=\"triangle_0\""), "{:?}", paths);
+
+        // a label just above each vertex
+        assert!(svg.contains("<text x=\"40.00\" y=\"18.00\">2</text>"));
+        assert_eq!(svg.matches("<text").count(), 6);
+    }
+}
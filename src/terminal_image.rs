@@ -0,0 +1,207 @@
+use crate::render::Image;
+
+use std::fmt::Write;
+
+// Drawing images in a terminal, for looking at textures and renders over ssh.
+//
+// Each character is two pixels: the upper half block (▀) with the foreground color as the
+// top pixel and the background color as the bottom one. Transparent pixels are drawn over
+// a checkerboard so they can be told apart from black.
+
+//
+/// The two greys of the checkerboard behind transparent pixels
+//
+const CHECKERBOARD : [[u8; 3]; 2] = [[0x66, 0x66, 0x66], [0x99, 0x99, 0x99]];
+
+//
+/// How many pixels wide (and tall) each checkerboard square is
+//
+const CHECKERBOARD_SIZE : usize = 4;
+
+//
+/// Which colors the terminal can show
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorMode
+{
+    //
+    /// 24 bit color escape codes
+    //
+    TrueColor,
+
+    //
+    /// The xterm 256 color palette
+    //
+    Palette256
+}
+
+impl ColorMode
+{
+    //
+    /// Parse a color mode from the command line (truecolor, 256)
+    //
+    pub fn from_name (name : &str) -> Option<ColorMode>
+    {
+        return match name.to_lowercase().as_str() {
+            "truecolor" | "24bit" => Some(ColorMode::TrueColor),
+            "256" => Some(ColorMode::Palette256),
+            _ => None
+        };
+    }
+
+    //
+    /// Guess from the environment: terminals with 24 bit color set COLORTERM to truecolor or 24bit
+    //
+    pub fn detect () -> ColorMode
+    {
+        return match std::env::var("COLORTERM") {
+            Ok(value) if value.contains("truecolor") || value.contains("24bit") => ColorMode::TrueColor,
+            _ => ColorMode::Palette256
+        };
+    }
+}
+
+//
+/// Draw an image as text, scaled (keeping its shape) to fit in max_columns x max_rows characters.
+/// Small images are scaled up by a whole number so their texels stay square.
+//
+pub fn draw (image : &Image, max_columns : usize, max_rows : usize, mode : ColorMode) -> String
+{
+    if image.width == 0 || image.height == 0 || max_columns == 0 || max_rows == 0 {
+        return String::new();
+    }
+
+    let (max_width, max_height) = (max_columns as f64, max_rows as f64 * 2.0);
+    let mut scale = (max_width / image.width as f64).min(max_height / image.height as f64);
+    if scale > 1.0 {
+        scale = scale.floor();
+    }
+    let width = ((image.width as f64 * scale).round() as usize).max(1);
+    let height = ((image.height as f64 * scale).round() as usize).max(1);
+
+    let sample = |x : usize, y : usize| -> [u8; 3] {
+        let color = image.pixel((x * image.width / width).min(image.width - 1), (y * image.height / height).min(image.height - 1));
+        let behind = CHECKERBOARD[(x / CHECKERBOARD_SIZE + y / CHECKERBOARD_SIZE) % 2];
+        let alpha = color[3] as u32;
+        let mix = |c : usize| -> u8 { ((color[c] as u32 * alpha + behind[c] as u32 * (255 - alpha)) / 255) as u8 };
+        return [mix(0), mix(1), mix(2)];
+    };
+
+    let mut out = String::new();
+    for row in 0 .. height.div_ceil(2) {
+        for x in 0 .. width {
+            let top = sample(x, row * 2);
+            if row * 2 + 1 < height {
+                let bottom = sample(x, row * 2 + 1);
+                write!(out, "{}{}\u{2580}", p_color_code(38, top, mode), p_color_code(48, bottom, mode)).unwrap();
+            } else {
+                // odd height: only the top pixel, over the terminal's own background
+                write!(out, "\x1b[0m{}\u{2580}", p_color_code(38, top, mode)).unwrap();
+            }
+        }
+        writeln!(out, "\x1b[0m").unwrap();
+    }
+
+    return out;
+}
+
+//
+/// The escape code to set the foreground (38) or background (48) color
+//
+fn p_color_code (layer : u8, color : [u8; 3], mode : ColorMode) -> String
+{
+    return match mode {
+        ColorMode::TrueColor => format!("\x1b[{};2;{};{};{}m", layer, color[0], color[1], color[2]),
+        ColorMode::Palette256 => format!("\x1b[{};5;{}m", layer, p_palette_256(color))
+    };
+}
+
+//
+/// The closest color in the xterm 256 color palette: the 6x6x6 color cube or the 24 greys
+//
+fn p_palette_256 (color : [u8; 3]) -> u8
+{
+    // the cube levels are 0, 95, 135, 175, 215, 255
+    let level = |c : u8| -> usize { if c < 48 { 0 } else if c < 115 { 1 } else { ((c as usize - 35) / 40).min(5) } };
+    let level_value = |l : usize| -> i32 { if l == 0 { 0 } else { 55 + l as i32 * 40 } };
+    let cube = [level(color[0]), level(color[1]), level(color[2])];
+    let cube_color = [level_value(cube[0]), level_value(cube[1]), level_value(cube[2])];
+
+    // the greys go from 8 to 238 in steps of 10
+    let average = (color[0] as i32 + color[1] as i32 + color[2] as i32) / 3;
+    let grey = ((average - 3) / 10).clamp(0, 23);
+    let grey_value = 8 + grey * 10;
+
+    let distance = |other : [i32; 3]| -> i32 { (0 .. 3).map(|c| (color[c] as i32 - other[c]).pow(2)).sum() };
+    if distance([grey_value; 3]) < distance(cube_color) {
+        return 232 + grey as u8;
+    }
+    return (16 + cube[0] * 36 + cube[1] * 6 + cube[2]) as u8;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const RED : [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+    const GREEN : [u8; 4] = [0x00, 0xFF, 0x00, 0xFF];
+    const BLUE : [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+    #[test]
+    fn packs_two_rows_into_each_line ()
+    {
+        // 2x3: red, green and blue rows. The last line only has a top pixel.
+        let mut image = Image::new(2, 3, RED);
+        for x in 0 .. 2 {
+            image.set_pixel(x, 1, GREEN);
+            image.set_pixel(x, 2, BLUE);
+        }
+
+        let cell = "\x1b[38;2;255;0;0m\x1b[48;2;0;255;0m\u{2580}";
+        let last = "\x1b[0m\x1b[38;2;0;0;255m\u{2580}";
+        let expected = format!("{0}{0}\x1b[0m\n{1}{1}\x1b[0m\n", cell, last);
+        assert_eq!(draw(&image, 2, 2, ColorMode::TrueColor), expected);
+    }
+
+    #[test]
+    fn scales_to_fit ()
+    {
+        // small images by a whole number, so 2x1 in 8x8 characters is 8x4 pixels (2 lines)
+        let image = Image::new(2, 1, RED);
+        let text = draw(&image, 8, 8, ColorMode::TrueColor);
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.lines().all(|line| line.matches('\u{2580}').count() == 8));
+
+        // large ones down to the smaller of the two limits
+        let image = Image::new(100, 10, RED);
+        let text = draw(&image, 20, 20, ColorMode::TrueColor);
+        assert_eq!(text.lines().count(), 1);
+        assert_eq!(text.matches('\u{2580}').count(), 20);
+
+        assert_eq!(draw(&image, 0, 20, ColorMode::TrueColor), "");
+        assert_eq!(draw(&Image::new(0, 4, RED), 20, 20, ColorMode::TrueColor), "");
+    }
+
+    #[test]
+    fn draws_transparency_over_a_checkerboard ()
+    {
+        let image = Image::new(1, 1, [0xFF, 0xFF, 0xFF, 0x00]);
+        let grey = CHECKERBOARD[0];
+        assert_eq!(draw(&image, 1, 1, ColorMode::TrueColor),
+            format!("\x1b[0m\x1b[38;2;{0};{0};{0}m\u{2580}\x1b[0m\n", grey[0]));
+    }
+
+    #[test]
+    fn picks_the_nearest_of_256_colors ()
+    {
+        assert_eq!(p_palette_256([0xFF, 0x00, 0x00]), 196);
+        assert_eq!(p_palette_256([0x00, 0x00, 0x00]), 16);
+        assert_eq!(p_palette_256([0x80, 0x80, 0x80]), 244);
+        assert_eq!(p_color_code(48, [0xFF, 0x00, 0x00], ColorMode::Palette256), "\x1b[48;5;196m");
+
+        assert_eq!(ColorMode::from_name("TrueColor"), Some(ColorMode::TrueColor));
+        assert_eq!(ColorMode::from_name("256"), Some(ColorMode::Palette256));
+        assert_eq!(ColorMode::from_name("16"), None);
+    }
+}
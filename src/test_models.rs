@@ -0,0 +1,305 @@
+use crate::segment_map::{TEXTURE_SEGMENT, VERTEX_SEGMENT};
+use crate::texture_type::TextureType;
+use crate::vertex_store::{Vertex, VertexStore};
+
+// Building small models in memory for tests, with the same layout as the game's:
+// header, textures, display list, vertex store, then the animated textures.
+//
+// Along with the builder are functions for encoding the display list commands the
+// tests need (F3DEX, like the game uses).
+
+//
+/// The size of the model header
+//
+const HEADER_SIZE : usize = 0x38;
+
+//
+/// A model being put together for a test
+//
+#[derive(Default)]
+pub struct ModelBuilder
+{
+    //
+    /// Type, width, height and data (palette and pixels, and any mipmap levels) of each texture
+    //
+    m_textures: Vec<(TextureType, u8, u8, Vec<u8>)>,
+
+    m_verticies: Vec<Vertex>,
+
+    m_commands: Vec<[u8; 8]>,
+
+    //
+    /// Frame size, frame count and frame rate of each animated texture slot (None for no section)
+    //
+    m_animated_textures: Option<Vec<(u16, u16, f32)>>
+}
+
+impl ModelBuilder
+{
+    pub fn new () -> ModelBuilder
+    {
+        return ModelBuilder::default();
+    }
+
+    //
+    /// Add a texture, returning its segmented address (for G_SETTIMG)
+    //
+    pub fn texture (&mut self, texture_type : TextureType, width : u8, height : u8, data : Vec<u8>) -> u32
+    {
+        let offset : usize = self.m_textures.iter().map(|t| t.3.len()).sum();
+        self.m_textures.push((texture_type, width, height, data));
+        return ((TEXTURE_SEGMENT as u32) << 24) | offset as u32;
+    }
+
+    //
+    /// Add a vertex to the vertex store, returning its index
+    //
+    pub fn vertex (&mut self, position : [i16; 3], uv : [i16; 2], rgb_or_norm : [u8; 3], alpha : u8) -> usize
+    {
+        self.m_verticies.push(Vertex { pos: position, flag: 0, uv, rgb_or_norm, alpha });
+        return self.m_verticies.len() - 1;
+    }
+
+    //
+    /// Add a display list command
+    //
+    pub fn command (&mut self, command : [u8; 8]) -> &mut ModelBuilder
+    {
+        self.m_commands.push(command);
+        return self;
+    }
+
+    //
+    /// Add the animated texture section, with up to 4 slots (the rest are left empty)
+    //
+    pub fn animated_textures (&mut self, slots : &[(u16, u16, f32)]) -> &mut ModelBuilder
+    {
+        self.m_animated_textures = Some(slots.to_vec());
+        return self;
+    }
+
+    //
+    /// The model file
+    //
+    pub fn build (&self) -> Vec<u8>
+    {
+        // textures: [size of the data] [00 count 00 00], a 16 byte header for each, then the data
+        let texture_data : Vec<u8> = self.m_textures.iter().flat_map(|t| t.3.iter().copied()).collect();
+        let mut textures = Vec::<u8>::new();
+        textures.extend_from_slice(&(texture_data.len() as u32).to_be_bytes());
+        textures.extend_from_slice(&[0, self.m_textures.len() as u8, 0, 0]);
+        let mut offset = 0u32;
+        for (texture_type, width, height, data) in &self.m_textures {
+            textures.extend_from_slice(&offset.to_be_bytes());
+            textures.extend_from_slice(&[0, p_type_code(*texture_type), 0, 0, *width, *height, 0, 0, 0, 0, 0, 0]);
+            offset += data.len() as u32;
+        }
+        textures.extend_from_slice(&texture_data);
+
+        // display list: [command count] [0], then the commands
+        let mut display_list = Vec::<u8>::new();
+        display_list.extend_from_slice(&(self.m_commands.len() as u32).to_be_bytes());
+        display_list.extend_from_slice(&[0; 4]);
+        for command in &self.m_commands {
+            display_list.extend_from_slice(command);
+        }
+
+        // vertex store: the bounds, [vertex count * 2], then the verticies
+        let mut store = VertexStore::new();
+        for v in &self.m_verticies {
+            store.add(Vertex { pos: v.pos, flag: v.flag, uv: v.uv, rgb_or_norm: v.rgb_or_norm, alpha: v.alpha });
+        }
+        store.recompute_bounds();
+        let bounds = store.bounds();
+        let mut verticies = Vec::<u8>::new();
+        for value in bounds.draw_distance_negative.iter().chain(bounds.draw_distance_positive.iter()).chain(bounds.object_coordinate_range.iter()) {
+            verticies.extend_from_slice(&value.to_be_bytes());
+        }
+        verticies.extend_from_slice(&bounds.object_collision_range.to_be_bytes());
+        verticies.extend_from_slice(&bounds.banjo_collision_range.to_be_bytes());
+        verticies.extend_from_slice(&(self.m_verticies.len() as u16 * 2).to_be_bytes());
+        for v in store.verticies() {
+            for value in v.pos.iter() {
+                verticies.extend_from_slice(&value.to_be_bytes());
+            }
+            verticies.extend_from_slice(&v.flag.to_be_bytes());
+            verticies.extend_from_slice(&v.uv[0].to_be_bytes());
+            verticies.extend_from_slice(&v.uv[1].to_be_bytes());
+            verticies.extend_from_slice(&[v.rgb_or_norm[0], v.rgb_or_norm[1], v.rgb_or_norm[2], v.alpha]);
+        }
+
+        // animated textures: 4 slots of [ssss] [cccc] [ffff ffff]
+        let mut animated_textures = Vec::<u8>::new();
+        if let Some(slots) = &self.m_animated_textures {
+            for slot in 0 .. 4 {
+                let (frame_size, frame_count, frame_rate) = slots.get(slot).copied().unwrap_or((0, 0, 0.0));
+                animated_textures.extend_from_slice(&frame_size.to_be_bytes());
+                animated_textures.extend_from_slice(&frame_count.to_be_bytes());
+                animated_textures.extend_from_slice(&frame_rate.to_be_bytes());
+            }
+        }
+
+        // the display list and animated textures are 8 byte aligned
+        let texture_offset = HEADER_SIZE;
+        let display_list_offset = p_align(texture_offset + textures.len(), 8);
+        let vertex_store_offset = display_list_offset + display_list.len();
+        let animated_texture_offset = if animated_textures.is_empty() { 0 } else { p_align(vertex_store_offset + verticies.len(), 8) };
+
+        // G_TRI2 draws two triangles, but a degenerate one (every corner the same slot) doesnt count
+        let triangle_count = self.m_commands.iter().filter(|c| c[0] == 0xB1)
+            .flat_map(|c| vec![[c[1], c[2], c[3]], [c[5], c[6], c[7]]])
+            .filter(|t| !(t[0] == t[1] && t[1] == t[2]))
+            .count() as u16;
+
+        let mut out = Vec::<u8>::new();
+        out.extend_from_slice(&0x0Bu32.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&(texture_offset as u16).to_be_bytes());
+        out.extend_from_slice(&0u16.to_be_bytes());
+        out.extend_from_slice(&(display_list_offset as u32).to_be_bytes());
+        out.extend_from_slice(&(vertex_store_offset as u32).to_be_bytes());
+        out.extend_from_slice(&[0; 6 * 4]);
+        out.extend_from_slice(&(animated_texture_offset as u32).to_be_bytes());
+        out.extend_from_slice(&0u16.to_be_bytes());
+        out.extend_from_slice(&triangle_count.to_be_bytes());
+        out.extend_from_slice(&(self.m_verticies.len() as u16 * 2).to_be_bytes());
+        out.extend_from_slice(&0u16.to_be_bytes());
+
+        out.extend_from_slice(&textures);
+        out.resize(display_list_offset, 0);
+        out.extend_from_slice(&display_list);
+        out.extend_from_slice(&verticies);
+        if animated_texture_offset != 0 {
+            out.resize(animated_texture_offset, 0);
+            out.extend_from_slice(&animated_textures);
+        }
+        return out;
+    }
+}
+
+fn p_align (offset : usize, alignment : usize) -> usize
+{
+    return offset.div_ceil(alignment) * alignment;
+}
+
+//
+/// The type code in the texture header
+//
+fn p_type_code (texture_type : TextureType) -> u8
+{
+    return match texture_type {
+        TextureType::CI4 => 0x01,
+        TextureType::CI8 => 0x02,
+        TextureType::RGBA16 => 0x04,
+        TextureType::RGBA32 => 0x08,
+        TextureType::IA8 => 0x10,
+        TextureType::Unknown => 0x00
+    };
+}
+
+// --- display list commands
+
+//
+/// The segmented address of a vertex in the vertex store
+//
+pub fn vertex_address (index : usize) -> u32
+{
+    return ((VERTEX_SEGMENT as u32) << 24) | (index * 16) as u32;
+}
+
+//
+/// G_VTX: load count verticies from address into the vertex buffer, starting at slot
+//
+pub fn g_vtx (slot : u8, count : u8, address : u32) -> [u8; 8]
+{
+    let length = (count as u16 * 16).saturating_sub(1) & 0x3FF;
+    let address = address.to_be_bytes();
+    return [0x04, slot * 2, (count << 2) | (length >> 8) as u8, length as u8, address[0], address[1], address[2], address[3]];
+}
+
+//
+/// G_TRI2: two triangles, as vertex buffer slots
+//
+pub fn g_tri2 (first : [u8; 3], second : [u8; 3]) -> [u8; 8]
+{
+    return [0xB1, first[0] * 2, first[1] * 2, first[2] * 2, 0, second[0] * 2, second[1] * 2, second[2] * 2];
+}
+
+//
+/// G_DL: run the display list at address, returning to this one afterwards
+//
+pub fn g_dl (address : u32) -> [u8; 8]
+{
+    return p_word_command(0x06, 0, address);
+}
+
+//
+/// G_SETGEOMETRYMODE: turn on the geometry mode flags
+//
+pub fn g_setgeometrymode (flags : u32) -> [u8; 8]
+{
+    return p_word_command(0xB7, 0, flags);
+}
+
+//
+/// G_CLEARGEOMETRYMODE: turn off the geometry mode flags
+//
+pub fn g_cleargeometrymode (flags : u32) -> [u8; 8]
+{
+    return p_word_command(0xB6, 0, flags);
+}
+
+//
+/// G_SetOtherMode_H: replace length bits at shift with the same bits of data
+//
+pub fn g_setothermode_h (shift : u8, length : u8, data : u32) -> [u8; 8]
+{
+    return p_word_command(0xBA, ((shift as u32) << 8) | length as u32, data);
+}
+
+//
+/// G_TEXTURE: turn texturing on with a scale (0.16 fixed point), tile and number of mipmap levels past the first
+//
+pub fn g_texture (scale : [u16; 2], levels : u8, tile : u8) -> [u8; 8]
+{
+    return p_word_command(0xBB, ((((levels & 0x07) << 3) | (tile & 0x07)) as u32) << 8 | 1, ((scale[0] as u32) << 16) | scale[1] as u32);
+}
+
+//
+/// G_SETTIMG for an RGBA16 texture
+//
+pub fn g_settimg (address : u32) -> [u8; 8]
+{
+    return p_word_command(0xFD, 0x10 << 16, address);
+}
+
+//
+/// G_SETTILE for RGBA16, with the line in 64 bit words and the clamp/mirror, mask and shift for S and T
+//
+pub fn g_settile (tile : u8, line : u16, tmem_address : u16, clamp_mirror : [u8; 2], mask : [u8; 2], shift : [u8; 2]) -> [u8; 8]
+{
+    let high = (2 << 19) | ((line as u32 & 0x1FF) << 9) | (tmem_address as u32 & 0x1FF);
+    let low = ((tile as u32 & 0x07) << 24)
+        | ((clamp_mirror[1] as u32 & 0x03) << 18) | ((mask[1] as u32 & 0x0F) << 14) | ((shift[1] as u32 & 0x0F) << 10)
+        | ((clamp_mirror[0] as u32 & 0x03) << 8) | ((mask[0] as u32 & 0x0F) << 4) | (shift[0] as u32 & 0x0F);
+    return p_word_command(0xF5, high, low);
+}
+
+//
+/// G_SETTILESIZE with the corners in texels
+//
+pub fn g_settilesize (tile : u8, upper_left : [u16; 2], lower_right : [u16; 2]) -> [u8; 8]
+{
+    let high = ((upper_left[0] as u32 * 4) << 12) | (upper_left[1] as u32 * 4);
+    let low = ((tile as u32 & 0x07) << 24) | ((lower_right[0] as u32 * 4) << 12) | (lower_right[1] as u32 * 4);
+    return p_word_command(0xF2, high, low);
+}
+
+//
+/// A command with its opcode, the 24 bits after it and a second word
+//
+fn p_word_command (opcode : u8, high : u32, low : u32) -> [u8; 8]
+{
+    let word = ((opcode as u64) << 56) | ((high as u64 & 0xFFFFFF) << 32) | low as u64;
+    return word.to_be_bytes();
+}
@@ -1,3 +1,9 @@
+use crate::errors;
+use crate::errors::Result;
+use crate::palette;
+use crate::palette::Palette;
+use crate::quantize;
+use crate::quantize::{Dither, QuantizationError};
 use crate::texture_type::TextureType;
 
 // proxy for the texture - stores a bit of the header info
@@ -78,4 +84,372 @@ impl Texture
     pub fn height (&self) -> u8 {
         return self.m_height;
     }
-}
\ No newline at end of file
+
+    //
+    /// The size of the texture data (palette + pixels) in bytes
+    //
+    pub fn data_size (&self) -> u32 {
+        let pixels = self.m_width as u32 * self.m_height as u32;
+        return self.m_texture_type.palette_size() + (pixels * self.m_texture_type.bits_per_pixel()).div_ceil(8);
+    }
+
+    //
+    /// How many bytes of TMEM the texture takes once loaded (the palette is separate)
+    //
+    pub fn tmem_size (&self) -> u32 {
+        return self.m_texture_type.tmem_size(self.m_width as u32, self.m_height as u32);
+    }
+
+    //
+    /// If the texture fits in TMEM (see TextureType::tmem_limit)
+    //
+    pub fn fits_tmem (&self) -> bool {
+        return self.tmem_size() <= self.m_texture_type.tmem_limit();
+    }
+
+    //
+    /// The palette of a CI texture (None for other types).
+    /// data is the texture data from the model (see Model::texture_data)
+    //
+    pub fn palette (&self, data: &[u8]) -> Result<Option<Palette>>
+    {
+        let palette_size = self.m_texture_type.palette_size() as usize;
+        if palette_size == 0 {
+            return Ok(None);
+        }
+
+        return Ok(Some(Palette::from_bytes(data, palette_size / 2)?));
+    }
+
+    //
+    /// Decode the texture into 8 bit RGBA (width * height * 4 bytes).
+    /// data is the texture data from the model (see Model::texture_data)
+    //
+    pub fn decode_rgba (&self, data: &[u8]) -> Result<Vec<u8>>
+    {
+        return match self.palette(data)? {
+            Some(palette) => self.decode_rgba_with_palette(data, &palette),
+            None => self.p_decode_rgba(data, None)
+        };
+    }
+
+    //
+    /// Decode a CI texture into 8 bit RGBA using a different palette than its own
+    /// (the palette needs a color for every index the texture uses)
+    //
+    pub fn decode_rgba_with_palette (&self, data: &[u8], palette: &Palette) -> Result<Vec<u8>>
+    {
+        if self.m_texture_type.palette_size() == 0 {
+            return errors::InvalidPalette { reason: format!("{:?} textures dont use a palette", self.m_texture_type) }.fail();
+        }
+
+        return self.p_decode_rgba(data, Some(palette));
+    }
+
+    //
+    /// Encode an 8 bit RGBA image (width * height * 4 bytes) into this texture's type,
+    /// returning the texture data (palette + pixels) to store in the model.
+    /// CI textures get a palette generated from the image (see quantize), and also return
+    /// how far the result is from the image.
+    //
+    pub fn encode_rgba (&self, rgba: &[u8], dither: Dither) -> Result<(Vec<u8>, Option<QuantizationError>)>
+    {
+        let pixel_count = self.m_width as usize * self.m_height as usize;
+        if rgba.len() != pixel_count * 4 {
+            return errors::TextureEncodeError {
+                reason: format!("texture_{} needs {:#X} bytes of RGBA, got {:#X}", self.m_texture_index, pixel_count * 4, rgba.len())
+            }.fail();
+        }
+
+        let mut data = Vec::<u8>::with_capacity(self.data_size() as usize);
+        let mut error = None;
+        match self.m_texture_type {
+            TextureType::CI4 | TextureType::CI8 => {
+                let palette_count = self.m_texture_type.palette_size() as usize / 2;
+                let quantized = quantize::quantize(rgba, self.m_width as usize, self.m_height as usize, palette_count, dither);
+
+                data.extend_from_slice(&quantized.palette.to_bytes());
+                data.resize(palette_count * 2, 0);
+
+                if self.m_texture_type == TextureType::CI4 {
+                    // high nibble first
+                    for pair in quantized.indices.chunks(2) {
+                        data.push((pair[0] << 4) | pair.get(1).copied().unwrap_or(0));
+                    }
+                } else {
+                    data.extend_from_slice(&quantized.indices);
+                }
+
+                error = Some(quantized.error);
+            },
+            TextureType::RGBA16 => {
+                for p in rgba.chunks_exact(4) {
+                    data.extend_from_slice(&palette::rgba8_to_rgba5551([p[0], p[1], p[2], p[3]]).to_be_bytes());
+                }
+            },
+            TextureType::RGBA32 => data.extend_from_slice(rgba),
+            TextureType::IA8 => {
+                // [iiii aaaa]
+                for p in rgba.chunks_exact(4) {
+                    let intensity = (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000;
+                    data.push(((intensity as u8) & 0xF0) | (p[3] >> 4));
+                }
+            },
+            TextureType::Unknown => {
+                return errors::UnsupportedTextureType { texture_type: self.m_texture_type }.fail();
+            }
+        }
+
+        return Ok((data, error));
+    }
+
+    // --- private
+
+    fn p_decode_rgba (&self, data: &[u8], palette: Option<&Palette>) -> Result<Vec<u8>>
+    {
+        if data.len() < self.data_size() as usize {
+            return errors::SectionOutOfBounds {
+                section: format!("texture_{}", self.m_texture_index), offset: 0usize, length: data.len()
+            }.fail();
+        }
+
+        let pixel_count = self.m_width as usize * self.m_height as usize;
+        let palette_size = self.m_texture_type.palette_size() as usize;
+        let pixels = &data[palette_size..];
+
+        let mut rgba = Vec::<u8>::with_capacity(pixel_count * 4);
+        for i in 0 .. pixel_count {
+            let color = match self.m_texture_type {
+                TextureType::CI4 | TextureType::CI8 => {
+                    // CI4 is high nibble first
+                    let index = match self.m_texture_type {
+                        TextureType::CI4 if i % 2 == 0 => pixels[i / 2] >> 4,
+                        TextureType::CI4 => pixels[i / 2] & 0x0F,
+                        _ => pixels[i]
+                    };
+
+                    match palette.and_then(|p| p.rgba(index as usize)) {
+                        Some(color) => color,
+                        None => {
+                            return errors::InvalidPalette { reason: format!("no color for index {}", index) }.fail();
+                        }
+                    }
+                },
+                TextureType::RGBA16 => rgba5551_to_rgba8(p_read_u16(pixels, i * 2)),
+                TextureType::RGBA32 => [pixels[i*4], pixels[i*4 + 1], pixels[i*4 + 2], pixels[i*4 + 3]],
+                TextureType::IA8 => {
+                    // [iiii aaaa]
+                    let intensity = (pixels[i] >> 4) * 0x11;
+                    let alpha = (pixels[i] & 0x0F) * 0x11;
+                    [intensity, intensity, intensity, alpha]
+                },
+                TextureType::Unknown => {
+                    return errors::UnsupportedTextureType { texture_type: self.m_texture_type }.fail();
+                }
+            };
+            rgba.extend_from_slice(&color);
+        }
+
+        return Ok(rgba);
+    }
+}
+
+//
+/// Where an address (ex. from G_SETTIMG) lands in the model's textures
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextureAddress
+{
+    //
+    /// The start of a texture (the palette for CI textures)
+    //
+    Start { index: usize },
+
+    //
+    /// The pixels of a CI texture, just after its palette
+    //
+    Pixels { index: usize },
+
+    //
+    /// Somewhere inside a texture which isnt the start of the palette or pixels
+    //
+    Inside { index: usize, offset: usize },
+
+    //
+    /// In the texture data, but not inside any texture. offset is from the start of the texture data.
+    //
+    Between { offset: usize, previous: Option<usize> },
+
+    //
+    /// Not in the texture data at all
+    //
+    NotTextureData { segmented_address: u32 }
+}
+
+impl TextureAddress
+{
+    //
+    /// The texture the address belongs to, if any
+    //
+    pub fn texture_index (&self) -> Option<usize>
+    {
+        return match self {
+            TextureAddress::Start { index } | TextureAddress::Pixels { index } | TextureAddress::Inside { index, .. } => Some(*index),
+            _ => None
+        };
+    }
+
+    //
+    /// A short description for listings (ex. texture_3), with a diagnostic if it doesnt point at a texture
+    //
+    pub fn describe (&self) -> String
+    {
+        return match self {
+            TextureAddress::Start { index } => format!("texture_{}", index),
+            TextureAddress::Pixels { index } => format!("texture_{} (pixels)", index),
+            TextureAddress::Inside { index, offset } => format!("texture_{} + {:#X} (not the start of the texture)", index, offset),
+            TextureAddress::Between { offset, previous: Some(previous) } => format!("between textures (texture data + {:#X}, after texture_{})", offset, previous),
+            TextureAddress::Between { offset, previous: None } => format!("between textures (texture data + {:#X}, before the first texture)", offset),
+            TextureAddress::NotTextureData { segmented_address } => format!("{:#X} is not texture data", segmented_address)
+        };
+    }
+}
+
+//
+/// Expand a RGBA5551 color to 8 bits per channel
+//
+pub fn rgba5551_to_rgba8 (color: u16) -> [u8; 4]
+{
+    let expand = |v: u16| -> u8 { ((v << 3) | (v >> 2)) as u8 };
+
+    return [
+        expand((color >> 11) & 0x1F),
+        expand((color >> 6) & 0x1F),
+        expand((color >> 1) & 0x1F),
+        if color & 1 != 0 { 0xFF } else { 0x00 }
+    ];
+}
+
+fn p_read_u16 (data: &[u8], offset: usize) -> u16
+{
+    return u16::from_be_bytes([data[offset], data[offset + 1]]);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::model::Model;
+    use crate::test_models;
+    use crate::test_models::ModelBuilder;
+
+    //
+    /// 4x4 image with 16 opaque colors, all of which every type (but IA8) holds exactly
+    //
+    fn p_image () -> Vec<u8>
+    {
+        return (0 .. 16u16).flat_map(|i| rgba5551_to_rgba8((i << 11) | ((15 - i) << 1) | 1)).collect();
+    }
+
+    #[test]
+    fn encodes_ci4_with_a_palette ()
+    {
+        // red, green, red: palette padded to 16 colors, then the indices high nibble first
+        let texture = Texture::new(0, 0, 0, TextureType::CI4, 3, 1);
+        let rgba = [0xFF, 0x00, 0x00, 0xFF,   0x00, 0xFF, 0x00, 0xFF,   0xFF, 0x00, 0x00, 0xFF];
+        let (data, error) = texture.encode_rgba(&rgba, Dither::None).unwrap();
+
+        let mut expected = vec![0xF8, 0x01, 0x07, 0xC1];
+        expected.resize(0x20, 0);
+        expected.extend_from_slice(&[0x01, 0x00]);
+        assert_eq!(data, expected);
+        assert_eq!(data.len(), texture.data_size() as usize);
+        assert_eq!(error.unwrap().mean_squared, 0.0);
+
+        assert_eq!(texture.palette(&data).unwrap().unwrap().colors().len(), 16);
+        assert_eq!(texture.decode_rgba(&data).unwrap(), rgba.to_vec());
+    }
+
+    #[test]
+    fn decodes_what_it_encodes ()
+    {
+        let rgba = p_image();
+        for texture_type in &[TextureType::CI4, TextureType::CI8, TextureType::RGBA16, TextureType::RGBA32] {
+            let texture = Texture::new(0, 0, 0, *texture_type, 4, 4);
+            let (data, error) = texture.encode_rgba(&rgba, Dither::FloydSteinberg).unwrap();
+            assert_eq!(data.len(), texture.data_size() as usize, "{:?}", texture_type);
+            assert_eq!(error.is_some(), texture_type.palette_size() != 0, "{:?}", texture_type);
+            assert_eq!(texture.decode_rgba(&data).unwrap(), rgba, "{:?}", texture_type);
+        }
+
+        // IA8 keeps 4 bits of intensity and alpha
+        let grey : Vec<u8> = (0 .. 16u8).flat_map(|i| [i * 0x11, i * 0x11, i * 0x11, (15 - i) * 0x11]).collect();
+        let texture = Texture::new(0, 0, 0, TextureType::IA8, 4, 4);
+        let (data, _) = texture.encode_rgba(&grey, Dither::None).unwrap();
+        assert_eq!(texture.decode_rgba(&data).unwrap(), grey);
+    }
+
+    #[test]
+    fn decodes_with_another_palette ()
+    {
+        let texture = Texture::new(0, 0, 0, TextureType::CI4, 4, 4);
+        let (data, _) = texture.encode_rgba(&p_image(), Dither::None).unwrap();
+
+        // every index onto one color
+        let palette = Palette::new(vec![0xF801; 16]);
+        assert_eq!(texture.decode_rgba_with_palette(&data, &palette).unwrap(), [0xFF, 0x00, 0x00, 0xFF].repeat(16));
+
+        match texture.decode_rgba_with_palette(&data, &Palette::new(vec![0xF801])) {
+            Err(errors::Error::InvalidPalette { .. }) => {},
+            other => panic!("expected InvalidPalette, got {:?}", other.map(|_| ()))
+        }
+        match Texture::new(0, 0, 0, TextureType::RGBA16, 4, 4).decode_rgba_with_palette(&data, &palette) {
+            Err(errors::Error::InvalidPalette { .. }) => {},
+            other => panic!("expected InvalidPalette, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn encode_checks_the_image_size ()
+    {
+        let texture = Texture::new(0, 0, 0, TextureType::RGBA16, 4, 4);
+        match texture.encode_rgba(&p_image()[4 ..], Dither::None) {
+            Err(errors::Error::TextureEncodeError { .. }) => {},
+            other => panic!("expected TextureEncodeError, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn classifies_texture_addresses ()
+    {
+        // a CI4 texture (0x20 bytes of palette, 8 of pixels) with 8 bytes of padding, then a RGBA16 texture
+        let mut builder = ModelBuilder::new();
+        let ci4 = builder.texture(TextureType::CI4, 4, 4, vec![0; 0x30]);
+        let rgba16 = builder.texture(TextureType::RGBA16, 2, 2, vec![0; 8]);
+        builder.vertex([0, 0, 0], [0, 0], [0, 0, 0], 0xFF);
+        let model = Model::from_bytes(builder.build()).unwrap();
+
+        assert_eq!(model.texture_for_address(ci4), TextureAddress::Start { index: 0 });
+        assert_eq!(model.texture_for_address(ci4 + 0x20), TextureAddress::Pixels { index: 0 });
+        assert_eq!(model.texture_for_address(ci4 + 0x22), TextureAddress::Inside { index: 0, offset: 0x22 });
+        assert_eq!(model.texture_for_address(ci4 + 0x2C), TextureAddress::Between { offset: 0x2C, previous: Some(0) });
+        assert_eq!(model.texture_for_address(rgba16), TextureAddress::Start { index: 1 });
+
+        // RGBA16 has no palette, so 2 bytes in is just inside
+        assert_eq!(model.texture_for_address(rgba16 + 2), TextureAddress::Inside { index: 1, offset: 2 });
+
+        for address in &[rgba16 + 8, test_models::vertex_address(0), 0x0600_0000] {
+            assert_eq!(model.texture_for_address(*address), TextureAddress::NotTextureData { segmented_address: *address });
+        }
+    }
+
+    #[test]
+    fn describes_texture_addresses ()
+    {
+        assert_eq!(TextureAddress::Start { index: 3 }.describe(), "texture_3");
+        assert_eq!(TextureAddress::Pixels { index: 3 }.texture_index(), Some(3));
+        assert_eq!(TextureAddress::Inside { index: 1, offset: 0x10 }.describe(), "texture_1 + 0x10 (not the start of the texture)");
+        assert_eq!(TextureAddress::Between { offset: 0x2C, previous: None }.texture_index(), None);
+        assert_eq!(TextureAddress::NotTextureData { segmented_address: 0x0600_0000 }.describe(), "0x6000000 is not texture data");
+    }
+}
@@ -0,0 +1,210 @@
+use crate::display_list_interpreter::{InterpreterResult, RenderState};
+use crate::model::Model;
+use crate::texture::Texture;
+use crate::tile_descriptor::TileDescriptor;
+
+// Converting vertex texture coordinates to normalized UVs (0-1 across the texture) and back.
+//
+// A vertex's S and T are S10.5 fixed point texels. The RSP multiplies them by G_TEXTURE's
+// scale (0x10000 is 1.0, and 0xFFFF is used for "about 1"), then the RDP applies the tile's
+// shift and makes them relative to the tile's upper left corner (G_SETTILESIZE).
+//
+// Nothing is wrapped: a coordinate past the edge of the texture gives a UV past 0-1
+// (the texture repeats there), and negative coordinates give negative UVs.
+
+//
+/// S10.5 coordinates have 5 fraction bits
+//
+const S10_5_ONE : f64 = 32.0;
+
+//
+/// G_TEXTURE's scale for 1.0
+//
+const TEXTURE_SCALE_ONE : f64 = 65536.0;
+
+//
+/// Everything needed to convert between a vertex's texture coordinates and normalized UVs
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvMapping
+{
+    //
+    /// The S and T scale from G_TEXTURE
+    //
+    pub scale: [u16; 2],
+
+    //
+    /// The width and height of the texture in texels
+    //
+    pub texture_size: [u32; 2],
+
+    //
+    /// The tile the texture is read through (for its shift and upper left corner)
+    //
+    pub tile: TileDescriptor
+}
+
+impl UvMapping
+{
+    //
+    /// The mapping the display list had set up when drawing with a texture (ex. the state of a triangle)
+    //
+    pub fn from_state (state : &RenderState, texture : &Texture) -> UvMapping
+    {
+        return UvMapping {
+            scale: state.texture_scale,
+            texture_size: [texture.width() as u32, texture.height() as u32],
+            tile: state.tiles[state.texture_tile as usize % state.tiles.len()].unwrap_or_default()
+        };
+    }
+
+    //
+    /// Convert a vertex's S10.5 texture coordinates to normalized UVs
+    //
+    pub fn normalize (&self, uv : [i16; 2]) -> [f64; 2]
+    {
+        let mut out = [0.0; 2];
+        for (axis, value) in out.iter_mut().enumerate() {
+            let texels = uv[axis] as f64 / S10_5_ONE * self.scale[axis] as f64 / TEXTURE_SCALE_ONE;
+            let in_tile = self.tile.tile_coordinate(texels, axis);
+            *value = in_tile / self.texture_size[axis].max(1) as f64;
+        }
+        return out;
+    }
+
+    //
+    /// Convert normalized UVs back to S10.5 texture coordinates for a vertex (rounded to the nearest 1/32 texel).
+    /// None if they dont fit (S10.5 only goes from -1024 to just under 1024 texels), or the scale is 0.
+    //
+    pub fn denormalize (&self, uv : [f64; 2]) -> Option<[i16; 2]>
+    {
+        let mut out = [0i16; 2];
+        for (axis, value) in out.iter_mut().enumerate() {
+            if self.scale[axis] == 0 {
+                return None;
+            }
+
+            let in_tile = uv[axis] * self.texture_size[axis] as f64;
+            let texels = self.tile.texture_coordinate(in_tile, axis);
+            let fixed = (texels * TEXTURE_SCALE_ONE / self.scale[axis] as f64 * S10_5_ONE).round();
+            if !(i16::MIN as f64 ..= i16::MAX as f64).contains(&fixed) {
+                return None;
+            }
+            *value = fixed as i16;
+        }
+        return Some(out);
+    }
+}
+
+//
+/// The mapping for each vertex in the model's vertex store: the one of the first textured triangle
+/// (in the order the display list draws them) using it. None for verticies no textured triangle uses.
+//
+pub fn vertex_mappings (model : &Model, result : &InterpreterResult) -> Vec<Option<UvMapping>>
+{
+    let mut mappings = vec![None; model.vertex_store().verticies().len()];
+    for triangle in &result.triangles {
+        let texture = match triangle.texture.and_then(|i| model.textures().get(i)) {
+            Some(t) => t,
+            None => continue
+        };
+
+        for vertex in &triangle.verticies {
+            if let Some(mapping @ None) = mappings.get_mut(*vertex) {
+                *mapping = Some(UvMapping::from_state(&triangle.state, texture));
+            }
+        }
+    }
+    return mappings;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::dlcommands::g_settilesize::G_SETTILESIZE;
+    use crate::texture_type::TextureType;
+
+    fn p_mapping (scale : u16, size : u32, tile : TileDescriptor) -> UvMapping
+    {
+        return UvMapping { scale: [scale, scale], texture_size: [size, size], tile };
+    }
+
+    fn p_assert_near (got : [f64; 2], expected : [f64; 2])
+    {
+        assert!((got[0] - expected[0]).abs() < 1e-9 && (got[1] - expected[1]).abs() < 1e-9, "{:?} != {:?}", got, expected);
+    }
+
+    #[test]
+    fn normalizes_with_the_texture_scale ()
+    {
+        // 1024 and 512 are 32 and 16 texels in S10.5
+        let about_one = p_mapping(0xFFFF, 32, TileDescriptor::default());
+        let scale = 0xFFFF as f64 / 65536.0;
+        p_assert_near(about_one.normalize([1024, 512]), [scale, scale / 2.0]);
+
+        let half = p_mapping(0x8000, 32, TileDescriptor::default());
+        p_assert_near(half.normalize([1024, 512]), [0.5, 0.25]);
+    }
+
+    #[test]
+    fn applies_the_tile_shift_and_offset ()
+    {
+        // upper left at 2,4 texels, lower right at 7,7
+        let settilesize = G_SETTILESIZE::new([0xF2, 0x00, 0x80, 0x10, 0x01, 0x01, 0xC0, 0x1C]).unwrap();
+        let mut tile = TileDescriptor { shift: [1, 15], ..Default::default() };
+        tile.set_size(&settilesize);
+
+        let mut state = RenderState { texture_scale: [0x8000, 0x8000], texture_tile: 1, ..Default::default() };
+        state.tiles[1] = Some(tile);
+        let texture = Texture::new(0, 0, 0, TextureType::RGBA16, 8, 8);
+        let mapping = UvMapping::from_state(&state, &texture);
+        assert_eq!(mapping, p_mapping(0x8000, 8, tile));
+
+        // 4 texels: S is halved then moved back 2, T is doubled then moved back 4
+        p_assert_near(mapping.normalize([256, 256]), [0.0, 0.5]);
+    }
+
+    #[test]
+    fn keeps_negative_coordinates ()
+    {
+        let mapping = p_mapping(0x8000, 16, TileDescriptor::default());
+        p_assert_near(mapping.normalize([-256, -64]), [-0.25, -0.0625]);
+    }
+
+    #[test]
+    fn does_not_wrap_coordinates_past_the_texture ()
+    {
+        // the texture repeats past 1.0, which is where the fraction lands
+        let mapping = p_mapping(0x8000, 16, TileDescriptor::default());
+        let uv = mapping.normalize([1280, 2560]);
+        p_assert_near(uv, [1.25, 2.5]);
+        p_assert_near([uv[0].fract(), uv[1].fract()], [0.25, 0.5]);
+    }
+
+    #[test]
+    fn denormalize_round_trips ()
+    {
+        let shifted = TileDescriptor { shift: [15, 2], area: Some(([4, 8], [60, 60])), ..Default::default() };
+        let mappings = [
+            p_mapping(0xFFFF, 32, TileDescriptor::default()),
+            p_mapping(0x8000, 16, TileDescriptor::default()),
+            p_mapping(0x8000, 64, shifted)
+        ];
+
+        for mapping in mappings.iter() {
+            for uv in [[0, 0], [1, -1], [-1024, 255], [1000, -33], [4096, 2048]].iter() {
+                assert_eq!(mapping.denormalize(mapping.normalize(*uv)), Some(*uv), "{:?} {:?}", mapping, uv);
+            }
+        }
+    }
+
+    #[test]
+    fn denormalize_rejects_what_does_not_fit ()
+    {
+        let mapping = p_mapping(0x8000, 32, TileDescriptor::default());
+        assert_eq!(mapping.denormalize([100.0, 0.0]), None);
+        assert_eq!(mapping.denormalize([0.0, -100.0]), None);
+        assert_eq!(p_mapping(0, 32, TileDescriptor::default()).denormalize([0.5, 0.5]), None);
+    }
+}
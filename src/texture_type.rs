@@ -1,5 +1,10 @@
 
-#[derive(Debug,Copy,Clone)]
+//
+/// The size of the N64's texture memory in bytes
+//
+pub const TMEM_SIZE : u32 = 4096;
+
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub enum TextureType
 {
     Unknown,
@@ -26,4 +31,150 @@ impl TextureType
             _ => TextureType::Unknown
         }
     }
+
+    //
+    /// How many bits each pixel takes (0 if unknown)
+    //
+    pub fn bits_per_pixel (&self) -> u32
+    {
+        return match self {
+            TextureType::CI4 => 4,
+            TextureType::CI8 => 8,
+            TextureType::RGBA16 => 16,
+            TextureType::RGBA32 => 32,
+            TextureType::IA8 => 8,
+            TextureType::Unknown => 0
+        }
+    }
+
+    //
+    /// The size of the palette stored before the pixels, in bytes (0 if there is none)
+    //
+    pub fn palette_size (&self) -> u32
+    {
+        return match self {
+            TextureType::CI4 => 16 * 2,
+            TextureType::CI8 => 256 * 2,
+            _ => 0
+        }
+    }
+
+    //
+    /// How many bytes of TMEM a texture of this type and size takes once loaded.
+    /// TMEM is made of 64 bit lines, so every row is padded to 8 bytes.
+    //
+    pub fn tmem_size (&self, width : u32, height : u32) -> u32
+    {
+        let row_size = (width * self.bits_per_pixel()).div_ceil(8).div_ceil(8) * 8;
+        return row_size * height;
+    }
+
+    //
+    /// How much TMEM there is for the texels: 4KB, but CI textures only get the lower
+    /// 2KB since the palette (TLUT) is loaded into the upper half
+    //
+    pub fn tmem_limit (&self) -> u32
+    {
+        return if self.palette_size() != 0 { TMEM_SIZE / 2 } else { TMEM_SIZE };
+    }
+
+    //
+    /// Pick the smallest texture type which stores an 8 bit RGBA image without losing anything:
+    /// CI4 (16 colors), IA8 (grayscale with 4 bit intensity/alpha), CI8 (256 colors),
+    /// RGBA16 (5 bit color with binary alpha), and finally RGBA32.
+    //
+    pub fn smallest_lossless (rgba : &[u8]) -> TextureType
+    {
+        let expand5 = |v : u8| -> u8 { ((v >> 3) << 3) | (v >> 5) };
+        let fits_rgba5551 = rgba.chunks_exact(4).all(|p| {
+            (p[3] == 0 || p[3] == 0xFF) && p[0 .. 3].iter().all(|c| expand5(*c) == *c)
+        });
+        let fits_ia8 = rgba.chunks_exact(4).all(|p| {
+            p[0] == p[1] && p[1] == p[2] && p[0] % 0x11 == 0 && p[3] % 0x11 == 0
+        });
+
+        let mut colors = Vec::<&[u8]>::new();
+        if fits_rgba5551 {
+            for p in rgba.chunks_exact(4) {
+                if !colors.contains(&p) {
+                    colors.push(p);
+                    if colors.len() > 256 {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if fits_rgba5551 && colors.len() <= 16 {
+            return TextureType::CI4;
+        }
+        if fits_ia8 {
+            return TextureType::IA8;
+        }
+        if fits_rgba5551 && colors.len() <= 256 {
+            return TextureType::CI8;
+        }
+        if fits_rgba5551 {
+            return TextureType::RGBA16;
+        }
+        return TextureType::RGBA32;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::texture::{rgba5551_to_rgba8, Texture};
+
+    //
+    /// An image with count different colors, all of which RGBA5551 holds exactly
+    //
+    fn p_colors (count : u16) -> Vec<u8>
+    {
+        return (0 .. count).flat_map(|i| rgba5551_to_rgba8((i << 1) | 1)).collect();
+    }
+
+    #[test]
+    fn picks_the_smallest_lossless_type ()
+    {
+        assert_eq!(TextureType::smallest_lossless(&p_colors(16)), TextureType::CI4);
+        assert_eq!(TextureType::smallest_lossless(&p_colors(17)), TextureType::CI8);
+        assert_eq!(TextureType::smallest_lossless(&p_colors(256)), TextureType::CI8);
+        assert_eq!(TextureType::smallest_lossless(&p_colors(257)), TextureType::RGBA16);
+
+        // grey with partial alpha
+        assert_eq!(TextureType::smallest_lossless(&[0x11, 0x11, 0x11, 0x88, 0xFF, 0xFF, 0xFF, 0xFF]), TextureType::IA8);
+
+        // partial alpha in color, and color which needs more than 5 bits
+        assert_eq!(TextureType::smallest_lossless(&[0xFF, 0x00, 0x00, 0x80]), TextureType::RGBA32);
+        assert_eq!(TextureType::smallest_lossless(&[0x01, 0x00, 0x00, 0xFF]), TextureType::RGBA32);
+    }
+
+    #[test]
+    fn pads_tmem_rows_to_8_bytes ()
+    {
+        assert_eq!(TextureType::RGBA16.tmem_size(32, 32), 64 * 32);
+        assert_eq!(TextureType::RGBA32.tmem_size(2, 4), 8 * 4);
+        assert_eq!(TextureType::CI4.tmem_size(3, 2), 8 * 2);
+        assert_eq!(TextureType::CI8.tmem_size(9, 1), 16);
+
+        assert_eq!(TextureType::RGBA16.tmem_limit(), 4096);
+        assert_eq!(TextureType::IA8.tmem_limit(), 4096);
+        assert_eq!(TextureType::CI4.tmem_limit(), 2048);
+        assert_eq!(TextureType::CI8.tmem_limit(), 2048);
+    }
+
+    #[test]
+    fn checks_textures_fit_tmem ()
+    {
+        assert!(Texture::new(0, 0, 0, TextureType::RGBA16, 64, 32).fits_tmem());
+        assert!(!Texture::new(0, 0, 0, TextureType::RGBA16, 64, 64).fits_tmem());
+
+        // CI textures only get half, so 48x48 at 8 bits fits as IA8 but not CI8
+        assert_eq!(Texture::new(0, 0, 0, TextureType::CI8, 48, 48).tmem_size(), 2304);
+        assert!(!Texture::new(0, 0, 0, TextureType::CI8, 48, 48).fits_tmem());
+        assert!(Texture::new(0, 0, 0, TextureType::IA8, 48, 48).fits_tmem());
+        assert!(Texture::new(0, 0, 0, TextureType::CI8, 32, 64).fits_tmem());
+    }
 }
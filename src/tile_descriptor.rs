@@ -0,0 +1,153 @@
+use crate::dlcommands::g_settile::G_SETTILE;
+use crate::dlcommands::g_settilesize::G_SETTILESIZE;
+
+//
+/// The number of tile descriptors in the RDP
+//
+pub const TILE_COUNT : usize = 8;
+
+//
+/// Clamp/mirror flag: mirror every other repeat
+//
+pub const G_TX_MIRROR : u8 = 0x01;
+
+//
+/// Clamp/mirror flag: clamp to the edge of the tile
+//
+pub const G_TX_CLAMP : u8 = 0x02;
+
+//
+/// A tile descriptor: how the RDP reads a texture out of TMEM.
+/// Set by G_SETTILE (format and wrapping) and G_SETTILESIZE (the area covered).
+///
+/// Coordinates are in texels, for S (horizontal) and T (vertical).
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TileDescriptor
+{
+    //
+    /// The size of a row in TMEM, in 64 bit words
+    //
+    pub line: u16,
+
+    //
+    /// Where the tile starts in TMEM, in 64 bit words
+    //
+    pub tmem_address: u16,
+
+    //
+    /// Clamp/mirror flags (G_TX_CLAMP, G_TX_MIRROR) for S and T
+    //
+    pub clamp_mirror: [u8; 2],
+
+    //
+    /// Wrap size as a power of 2 for S and T (0 wraps nothing)
+    //
+    pub mask: [u8; 2],
+
+    //
+    /// Coordinate shift for S and T (1-10 divides, 11-15 multiplies)
+    //
+    pub shift: [u8; 2],
+
+    //
+    /// The upper left and lower right corners (10.2 fixed point), if G_SETTILESIZE has set them
+    //
+    pub area: Option<([u16; 2], [u16; 2])>
+}
+
+impl TileDescriptor
+{
+    //
+    /// Update the descriptor with a G_SETTILE (the area is kept)
+    //
+    pub fn set_tile (&mut self, settile : &G_SETTILE)
+    {
+        self.line = settile.line();
+        self.tmem_address = settile.tmem_address();
+        self.clamp_mirror = settile.clamp_mirror();
+        self.mask = settile.mask();
+        self.shift = settile.shift();
+    }
+
+    //
+    /// Update the descriptor with a G_SETTILESIZE
+    //
+    pub fn set_size (&mut self, settilesize : &G_SETTILESIZE)
+    {
+        self.area = Some((settilesize.upper_left(), settilesize.lower_right()));
+    }
+
+    //
+    /// The width and height of the tile in texels (None if G_SETTILESIZE hasnt set them)
+    //
+    pub fn size (&self) -> Option<[u32; 2]>
+    {
+        let (upper_left, lower_right) = self.area?;
+        return Some([
+            (lower_right[0].saturating_sub(upper_left[0]) / 4) as u32 + 1,
+            (lower_right[1].saturating_sub(upper_left[1]) / 4) as u32 + 1
+        ]);
+    }
+
+    //
+    /// Turn a texture coordinate (in texels, after the G_TEXTURE scale) into a coordinate in the tile:
+    /// apply the shift and move it relative to the tile's upper left corner.
+    //
+    pub fn tile_coordinate (&self, coordinate : f64, axis : usize) -> f64
+    {
+        let shift = self.shift[axis];
+        let shifted = match shift {
+            0 => coordinate,
+            1 ..= 10 => coordinate / (1u32 << shift) as f64,
+            _ => coordinate * (1u32 << (16 - shift as u32)) as f64
+        };
+
+        let upper_left = self.area.map(|(upper_left, _)| upper_left[axis] as f64 / 4.0).unwrap_or(0.0);
+        return shifted - upper_left;
+    }
+
+    //
+    /// The reverse of tile_coordinate: turn a coordinate in the tile back into a texture coordinate (in texels)
+    //
+    pub fn texture_coordinate (&self, tile_coordinate : f64, axis : usize) -> f64
+    {
+        let upper_left = self.area.map(|(upper_left, _)| upper_left[axis] as f64 / 4.0).unwrap_or(0.0);
+        let shifted = tile_coordinate + upper_left;
+
+        let shift = self.shift[axis];
+        return match shift {
+            0 => shifted,
+            1 ..= 10 => shifted * (1u32 << shift) as f64,
+            _ => shifted / (1u32 << (16 - shift as u32)) as f64
+        };
+    }
+
+    //
+    /// Clamp, wrap and mirror a whole texel coordinate in the tile the way the RDP does.
+    /// Clamping happens when it is turned on, or when there is no mask to wrap with.
+    //
+    pub fn wrap_texel (&self, texel : i64, axis : usize) -> i64
+    {
+        let mut texel = texel;
+        let mask = self.mask[axis];
+        let flags = self.clamp_mirror[axis];
+
+        if flags & G_TX_CLAMP != 0 || mask == 0 {
+            if let Some(size) = self.size() {
+                texel = texel.clamp(0, size[axis] as i64 - 1);
+            }
+        }
+
+        if mask != 0 {
+            let wrap_size = 1i64 << mask;
+            let repeat = texel.div_euclid(wrap_size);
+            texel = texel.rem_euclid(wrap_size);
+            if flags & G_TX_MIRROR != 0 && repeat % 2 != 0 {
+                texel = wrap_size - 1 - texel;
+            }
+        }
+
+        return texel;
+    }
+}
@@ -0,0 +1,590 @@
+use crate::display_list_interpreter::{InterpreterResult, VertexKind};
+use crate::errors::{self, Result};
+use crate::model::Model;
+use crate::segment_table;
+use crate::texture_coordinates;
+use crate::vertex_store::Vertex;
+
+use std::fmt::Write;
+
+// Listing the vertex store for view-vertex-store: as text, csv or json, with filtering
+// and sorting for searching through the thousands of verticies in level models.
+
+//
+/// How to write the listing
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ReportFormat
+{
+    //
+    /// One line per vertex, with the vertex store header as comments
+    //
+    Text,
+
+    //
+    /// A header row, then one row per vertex
+    //
+    Csv,
+
+    //
+    /// An array with an object per vertex
+    //
+    Json
+}
+
+impl ReportFormat
+{
+    //
+    /// Parse a format name from the command line (text, csv, json)
+    //
+    pub fn from_name (name : &str) -> Option<ReportFormat>
+    {
+        return match name.to_lowercase().as_str() {
+            "text" => Some(ReportFormat::Text),
+            "csv" => Some(ReportFormat::Csv),
+            "json" => Some(ReportFormat::Json),
+            _ => None
+        };
+    }
+}
+
+//
+/// What to put in the listing. csv and json always have the index.
+//
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReportOptions
+{
+    pub format: ReportFormat,
+
+    //
+    /// Show each vertex's index in the vertex store (text)
+    //
+    pub indices: bool,
+
+    //
+    /// Show the UVs normalized to the texture the vertex is drawn with
+    //
+    pub normalized_uvs: bool,
+
+    //
+    /// Show which triangles (in the order the display list draws them) use each vertex
+    //
+    pub triangles: bool,
+
+    //
+    /// Only verticies with an index in this range (inclusive)
+    //
+    pub range: Option<(usize, usize)>,
+
+    //
+    /// Only verticies inside this box (smallest and largest corner, inclusive)
+    //
+    pub within: Option<([i16; 3], [i16; 3])>,
+
+    //
+    /// Only verticies the display list uses this way (both is its own kind)
+    //
+    pub kind: Option<VertexKind>,
+
+    //
+    /// Sort by position (x, then y, then z) instead of by index
+    //
+    pub sort: bool
+}
+
+//
+/// Everything listed about one vertex
+//
+struct VertexRow<'a>
+{
+    index: usize,
+    vertex: &'a Vertex,
+    kind: VertexKind,
+    normalized_uv: Option<[f64; 2]>,
+    triangles: Vec<usize>
+}
+
+//
+/// List the verticies in the model's vertex store. result is from running the model's display
+/// list, which decides if each vertex has a color or a normal, and which texture it is drawn with.
+//
+pub fn report (model : &Model, result : &InterpreterResult, options : &ReportOptions) -> String
+{
+    let verticies = model.vertex_store().verticies();
+
+    let mut triangles = vec![Vec::<usize>::new(); verticies.len()];
+    for (i, triangle) in result.triangles.iter().enumerate() {
+        for vertex in &triangle.verticies {
+            if let Some(list) = triangles.get_mut(*vertex) {
+                if !list.contains(&i) {
+                    list.push(i);
+                }
+            }
+        }
+    }
+
+    let mappings = texture_coordinates::vertex_mappings(model, result);
+
+    let mut rows = Vec::<VertexRow>::new();
+    for (index, vertex) in verticies.iter().enumerate() {
+        if let Some((first, last)) = options.range {
+            if index < first || index > last {
+                continue;
+            }
+        }
+        if let Some((min, max)) = options.within {
+            if (0 .. 3).any(|axis| vertex.pos[axis] < min[axis] || vertex.pos[axis] > max[axis]) {
+                continue;
+            }
+        }
+
+        let kind = result.vertex_kinds.get(index).copied().unwrap_or(VertexKind::Unused);
+        if options.kind.is_some() && options.kind != Some(kind) {
+            continue;
+        }
+
+        let normalized_uv = mappings[index].map(|mapping| mapping.normalize(vertex.uv));
+
+        rows.push(VertexRow {
+            index,
+            vertex,
+            kind,
+            normalized_uv,
+            triangles: std::mem::take(&mut triangles[index])
+        });
+    }
+
+    if options.sort {
+        rows.sort_by_key(|r| (r.vertex.pos[0], r.vertex.pos[1], r.vertex.pos[2], r.index));
+    }
+
+    return match options.format {
+        ReportFormat::Text => p_text(model, &rows, options),
+        ReportFormat::Csv => p_csv(&rows, options),
+        ReportFormat::Json => p_json(&rows, options)
+    };
+}
+
+//
+/// Parse an index range from the command line: first-last (inclusive), or a single index
+//
+pub fn parse_range (text : &str) -> Option<(usize, usize)>
+{
+    let (first, last) = text.split_once('-').unwrap_or((text, text));
+    let (first, last) = (segment_table::parse_number(first)? as usize, segment_table::parse_number(last)? as usize);
+    return Some((first.min(last), first.max(last)));
+}
+
+//
+/// Parse a box from the command line: x1,y1,z1,x2,y2,z2 (any two opposite corners)
+//
+pub fn parse_box (text : &str) -> Option<([i16; 3], [i16; 3])>
+{
+    let values : Vec<i16> = text.split(',').map(|v| v.trim().parse::<i16>().ok()).collect::<Option<Vec<i16>>>()?;
+    if values.len() != 6 {
+        return None;
+    }
+
+    let mut min = [0i16; 3];
+    let mut max = [0i16; 3];
+    for axis in 0 .. 3 {
+        min[axis] = values[axis].min(values[axis + 3]);
+        max[axis] = values[axis].max(values[axis + 3]);
+    }
+    return Some((min, max));
+}
+
+//
+/// Parse a vertex kind from the command line (unused, colored, lit, both)
+//
+pub fn parse_kind (text : &str) -> Option<VertexKind>
+{
+    let text = text.to_lowercase();
+    return [VertexKind::Unused, VertexKind::Colored, VertexKind::Lit, VertexKind::Both].iter()
+        .copied()
+        .find(|kind| p_kind_name(*kind) == text);
+}
+
+//
+/// Read the normalized UVs back out of a csv listing (--format csv --normalized-uvs), ex. after
+/// editing them in a spreadsheet. Rows with the normalized UV columns empty are skipped.
+///
+/// Returns the vertex index and normalized UV of each row.
+//
+pub fn parse_uv_csv (text : &str) -> Result<Vec<(usize, [f64; 2])>>
+{
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+
+    let header : Vec<&str> = match lines.next() {
+        Some((_, line)) => line.split(',').map(|c| c.trim()).collect(),
+        None => return errors::InvalidCsv { line: 1usize, reason: "there is no header row".to_string() }.fail()
+    };
+    let column = |name : &str| -> Result<usize> {
+        return match header.iter().position(|c| *c == name) {
+            Some(i) => Ok(i),
+            None => errors::InvalidCsv { line: 1usize, reason: format!("there is no {} column", name) }.fail()
+        };
+    };
+    let (index_column, u_column, v_column) = (column("index")?, column("normalized_u")?, column("normalized_v")?);
+
+    let mut uvs = Vec::new();
+    for (i, line) in lines {
+        let cells : Vec<&str> = line.split(',').map(|c| c.trim()).collect();
+        let cell = |column : usize| -> &str { cells.get(column).copied().unwrap_or("") };
+
+        if cell(u_column).is_empty() && cell(v_column).is_empty() {
+            continue;
+        }
+
+        let index = match cell(index_column).parse::<usize>() {
+            Ok(index) => index,
+            Err(_) => return errors::InvalidCsv { line: i + 1, reason: format!("'{}' is not a vertex index", cell(index_column)) }.fail()
+        };
+        let mut uv = [0.0; 2];
+        for (axis, column) in [u_column, v_column].iter().enumerate() {
+            uv[axis] = match cell(*column).parse::<f64>() {
+                Ok(value) if value.is_finite() => value,
+                _ => return errors::InvalidCsv { line: i + 1, reason: format!("'{}' is not a normalized uv", cell(*column)) }.fail()
+            };
+        }
+        uvs.push((index, uv));
+    }
+
+    return Ok(uvs);
+}
+
+// --- formats
+
+fn p_text (model : &Model, rows : &[VertexRow], options : &ReportOptions) -> String
+{
+    let mut out = String::new();
+    let bounds = model.vertex_store().bounds();
+
+    writeln!(out, "# draw distance: negative:[{},{},{}] positive:[{},{},{}]",
+        bounds.draw_distance_negative[0], bounds.draw_distance_negative[1], bounds.draw_distance_negative[2],
+        bounds.draw_distance_positive[0], bounds.draw_distance_positive[1], bounds.draw_distance_positive[2]
+    ).unwrap();
+    writeln!(out, "# object coordinate range: [{},{}]",
+        bounds.object_coordinate_range[0], bounds.object_coordinate_range[1]
+    ).unwrap();
+    writeln!(out, "# collision range: objects:{} banjo:{}",
+        bounds.object_collision_range, bounds.banjo_collision_range
+    ).unwrap();
+    writeln!(out).unwrap();
+
+    for row in rows {
+        let v = row.vertex;
+        if options.indices {
+            write!(out, "{}: ", row.index).unwrap();
+        }
+
+        // G_LIGHTING decides if rgb_or_norm is a color or a normal
+        let shading = match row.kind {
+            VertexKind::Colored => p_describe_color(v),
+            VertexKind::Lit => p_describe_normal(v),
+            VertexKind::Both => format!("{} {}", p_describe_color(v), p_describe_normal(v)),
+            VertexKind::Unused => format!("rgborn:[{},{},{}] alpha:{} (unused)",
+                v.rgb_or_norm[0], v.rgb_or_norm[1], v.rgb_or_norm[2], v.alpha
+            )
+        };
+
+        write!(out, "pos:[{},{},{}] uv:[{},{}]", v.pos[0], v.pos[1], v.pos[2], v.uv[0], v.uv[1]).unwrap();
+        if options.normalized_uvs {
+            match row.normalized_uv {
+                Some(uv) => write!(out, " normalizedUv:[{:.4},{:.4}]", uv[0], uv[1]).unwrap(),
+                None => write!(out, " normalizedUv:none").unwrap()
+            }
+        }
+        write!(out, " {} flag:{:#X}", shading, v.flag).unwrap();
+        if options.triangles {
+            let list : Vec<String> = row.triangles.iter().map(|t| t.to_string()).collect();
+            write!(out, " triangles:[{}]", list.join(",")).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+
+    writeln!(out).unwrap();
+    if rows.len() == model.vertex_store().verticies().len() {
+        writeln!(out, "# total length: {} verts", rows.len()).unwrap();
+    } else {
+        writeln!(out, "# listed {} of {} verts", rows.len(), model.vertex_store().verticies().len()).unwrap();
+    }
+    return out;
+}
+
+fn p_csv (rows : &[VertexRow], options : &ReportOptions) -> String
+{
+    let mut out = String::new();
+
+    write!(out, "index,x,y,z,u,v,kind,r,g,b,alpha,nx,ny,nz,flag").unwrap();
+    if options.normalized_uvs {
+        write!(out, ",normalized_u,normalized_v").unwrap();
+    }
+    if options.triangles {
+        write!(out, ",triangles").unwrap();
+    }
+    writeln!(out).unwrap();
+
+    for row in rows {
+        let v = row.vertex;
+
+        // the color columns are only filled for colored verticies, the normal ones for lit verticies
+        let colored = matches!(row.kind, VertexKind::Colored | VertexKind::Both | VertexKind::Unused);
+        let lit = matches!(row.kind, VertexKind::Lit | VertexKind::Both);
+        let color = if colored { format!("{},{},{}", v.rgb_or_norm[0], v.rgb_or_norm[1], v.rgb_or_norm[2]) } else { ",,".to_string() };
+        let normal = v.normal();
+        let normal = if lit { format!("{:.4},{:.4},{:.4}", normal[0], normal[1], normal[2]) } else { ",,".to_string() };
+
+        write!(out, "{},{},{},{},{},{},{},{},{},{},{}",
+            row.index, v.pos[0], v.pos[1], v.pos[2], v.uv[0], v.uv[1],
+            p_kind_name(row.kind), color, v.alpha, normal, v.flag
+        ).unwrap();
+        if options.normalized_uvs {
+            match row.normalized_uv {
+                Some(uv) => write!(out, ",{:.4},{:.4}", uv[0], uv[1]).unwrap(),
+                None => write!(out, ",,").unwrap()
+            }
+        }
+        if options.triangles {
+            // space separated so it stays one column
+            let list : Vec<String> = row.triangles.iter().map(|t| t.to_string()).collect();
+            write!(out, ",{}", list.join(" ")).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+
+    return out;
+}
+
+fn p_json (rows : &[VertexRow], options : &ReportOptions) -> String
+{
+    let mut out = String::new();
+
+    writeln!(out, "[").unwrap();
+    for (i, row) in rows.iter().enumerate() {
+        let v = row.vertex;
+        let mut fields = vec![
+            format!("\"index\": {}", row.index),
+            format!("\"position\": [{}, {}, {}]", v.pos[0], v.pos[1], v.pos[2]),
+            format!("\"uv\": [{}, {}]", v.uv[0], v.uv[1]),
+            format!("\"kind\": \"{}\"", p_kind_name(row.kind))
+        ];
+
+        if matches!(row.kind, VertexKind::Colored | VertexKind::Both | VertexKind::Unused) {
+            let color = v.color();
+            fields.push(format!("\"color\": [{}, {}, {}, {}]", color[0], color[1], color[2], color[3]));
+        }
+        if matches!(row.kind, VertexKind::Lit | VertexKind::Both) {
+            let normal = v.normal();
+            fields.push(format!("\"normal\": [{:.4}, {:.4}, {:.4}]", normal[0], normal[1], normal[2]));
+            fields.push(format!("\"alpha\": {}", v.alpha));
+        }
+        fields.push(format!("\"flag\": {}", v.flag));
+
+        if options.normalized_uvs {
+            match row.normalized_uv {
+                Some(uv) => fields.push(format!("\"normalized_uv\": [{:.4}, {:.4}]", uv[0], uv[1])),
+                None => fields.push("\"normalized_uv\": null".to_string())
+            }
+        }
+        if options.triangles {
+            let list : Vec<String> = row.triangles.iter().map(|t| t.to_string()).collect();
+            fields.push(format!("\"triangles\": [{}]", list.join(", ")));
+        }
+
+        let separator = if i + 1 < rows.len() { "," } else { "" };
+        writeln!(out, "  {{ {} }}{}", fields.join(", "), separator).unwrap();
+    }
+    writeln!(out, "]").unwrap();
+
+    return out;
+}
+
+// --- helpers
+
+fn p_kind_name (kind : VertexKind) -> &'static str
+{
+    return match kind {
+        VertexKind::Unused => "unused",
+        VertexKind::Colored => "colored",
+        VertexKind::Lit => "lit",
+        VertexKind::Both => "both"
+    };
+}
+
+//
+/// A vertex's rgb_or_norm and alpha as a color
+//
+fn p_describe_color (v : &Vertex) -> String
+{
+    let color = v.color();
+    return format!("color:[{},{},{},{}]", color[0], color[1], color[2], color[3]);
+}
+
+//
+/// A vertex's rgb_or_norm as a normal, and its alpha
+//
+fn p_describe_normal (v : &Vertex) -> String
+{
+    let normal = v.normal();
+    return format!("normal:[{:.3},{:.3},{:.3}] alpha:{}", normal[0], normal[1], normal[2], v.alpha);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::display_list_interpreter::DisplayListInterpreter;
+    use crate::geometry_mode;
+    use crate::test_models::*;
+
+    //
+    /// Verticies 0 and 1 colored, 2 lit, 3 both and 4 unused.
+    /// Triangle 0 uses 0, 1 and 2 and triangle 1 uses 2 and 3.
+    //
+    fn p_model () -> Model
+    {
+        let mut builder = ModelBuilder::new();
+        for position in [[0, 0, 0], [10, -5, 3], [-10, 5, 0], [10, -5, -3], [0, 0, 0]] {
+            builder.vertex(position, [0, 0], [0x00, 0x7F, 0x00], 0xFF);
+        }
+        builder.command(g_vtx(0, 2, vertex_address(0)));
+        builder.command(g_vtx(2, 1, vertex_address(3)));
+        builder.command(g_setgeometrymode(geometry_mode::G_LIGHTING));
+        builder.command(g_vtx(3, 2, vertex_address(2)));
+        builder.command(g_tri2([0, 1, 3], [3, 4, 2]));
+        return Model::from_bytes(builder.build()).unwrap();
+    }
+
+    fn p_options (format : ReportFormat) -> ReportOptions
+    {
+        return ReportOptions { format, indices: false, normalized_uvs: false, triangles: false, range: None, within: None, kind: None, sort: false };
+    }
+
+    //
+    /// The indices listed, in order
+    //
+    fn p_listed (options : &ReportOptions) -> Vec<usize>
+    {
+        let model = p_model();
+        let result = DisplayListInterpreter::run(&model);
+        let csv = report(&model, &result, &ReportOptions { format: ReportFormat::Csv, ..*options });
+        return csv.lines().skip(1).map(|line| line.split(',').next().unwrap().parse::<usize>().unwrap()).collect();
+    }
+
+    #[test]
+    fn filters_by_index_range ()
+    {
+        let options = p_options(ReportFormat::Csv);
+        assert_eq!(p_listed(&options), [0, 1, 2, 3, 4]);
+        assert_eq!(p_listed(&ReportOptions { range: Some((1, 3)), ..options }), [1, 2, 3]);
+        assert_eq!(p_listed(&ReportOptions { range: Some((3, 3)), ..options }), [3]);
+        assert_eq!(p_listed(&ReportOptions { range: Some((5, 9)), ..options }), []);
+
+        assert_eq!(parse_range("3-1"), Some((1, 3)));
+        assert_eq!(parse_range("0x10"), Some((16, 16)));
+        assert_eq!(parse_range("a-b"), None);
+    }
+
+    #[test]
+    fn filters_by_box ()
+    {
+        // verticies on the corners and faces are inside
+        let options = p_options(ReportFormat::Csv);
+        let within = parse_box("10,-5,3,0,0,0").unwrap();
+        assert_eq!(within, ([0, -5, 0], [10, 0, 3]));
+        assert_eq!(p_listed(&ReportOptions { within: Some(within), ..options }), [0, 1, 4]);
+        assert_eq!(p_listed(&ReportOptions { within: Some(([20, 20, 20], [30, 30, 30])), ..options }), []);
+
+        assert_eq!(parse_box("1,2,3"), None);
+        assert_eq!(parse_box("1,2,3,4,5,x"), None);
+    }
+
+    #[test]
+    fn filters_by_kind ()
+    {
+        let options = p_options(ReportFormat::Csv);
+        assert_eq!(p_listed(&ReportOptions { kind: Some(VertexKind::Colored), ..options }), [0, 1]);
+        assert_eq!(p_listed(&ReportOptions { kind: Some(VertexKind::Lit), ..options }), [2]);
+        assert_eq!(p_listed(&ReportOptions { kind: Some(VertexKind::Both), ..options }), [3]);
+        assert_eq!(p_listed(&ReportOptions { kind: Some(VertexKind::Unused), ..options }), [4]);
+
+        // filters combine
+        assert_eq!(p_listed(&ReportOptions { kind: Some(VertexKind::Colored), range: Some((1, 4)), ..options }), [1]);
+        assert_eq!(p_listed(&ReportOptions { kind: Some(VertexKind::Lit), range: Some((3, 4)), ..options }), []);
+
+        assert_eq!(parse_kind("LIT"), Some(VertexKind::Lit));
+        assert_eq!(parse_kind("both"), Some(VertexKind::Both));
+        assert_eq!(parse_kind("normal"), None);
+    }
+
+    #[test]
+    fn sorts_by_position_then_index ()
+    {
+        let options = ReportOptions { sort: true, ..p_options(ReportFormat::Csv) };
+        assert_eq!(p_listed(&options), [2, 0, 4, 3, 1]);
+        assert_eq!(p_listed(&ReportOptions { range: Some((0, 3)), ..options }), [2, 0, 3, 1]);
+    }
+
+    #[test]
+    fn writes_csv_rows_the_shape_of_the_header ()
+    {
+        let model = p_model();
+        let result = DisplayListInterpreter::run(&model);
+        let options = ReportOptions { normalized_uvs: true, triangles: true, ..p_options(ReportFormat::Csv) };
+        let csv = report(&model, &result, &options);
+
+        let rows : Vec<Vec<&str>> = csv.lines().map(|line| line.split(',').collect()).collect();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0].join(","), "index,x,y,z,u,v,kind,r,g,b,alpha,nx,ny,nz,flag,normalized_u,normalized_v,triangles");
+        for row in &rows {
+            assert_eq!(row.len(), rows[0].len(), "{:?}", row);
+        }
+
+        // colors for colored verticies, normals for lit ones, no uvs without a texture, and the triangles space separated
+        assert_eq!(rows[1].join(","), "0,0,0,0,0,0,colored,0,127,0,255,,,,0,,,0");
+        assert_eq!(rows[3].join(","), "2,-10,5,0,0,0,lit,,,,255,0.0000,1.0000,0.0000,0,,,0 1");
+        assert_eq!(rows[4].join(","), "3,10,-5,-3,0,0,both,0,127,0,255,0.0000,1.0000,0.0000,0,,,1");
+        assert_eq!(rows[5].join(","), "4,0,0,0,0,0,unused,0,127,0,255,,,,0,,,");
+
+        let empty = report(&model, &result, &ReportOptions { range: Some((9, 9)), ..options });
+        assert_eq!(empty.lines().count(), 1);
+    }
+
+    #[test]
+    fn writes_a_json_array ()
+    {
+        let model = p_model();
+        let result = DisplayListInterpreter::run(&model);
+        let options = ReportOptions { normalized_uvs: true, triangles: true, ..p_options(ReportFormat::Json) };
+        let json = report(&model, &result, &ReportOptions { range: Some((2, 3)), ..options });
+
+        assert_eq!(json,
+            "[\n\
+             \x20 { \"index\": 2, \"position\": [-10, 5, 0], \"uv\": [0, 0], \"kind\": \"lit\", \"normal\": [0.0000, 1.0000, 0.0000], \"alpha\": 255, \"flag\": 0, \"normalized_uv\": null, \"triangles\": [0, 1] },\n\
+             \x20 { \"index\": 3, \"position\": [10, -5, -3], \"uv\": [0, 0], \"kind\": \"both\", \"color\": [0, 127, 0, 255], \"normal\": [0.0000, 1.0000, 0.0000], \"alpha\": 255, \"flag\": 0, \"normalized_uv\": null, \"triangles\": [1] }\n\
+             ]\n"
+        );
+
+        assert_eq!(report(&model, &result, &ReportOptions { range: Some((9, 9)), ..options }), "[\n]\n");
+    }
+
+    #[test]
+    fn reads_normalized_uvs_from_a_listing ()
+    {
+        let csv = "index,x,y,z,u,v,kind,r,g,b,alpha,nx,ny,nz,flag,normalized_u,normalized_v,triangles\n\
+                   0,-100,-100,0,0,0,colored,255,0,0,255,,,,0,0.0000,0.0000,0\n\
+                   1,100,-100,0,256,0,lit,,,,255,0.0000,0.0000,1.0000,0,,,\n\
+                   7,100,100,0,256,256,colored,0,0,255,255,,,,0,-0.5000,1.2500,0 1\n";
+        assert_eq!(parse_uv_csv(csv).unwrap(), vec![(0, [0.0, 0.0]), (7, [-0.5, 1.25])]);
+    }
+
+    #[test]
+    fn rejects_uv_csvs_it_cannot_read ()
+    {
+        assert!(parse_uv_csv("").is_err());
+        assert!(parse_uv_csv("index,u,v\n0,1,2\n").is_err());
+        assert!(parse_uv_csv("index,normalized_u,normalized_v\nx,0.5,0.5\n").is_err());
+        assert!(parse_uv_csv("index,normalized_u,normalized_v\n0,0.5,nan\n").is_err());
+    }
+}
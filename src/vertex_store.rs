@@ -1,4 +1,3 @@
-
 //
 /// Single vertex
 //
@@ -12,11 +11,63 @@ pub struct Vertex
     pub alpha: u8 // alpha
 }
 
+impl Vertex
+{
+    //
+    /// The normal, for a vertex drawn with G_LIGHTING on: rgb_or_norm as signed bytes, normalized.
+    /// [0, 0, 0] if all of them are 0.
+    //
+    pub fn normal (&self) -> [f64; 3]
+    {
+        let n = [self.rgb_or_norm[0] as i8 as f64, self.rgb_or_norm[1] as i8 as f64, self.rgb_or_norm[2] as i8 as f64];
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        if length == 0.0 {
+            return [0.0; 3];
+        }
+        return [n[0] / length, n[1] / length, n[2] / length];
+    }
+
+    //
+    /// The RGBA color, for a vertex drawn with G_LIGHTING off
+    //
+    pub fn color (&self) -> [u8; 4]
+    {
+        return [self.rgb_or_norm[0], self.rgb_or_norm[1], self.rgb_or_norm[2], self.alpha];
+    }
+}
+
+//
+/// The culling/collision values from the vertex store header.
+///
+/// Layout in the file (all big endian):
+/// [aaaa bbbb cccc] [dddd eeee ffff] [gggg hhhh] [iiii] [jjjj]
+/// a-c: draw distance - negative coords (x, y, z)
+/// d-f: draw distance - positive coords (x, y, z)
+/// g-h: object coordinate range (smallest, largest)
+/// i: collision range (enemies/objects)
+/// j: collision range (banjo)
+//
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct VertexStoreBounds
+{
+    pub draw_distance_negative: [i16; 3], // x, y, z
+    pub draw_distance_positive: [i16; 3], // x, y, z
+    pub object_coordinate_range: [i16; 2], // smallest, largest
+    pub object_collision_range: u16, // enemies/objects
+    pub banjo_collision_range: u16 // banjo
+}
+
+//
+/// The size of the vertex store header (bounds + vertex count)
+//
+pub const VERTEX_STORE_HEADER_SIZE : usize = 0x16;
+
 //
 /// Stores verticies
 //
 pub struct VertexStore
 {
+    m_bounds: VertexStoreBounds,
     m_verticies: Vec<Vertex>
 }
 
@@ -28,6 +79,7 @@ impl VertexStore
     pub fn new () -> VertexStore
     {
         return VertexStore {
+            m_bounds: VertexStoreBounds::default(),
             m_verticies: Vec::new()
         }
     }
@@ -44,4 +96,162 @@ impl VertexStore
     //
     pub fn verticies (&self) ->  &Vec<Vertex>
     { return &self.m_verticies; }
-}
\ No newline at end of file
+
+    //
+    /// Change a vertex's texture coordinates. False if there is no vertex at index.
+    //
+    pub fn set_uv (&mut self, index : usize, uv : [i16; 2]) -> bool
+    {
+        return match self.m_verticies.get_mut(index) {
+            Some(vertex) => {
+                vertex.uv = uv;
+                true
+            },
+            None => false
+        };
+    }
+
+    //
+    /// The culling/collision values from the header
+    //
+    pub fn bounds (&self) -> VertexStoreBounds
+    { return self.m_bounds; }
+
+    //
+    /// Replace the culling/collision values
+    //
+    pub fn set_bounds (&mut self, bounds: VertexStoreBounds)
+    { self.m_bounds = bounds; }
+
+    //
+    /// Derive the culling/collision values from the verticies in the store.
+    ///
+    /// - The draw distance is the axis aligned box around every vertex
+    /// - The object coordinate range is the smallest/largest coordinate on any axis
+    /// - The object collision range is the radius of the sphere around the center of the box
+    /// - The banjo collision range is the radius of the sphere around the model origin
+    ///
+    /// An empty store resets everything to 0.
+    //
+    pub fn recompute_bounds (&mut self)
+    {
+        if self.m_verticies.is_empty() {
+            self.m_bounds = VertexStoreBounds::default();
+            return;
+        }
+
+        let mut negative = [i16::MAX; 3];
+        let mut positive = [i16::MIN; 3];
+        for v in &self.m_verticies {
+            for axis in 0..3 {
+                negative[axis] = negative[axis].min(v.pos[axis]);
+                positive[axis] = positive[axis].max(v.pos[axis]);
+            }
+        }
+
+        let smallest = *negative.iter().min().unwrap();
+        let largest = *positive.iter().max().unwrap();
+
+        let center = [
+            (negative[0] as f64 + positive[0] as f64) / 2.0,
+            (negative[1] as f64 + positive[1] as f64) / 2.0,
+            (negative[2] as f64 + positive[2] as f64) / 2.0
+        ];
+
+        let mut object_radius_sq = 0.0f64;
+        let mut origin_radius_sq = 0.0f64;
+        for v in &self.m_verticies {
+            let mut from_center = 0.0;
+            let mut from_origin = 0.0;
+            for (p, c) in v.pos.iter().zip(center.iter()) {
+                let p = *p as f64;
+                from_center += (p - c) * (p - c);
+                from_origin += p * p;
+            }
+            object_radius_sq = object_radius_sq.max(from_center);
+            origin_radius_sq = origin_radius_sq.max(from_origin);
+        }
+
+        self.m_bounds = VertexStoreBounds {
+            draw_distance_negative: negative,
+            draw_distance_positive: positive,
+            object_coordinate_range: [smallest, largest],
+            object_collision_range: p_radius_to_u16(object_radius_sq),
+            banjo_collision_range: p_radius_to_u16(origin_radius_sq)
+        };
+    }
+}
+
+//
+/// Convert a squared radius to the rounded up u16 stored in the header
+//
+fn p_radius_to_u16 (radius_sq : f64) -> u16
+{
+    return radius_sq.sqrt().ceil().min(u16::MAX as f64) as u16;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn p_store (positions : &[[i16; 3]]) -> VertexStore
+    {
+        let mut store = VertexStore::new();
+        for pos in positions {
+            store.add(Vertex { pos: *pos, flag: 0, uv: [0, 0], rgb_or_norm: [0; 3], alpha: 0xFF });
+        }
+        return store;
+    }
+
+    #[test]
+    fn recomputes_bounds_from_the_verticies ()
+    {
+        let mut store = p_store(&[[-10, 0, 5], [30, 20, -5], [0, -40, 15]]);
+        store.recompute_bounds();
+
+        // the box is (-10, -40, -5) to (30, 20, 15), centered on (10, -10, 5). The second vertex is
+        // furthest from the center (sqrt 1400 = 37.4) and the third from the origin (sqrt 1825 = 42.7)
+        assert_eq!(store.bounds(), VertexStoreBounds {
+            draw_distance_negative: [-10, -40, -5],
+            draw_distance_positive: [30, 20, 15],
+            object_coordinate_range: [-40, 30],
+            object_collision_range: 38,
+            banjo_collision_range: 43
+        });
+    }
+
+    #[test]
+    fn a_single_vertex_has_no_object_radius ()
+    {
+        let mut store = p_store(&[[3, 4, 0]]);
+        store.recompute_bounds();
+
+        let bounds = store.bounds();
+        assert_eq!((bounds.draw_distance_negative, bounds.draw_distance_positive), ([3, 4, 0], [3, 4, 0]));
+        assert_eq!(bounds.object_coordinate_range, [0, 4]);
+        assert_eq!((bounds.object_collision_range, bounds.banjo_collision_range), (0, 5));
+    }
+
+    #[test]
+    fn covers_the_whole_coordinate_range ()
+    {
+        let mut store = p_store(&[[i16::MIN, i16::MIN, i16::MIN], [i16::MAX, i16::MAX, i16::MAX]]);
+        store.recompute_bounds();
+
+        // the center is -0.5 on each axis: sqrt(3) * 32767.5 = 56754.9 and sqrt(3) * 32768 = 56755.8
+        let bounds = store.bounds();
+        assert_eq!(bounds.object_coordinate_range, [i16::MIN, i16::MAX]);
+        assert_eq!(bounds.object_collision_range, 56755);
+        assert_eq!(bounds.banjo_collision_range, 56756);
+    }
+
+    #[test]
+    fn an_empty_store_resets_the_bounds ()
+    {
+        let mut store = VertexStore::new();
+        store.set_bounds(VertexStoreBounds { object_collision_range: 100, banjo_collision_range: 200, ..Default::default() });
+        store.recompute_bounds();
+        assert_eq!(store.bounds(), VertexStoreBounds::default());
+    }
+}